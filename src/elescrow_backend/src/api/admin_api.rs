@@ -9,20 +9,60 @@ use crate::types::{
     transaction::{Transaction, DisputeResolution},
 };
 
-use crate::{USER_SERVICE, TRANSACTION_SERVICE, AUDIT_LOGGER};
+use crate::types::ledger::LedgerConfig;
+use crate::types::role::{Role, Permission, RoleAssignment};
+use crate::types::rate_limit::{EndpointClass, RateLimitConfig, RateLimitBlock};
+use crate::types::guard::{GuardPolicy, Operation};
+use crate::types::{transaction::Currency, user::VerificationLevel};
+use crate::types::kyc::KycSubmission;
+use crate::types::limits::TierLimits;
+use crate::types::referral::ReferralConfig;
+use crate::types::treasury::{FeeReport, TreasuryBalance};
+use crate::types::fees::{CreateFeePromotionRequest, FeePromotion, FeeRule, FeeTransactionType};
+use crate::types::ledger::Account;
+use crate::services::ledger_service::{self, IcrcLedger};
 
-fn ensure_admin(caller: candid::Principal) -> Result<(), ApiError> {
-    let admin_principals = vec![
-        "tdq4z-gz524-doqo7-nat24-nclox-v47yj-t5net-wnabe-nnr2g-fgl32-rqe",
-    ];
-    
-    if !admin_principals.contains(&caller.to_text().as_str()) {
-        return Err(ApiError::Unauthorized {
-            reason: "Admin access required".to_string(),
-        });
-    }
-    
-    Ok(())
+use crate::{USER_SERVICE, TRANSACTION_SERVICE, AUDIT_LOGGER, LEDGER_SERVICE, ROLE_SERVICE, RATE_LIMITER, AUTHORIZATION_GUARD, KYC_SERVICE, TIER_LIMITER, REFERRAL_SERVICE, TREASURY_SERVICE, FEE_SERVICE};
+
+fn ensure_permission(caller: candid::Principal, permission: Permission) -> Result<(), ApiError> {
+    ROLE_SERVICE.with(|service| service.borrow().ensure_permission(caller, permission))
+}
+
+#[update]
+#[candid_method(update)]
+pub fn admin_grant_role(principal: candid::Principal, role: Role) -> Result<RoleAssignment, ApiError> {
+    let caller = msg_caller();
+
+    ROLE_SERVICE.with(|service| {
+        service.borrow().grant_role(caller, principal, role)
+    })
+}
+
+#[update]
+#[candid_method(update)]
+pub fn admin_revoke_role(principal: candid::Principal, role: Role) -> Result<(), ApiError> {
+    let caller = msg_caller();
+
+    ROLE_SERVICE.with(|service| {
+        service.borrow().revoke_role(caller, principal, role)
+    })
+}
+
+#[query]
+#[candid_method(query)]
+pub fn admin_list_roles() -> Result<Vec<RoleAssignment>, ApiError> {
+    let caller = msg_caller();
+    ensure_permission(caller, Permission::ManageRoles)?;
+
+    ROLE_SERVICE.with(|service| Ok(service.borrow().list_assignments()))
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_my_roles() -> Vec<Role> {
+    let caller = msg_caller();
+
+    ROLE_SERVICE.with(|service| service.borrow().get_roles(caller))
 }
 
 #[update]
@@ -32,7 +72,7 @@ pub fn admin_freeze_account(
     reason: String,
 ) -> Result<(), ApiError> {
     let caller = msg_caller();
-    ensure_admin(caller)?;
+    ensure_permission(caller, Permission::FreezeAccounts)?;
     
     USER_SERVICE.with(|service| {
         service.borrow().freeze_account(user_principal, reason, caller)
@@ -43,7 +83,7 @@ pub fn admin_freeze_account(
 #[candid_method(update)]
 pub fn admin_unfreeze_account(user_principal: candid::Principal) -> Result<(), ApiError> {
     let caller = msg_caller();
-    ensure_admin(caller)?;
+    ensure_permission(caller, Permission::FreezeAccounts)?;
     
     USER_SERVICE.with(|service| {
         service.borrow().unfreeze_account(user_principal, caller)
@@ -57,7 +97,7 @@ pub fn admin_verify_user(
     verification_level: crate::types::user::VerificationLevel,
 ) -> Result<(), ApiError> {
     let caller = msg_caller();
-    ensure_admin(caller)?;
+    ensure_permission(caller, Permission::VerifyUsers)?;
     
     USER_SERVICE.with(|service| {
         service.borrow().admin_update_verification_status(user_principal, verification_level, caller)?;
@@ -72,7 +112,7 @@ pub fn admin_search_users(
     pagination: PaginationParams,
 ) -> Result<Vec<crate::types::user::User>, ApiError> {
     let caller = msg_caller();
-    ensure_admin(caller)?;
+    ensure_permission(caller, Permission::ViewUsers)?;
     
     USER_SERVICE.with(|service| {
         service.borrow().search_users(params, pagination)
@@ -83,10 +123,10 @@ pub fn admin_search_users(
 #[candid_method(query)]
 pub fn admin_get_transaction(transaction_id: u64) -> Result<Transaction, ApiError> {
     let caller = msg_caller();
-    ensure_admin(caller)?;
+    ensure_permission(caller, Permission::ViewTransactions)?;
     
     TRANSACTION_SERVICE.with(|service| {
-        service.borrow().get_transaction(transaction_id, caller)
+        service.borrow().get_transaction_unchecked(transaction_id)
     })
 }
 
@@ -97,7 +137,7 @@ pub fn admin_resolve_dispute(
     resolution: DisputeResolution
 ) -> Result<Transaction, ApiError> {
     let caller = msg_caller();
-    ensure_admin(caller)?;
+    ensure_permission(caller, Permission::ResolveDisputes)?;
     
     TRANSACTION_SERVICE.with(|service| {
         service.borrow_mut().resolve_dispute(transaction_id, resolution, caller)
@@ -111,7 +151,7 @@ pub fn admin_reverse_transaction(
     reason: String,
 ) -> Result<Transaction, ApiError> {
     let caller = msg_caller();
-    ensure_admin(caller)?;
+    ensure_permission(caller, Permission::ReverseTransactions)?;
     
     TRANSACTION_SERVICE.with(|service| {
         service.borrow().reverse_transaction(transaction_id, caller, reason)
@@ -124,29 +164,324 @@ pub fn admin_get_audit_logs(
     pagination: PaginationParams,
 ) -> Result<Vec<AuditLog>, ApiError> {
     let caller = msg_caller();
-    ensure_admin(caller)?;
+    ensure_permission(caller, Permission::ViewAuditLogs)?;
     
     AUDIT_LOGGER.with(|logger| {
         logger.borrow().get_logs(pagination)
     })
 }
 
+#[update]
+#[candid_method(update)]
+pub fn admin_delete_message(message_id: u64, reason: String) -> Result<(), ApiError> {
+    let caller = msg_caller();
+    ensure_permission(caller, Permission::ModerateMessages)?;
+
+    let message = crate::messaging::hard_delete_message(message_id)?;
+
+    AUDIT_LOGGER.with(|logger| {
+        logger.borrow().log(
+            caller,
+            AuditAction::MessageRemoved,
+            &format!("Message {}", message_id),
+            Some(format!("From {} to {}. Reason: {}", message.from, message.to, reason)),
+        );
+    });
+
+    Ok(())
+}
+
 #[update]
 #[candid_method(update)]
 pub fn admin_update_fee_percentage(new_fee_bps: u64) -> Result<(), ApiError> {
     let caller = msg_caller();
-    ensure_admin(caller)?;
+    ensure_permission(caller, Permission::ManageFees)?;
 
     TRANSACTION_SERVICE.with(|service| {
-        service.borrow_mut().update_fee_percentage(new_fee_bps, caller)
+        service.borrow().update_fee_percentage(new_fee_bps, caller)
     })
 }
 
+#[update]
+#[candid_method(update)]
+pub fn admin_update_max_agent_fee(max_agent_fee_bps: u64) -> Result<(), ApiError> {
+    let caller = msg_caller();
+    ensure_permission(caller, Permission::ManageFees)?;
+
+    TRANSACTION_SERVICE.with(|service| {
+        service.borrow().update_max_agent_fee(max_agent_fee_bps, caller)
+    })
+}
+
+#[update]
+#[candid_method(update)]
+pub fn admin_set_fee_rule(rule: FeeRule) -> Result<(), ApiError> {
+    let caller = msg_caller();
+    ensure_permission(caller, Permission::ManageFees)?;
+
+    FEE_SERVICE.with(|service| service.borrow().set_rule(caller, rule))
+}
+
+#[update]
+#[candid_method(update)]
+pub fn admin_remove_fee_rule(
+    transaction_type: FeeTransactionType,
+    currency: Option<Currency>,
+) -> Result<(), ApiError> {
+    let caller = msg_caller();
+    ensure_permission(caller, Permission::ManageFees)?;
+
+    FEE_SERVICE.with(|service| service.borrow().remove_rule(caller, transaction_type, currency))
+}
+
+#[query]
+#[candid_method(query)]
+pub fn admin_get_fee_rules() -> Result<Vec<FeeRule>, ApiError> {
+    let caller = msg_caller();
+    ensure_permission(caller, Permission::ManageFees)?;
+
+    FEE_SERVICE.with(|service| Ok(service.borrow().get_rules()))
+}
+
+#[update]
+#[candid_method(update)]
+pub fn admin_create_fee_promotion(request: CreateFeePromotionRequest) -> Result<FeePromotion, ApiError> {
+    let caller = msg_caller();
+    ensure_permission(caller, Permission::ManageFees)?;
+
+    FEE_SERVICE.with(|service| service.borrow().create_promotion(caller, request))
+}
+
+#[update]
+#[candid_method(update)]
+pub fn admin_remove_fee_promotion(promotion_id: u64) -> Result<(), ApiError> {
+    let caller = msg_caller();
+    ensure_permission(caller, Permission::ManageFees)?;
+
+    FEE_SERVICE.with(|service| service.borrow().remove_promotion(caller, promotion_id))
+}
+
+#[query]
+#[candid_method(query)]
+pub fn admin_get_fee_promotions() -> Result<Vec<FeePromotion>, ApiError> {
+    let caller = msg_caller();
+    ensure_permission(caller, Permission::ManageFees)?;
+
+    FEE_SERVICE.with(|service| Ok(service.borrow().get_promotions()))
+}
+
+#[query]
+#[candid_method(query)]
+pub fn admin_get_treasury_balances() -> Result<Vec<TreasuryBalance>, ApiError> {
+    let caller = msg_caller();
+    ensure_permission(caller, Permission::ViewTreasury)?;
+
+    TREASURY_SERVICE.with(|service| Ok(service.borrow().get_balances()))
+}
+
+#[query]
+#[candid_method(query)]
+pub fn admin_get_fee_report(
+    start: u64,
+    end: u64,
+    currency: Option<Currency>,
+) -> Result<Vec<FeeReport>, ApiError> {
+    let caller = msg_caller();
+    ensure_permission(caller, Permission::ViewTreasury)?;
+
+    TREASURY_SERVICE.with(|service| service.borrow().get_fee_report(start, end, currency))
+}
+
+#[update]
+#[candid_method(update)]
+pub async fn admin_withdraw_treasury(
+    amount: u64,
+    currency: Option<Currency>,
+    to: Account,
+) -> Result<TreasuryBalance, ApiError> {
+    let caller = msg_caller();
+    ensure_permission(caller, Permission::ManageTreasury)?;
+    let config = LEDGER_SERVICE.with(|service| {
+        service.borrow().get_config(&currency.unwrap_or_default())
+    })?;
+    let ledger = IcrcLedger::new(config.ledger_canister);
+
+    ledger_service::withdraw_treasury(&ledger, &config, caller, amount, to).await
+}
+
+#[update]
+#[candid_method(update)]
+pub fn admin_set_ledger(config: LedgerConfig) -> Result<(), ApiError> {
+    let caller = msg_caller();
+    ensure_permission(caller, Permission::ManageLedgers)?;
+
+    LEDGER_SERVICE.with(|service| {
+        service.borrow().set_config(config, caller)
+    })
+}
+
+#[update]
+#[candid_method(update)]
+pub fn admin_set_rate_limit(class: EndpointClass, limits: RateLimitConfig) -> Result<(), ApiError> {
+    let caller = msg_caller();
+    ensure_permission(caller, Permission::ManageSystem)?;
+
+    RATE_LIMITER.with(|limiter| limiter.borrow().set_config(caller, class, limits))
+}
+
+#[query]
+#[candid_method(query)]
+pub fn admin_get_rate_limits() -> Result<Vec<(EndpointClass, RateLimitConfig)>, ApiError> {
+    let caller = msg_caller();
+    ensure_permission(caller, Permission::ManageSystem)?;
+
+    RATE_LIMITER.with(|limiter| Ok(limiter.borrow().get_all_configs()))
+}
+
+#[query]
+#[candid_method(query)]
+pub fn admin_list_rate_limit_blocks() -> Result<Vec<RateLimitBlock>, ApiError> {
+    let caller = msg_caller();
+    ensure_permission(caller, Permission::ManageSystem)?;
+
+    RATE_LIMITER.with(|limiter| Ok(limiter.borrow().list_blocks()))
+}
+
+#[update]
+#[candid_method(update)]
+pub fn admin_clear_rate_limit(
+    principal: candid::Principal,
+    class: Option<EndpointClass>,
+) -> Result<u64, ApiError> {
+    let caller = msg_caller();
+    ensure_permission(caller, Permission::ManageSystem)?;
+
+    RATE_LIMITER.with(|limiter| Ok(limiter.borrow().clear(caller, principal, class)))
+}
+
+#[update]
+#[candid_method(update)]
+pub fn admin_set_referral_config(referral_config: ReferralConfig) -> Result<(), ApiError> {
+    let caller = msg_caller();
+    ensure_permission(caller, Permission::ManageFees)?;
+
+    REFERRAL_SERVICE.with(|service| service.borrow().set_config(caller, referral_config))
+}
+
+#[query]
+#[candid_method(query)]
+pub fn admin_get_referral_config() -> Result<ReferralConfig, ApiError> {
+    let caller = msg_caller();
+    ensure_permission(caller, Permission::ManageFees)?;
+
+    REFERRAL_SERVICE.with(|service| Ok(service.borrow().get_config()))
+}
+
+#[update]
+#[candid_method(update)]
+pub fn admin_set_tier_limits(tier: VerificationLevel, currency: Currency, limits: TierLimits) -> Result<(), ApiError> {
+    let caller = msg_caller();
+    ensure_permission(caller, Permission::ManageSystem)?;
+
+    TIER_LIMITER.with(|limiter| limiter.borrow().set_limits(caller, tier, currency, limits))
+}
+
+#[query]
+#[candid_method(query)]
+pub fn admin_get_tier_limits(currency: Option<Currency>) -> Result<Vec<(VerificationLevel, TierLimits)>, ApiError> {
+    let caller = msg_caller();
+    ensure_permission(caller, Permission::ManageSystem)?;
+
+    TIER_LIMITER.with(|limiter| Ok(limiter.borrow().get_all_limits(&currency.unwrap_or_default())))
+}
+
+#[query]
+#[candid_method(query)]
+pub fn admin_get_kyc_queue() -> Result<Vec<KycSubmission>, ApiError> {
+    let caller = msg_caller();
+    ensure_permission(caller, Permission::VerifyUsers)?;
+
+    KYC_SERVICE.with(|service| Ok(service.borrow().get_review_queue()))
+}
+
+#[query]
+#[candid_method(query)]
+pub fn admin_get_kyc_submission(submission_id: u64) -> Result<KycSubmission, ApiError> {
+    let caller = msg_caller();
+    ensure_permission(caller, Permission::VerifyUsers)?;
+
+    KYC_SERVICE.with(|service| service.borrow().get_submission(submission_id))
+}
+
+#[update]
+#[candid_method(update)]
+pub fn admin_claim_kyc_submission(submission_id: u64) -> Result<KycSubmission, ApiError> {
+    let caller = msg_caller();
+    ensure_permission(caller, Permission::VerifyUsers)?;
+
+    KYC_SERVICE.with(|service| service.borrow().claim(caller, submission_id))
+}
+
+#[update]
+#[candid_method(update)]
+pub fn admin_approve_kyc_submission(submission_id: u64) -> Result<KycSubmission, ApiError> {
+    let caller = msg_caller();
+    ensure_permission(caller, Permission::VerifyUsers)?;
+
+    KYC_SERVICE.with(|service| service.borrow().approve(caller, submission_id))
+}
+
+#[update]
+#[candid_method(update)]
+pub fn admin_reject_kyc_submission(submission_id: u64, reason: String) -> Result<KycSubmission, ApiError> {
+    let caller = msg_caller();
+    ensure_permission(caller, Permission::VerifyUsers)?;
+
+    KYC_SERVICE.with(|service| service.borrow().reject(caller, submission_id, reason))
+}
+
+#[update]
+#[candid_method(update)]
+pub fn admin_set_operation_verification_level(
+    operation: Operation,
+    level: VerificationLevel,
+) -> Result<(), ApiError> {
+    let caller = msg_caller();
+    ensure_permission(caller, Permission::ManageSystem)?;
+
+    AUTHORIZATION_GUARD.with(|guard| {
+        guard.borrow().set_operation_level(caller, operation, level);
+        Ok(())
+    })
+}
+
+#[update]
+#[candid_method(update)]
+pub fn admin_set_verification_threshold(
+    currency: Currency,
+    level: VerificationLevel,
+    min_amount: Option<u64>,
+) -> Result<(), ApiError> {
+    let caller = msg_caller();
+    ensure_permission(caller, Permission::ManageSystem)?;
+
+    AUTHORIZATION_GUARD.with(|guard| guard.borrow().set_amount_threshold(caller, currency, level, min_amount))
+}
+
+#[query]
+#[candid_method(query)]
+pub fn admin_get_guard_policy() -> Result<GuardPolicy, ApiError> {
+    let caller = msg_caller();
+    ensure_permission(caller, Permission::ManageSystem)?;
+
+    AUTHORIZATION_GUARD.with(|guard| Ok(guard.borrow().get_policy()))
+}
+
 #[update]
 #[candid_method(update)]
 pub fn admin_pause_system(reason: String) -> Result<(), ApiError> {
     let caller = msg_caller();
-    ensure_admin(caller)?;
+    ensure_permission(caller, Permission::ManageSystem)?;
 
     crate::SYSTEM_STATE.with(|state| {
         let mut s = state.borrow_mut();
@@ -170,7 +505,7 @@ pub fn admin_pause_system(reason: String) -> Result<(), ApiError> {
 #[candid_method(update)]
 pub fn admin_resume_system() -> Result<(), ApiError> {
     let caller = msg_caller();
-    ensure_admin(caller)?;
+    ensure_permission(caller, Permission::ManageSystem)?;
 
     AUDIT_LOGGER.with(|log| {
         log.borrow().log(
@@ -0,0 +1,74 @@
+use candid::candid_method;
+use ic_cdk_macros::{query, update};
+use ic_cdk::api::msg_caller;
+use crate::types::{
+    errors::ApiError,
+    evidence::*,
+    rate_limit::EndpointClass,
+    guard::Operation,
+};
+use crate::EVIDENCE_SERVICE;
+
+#[update]
+#[candid_method(update)]
+pub fn begin_evidence_upload(request: BeginEvidenceUploadRequest) -> Result<EvidenceFile, ApiError> {
+    let caller = msg_caller();
+    super::rate_limit(caller, EndpointClass::Write)?;
+    super::authorize(caller, Operation::Evidence, None)?;
+    
+    EVIDENCE_SERVICE.with(|service| {
+        service.borrow().begin_upload(caller, request)
+    })
+}
+
+#[update]
+#[candid_method(update)]
+pub fn upload_evidence_chunk(evidence_id: u64, chunk_index: u32, data: Vec<u8>) -> Result<EvidenceFile, ApiError> {
+    let caller = msg_caller();
+    super::authorize(caller, Operation::Evidence, None)?;
+    
+    EVIDENCE_SERVICE.with(|service| {
+        service.borrow().upload_chunk(caller, evidence_id, chunk_index, data)
+    })
+}
+
+#[update]
+#[candid_method(update)]
+pub fn finish_evidence_upload(evidence_id: u64, expected_sha256: Option<String>) -> Result<EvidenceFile, ApiError> {
+    let caller = msg_caller();
+    super::authorize(caller, Operation::Evidence, None)?;
+    
+    EVIDENCE_SERVICE.with(|service| {
+        service.borrow().finish_upload(caller, evidence_id, expected_sha256)
+    })
+}
+
+#[query]
+#[candid_method(query)]
+pub fn list_dispute_evidence(transaction_id: u64) -> Result<Vec<EvidenceFile>, ApiError> {
+    let caller = msg_caller();
+    
+    EVIDENCE_SERVICE.with(|service| {
+        service.borrow().list_evidence(caller, transaction_id)
+    })
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_evidence(evidence_id: u64) -> Result<EvidenceFile, ApiError> {
+    let caller = msg_caller();
+    
+    EVIDENCE_SERVICE.with(|service| {
+        service.borrow().get_evidence(caller, evidence_id)
+    })
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_evidence_chunk(evidence_id: u64, chunk_index: u32) -> Result<Vec<u8>, ApiError> {
+    let caller = msg_caller();
+    
+    EVIDENCE_SERVICE.with(|service| {
+        service.borrow().get_chunk(caller, evidence_id, chunk_index)
+    })
+}
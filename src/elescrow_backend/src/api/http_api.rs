@@ -0,0 +1,159 @@
+use candid::candid_method;
+use ic_cdk_macros::query;
+use serde::Serialize;
+use serde_json::json;
+
+use crate::types::{
+    common::{HealthStatus, SystemStats},
+    http::{HttpRequest, HttpResponse, TransactionReceipt},
+    errors::ApiError,
+};
+use crate::storage::memory::utils::memory_usage;
+use crate::utils::{constants::API_VERSION, time::now};
+use crate::{SYSTEM_STATE, TRANSACTION_SERVICE, USER_SERVICE};
+
+/// Read-only view of the canister for dashboards, webhooks and scrapers. Nothing
+/// here is certified, so clients that need tamper-proof data should use the
+/// Candid interface instead.
+#[query]
+#[candid_method(query)]
+pub fn http_request(request: HttpRequest) -> HttpResponse {
+    route(&request)
+}
+
+fn route(request: &HttpRequest) -> HttpResponse {
+    let path = request.url.split(['?', '#']).next().unwrap_or("/").trim_end_matches('/');
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+    match request.method.to_uppercase().as_str() {
+        "OPTIONS" => return response(204, "text/plain", vec![]),
+        "GET" | "HEAD" => {}
+        _ => return error(405, "Method not allowed"),
+    }
+
+    match segments.as_slice() {
+        ["health"] => json_response(200, &health()),
+        ["stats"] => json_response(200, &stats()),
+        ["metrics"] => response(200, "text/plain; version=0.0.4", crate::services::metrics::render().into_bytes()),
+        ["transactions", id] => match id.parse::<u64>() {
+            Ok(id) => transaction_receipt(id),
+            Err(_) => error(400, "Transaction ID must be a number"),
+        },
+        _ => error(404, "Not found"),
+    }
+}
+
+fn health() -> HealthStatus {
+    let paused = SYSTEM_STATE.with(|s| s.borrow().is_paused);
+
+    HealthStatus {
+        status: if paused { "paused" } else { "healthy" }.to_string(),
+        version: API_VERSION.to_string(),
+        timestamp: now(),
+        memory_usage: memory_usage(),
+        uptime: now().saturating_sub(crate::init_timestamp()),
+    }
+}
+
+fn stats() -> SystemStats {
+    let users = USER_SERVICE.with(|s| s.borrow().get_user_statistics());
+    let transactions = TRANSACTION_SERVICE.with(|s| s.borrow().get_statistics());
+
+    SystemStats {
+        total_users: users.total_users,
+        active_users_24h: users.active_users_24h,
+        total_transactions: transactions.total_transactions,
+        pending_transactions: transactions.pending_count,
+        total_volume: transactions.total_volume,
+        fees_collected: transactions.total_fees,
+        avg_response_time_ms: 0,
+    }
+}
+
+fn transaction_receipt(id: u64) -> HttpResponse {
+    match TRANSACTION_SERVICE.with(|s| s.borrow().get_transaction_unchecked(id)) {
+        Ok(tx) => json_response(200, &TransactionReceipt {
+            id: tx.id,
+            transaction_type: tx.transaction_type.label().to_string(),
+            status: tx.status.label().to_string(),
+            amount: tx.amount,
+            fee: tx.fee,
+            currency: tx.currency.code(),
+            created_at: tx.created_at,
+            completed_at: tx.completed_at,
+        }),
+        Err(ApiError::NotFound { .. }) => error(404, &format!("Transaction {} not found", id)),
+        Err(e) => error(500, &e.to_string()),
+    }
+}
+
+fn json_response<T: Serialize>(status_code: u16, body: &T) -> HttpResponse {
+    match serde_json::to_vec(body) {
+        Ok(body) => response(status_code, "application/json", body),
+        Err(e) => error(500, &e.to_string()),
+    }
+}
+
+fn error(status_code: u16, message: &str) -> HttpResponse {
+    let body = json!({ "error": message }).to_string().into_bytes();
+    response(status_code, "application/json", body)
+}
+
+fn response(status_code: u16, content_type: &str, body: Vec<u8>) -> HttpResponse {
+    HttpResponse {
+        status_code,
+        headers: vec![
+            ("Content-Type".to_string(), content_type.to_string()),
+            ("Content-Length".to_string(), body.len().to_string()),
+            ("Access-Control-Allow-Origin".to_string(), "*".to_string()),
+            ("Access-Control-Allow-Methods".to_string(), "GET, HEAD, OPTIONS".to_string()),
+            ("Access-Control-Allow-Headers".to_string(), "Content-Type".to_string()),
+            ("Cache-Control".to_string(), "no-store".to_string()),
+        ],
+        body,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(url: &str) -> HttpResponse {
+        route(&HttpRequest {
+            method: "GET".to_string(),
+            url: url.to_string(),
+            headers: vec![],
+            body: vec![],
+        })
+    }
+
+    fn header<'a>(response: &'a HttpResponse, name: &str) -> Option<&'a str> {
+        response.headers.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+    }
+
+    #[test]
+    fn test_routes_and_errors() {
+        let health = get("/health?probe=1");
+        assert_eq!(health.status_code, 200);
+        assert_eq!(header(&health, "Content-Type"), Some("application/json"));
+        assert_eq!(header(&health, "Access-Control-Allow-Origin"), Some("*"));
+        let body: serde_json::Value = serde_json::from_slice(&health.body).unwrap();
+        assert_eq!(body["status"], "healthy");
+
+        assert_eq!(get("/stats/").status_code, 200);
+        let metrics = get("/metrics");
+        assert_eq!(metrics.status_code, 200);
+        assert_eq!(header(&metrics, "Content-Type"), Some("text/plain; version=0.0.4"));
+        assert_eq!(get("/transactions/abc").status_code, 400);
+        assert_eq!(get("/transactions/999999").status_code, 404);
+        assert_eq!(get("/nowhere").status_code, 404);
+
+        let post = route(&HttpRequest {
+            method: "POST".to_string(),
+            url: "/health".to_string(),
+            headers: vec![],
+            body: vec![],
+        });
+        assert_eq!(post.status_code, 405);
+    }
+}
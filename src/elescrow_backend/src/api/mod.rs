@@ -1,4 +1,19 @@
 pub mod user_api;
 pub mod notification_api;
 pub mod transaction_api;
-pub mod admin_api;
\ No newline at end of file
+pub mod admin_api;
+pub mod evidence_api;
+pub mod http_api;
+
+use candid::Principal;
+use crate::types::{errors::ApiError, guard::Operation, rate_limit::EndpointClass, transaction::Currency};
+
+/// Counts the call against the caller's limit for `class`.
+fn rate_limit(caller: Principal, class: EndpointClass) -> Result<(), ApiError> {
+    crate::RATE_LIMITER.with(|limiter| limiter.borrow().check(caller, class))
+}
+
+/// Runs the authorization guard for `operation`; see `AuthorizationGuard`.
+fn authorize(caller: Principal, operation: Operation, amount: Option<(u64, &Currency)>) -> Result<(), ApiError> {
+    crate::AUTHORIZATION_GUARD.with(|guard| guard.borrow().authorize(caller, operation, amount))
+}
\ No newline at end of file
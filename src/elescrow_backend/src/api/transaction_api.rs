@@ -1,17 +1,26 @@
 use candid::candid_method;
 use ic_cdk_macros::{query, update};
-use ic_cdk::api::msg_caller;
+use ic_cdk::api::{msg_caller, canister_self};
 use crate::types::{
     errors::ApiError,
     transaction::*,
+    ledger::{Account, LedgerConfig},
     common::PaginationParams,
+    rate_limit::EndpointClass,
+    guard::Operation,
+    limits::LimitUsage,
+    fees::{FeeQuote, FeeQuoteRequest},
 };
-use crate::{TRANSACTION_SERVICE, BALANCE_SERVICE};
+use crate::services::ledger_service::{self, IcrcLedger};
+use crate::{TRANSACTION_SERVICE, BALANCE_SERVICE, LEDGER_SERVICE, TIER_LIMITER, FEE_SERVICE};
+use crate::utils::{constants::{LIMIT_DAILY_WINDOW, LIMIT_MONTHLY_WINDOW}, time::now};
 
 #[update]
 #[candid_method(update)]
 pub async fn create_transaction(request: CreateTransactionRequest) -> Result<Transaction, ApiError> {
     let caller = msg_caller();
+    super::rate_limit(caller, EndpointClass::Write)?;
+    super::authorize(caller, Operation::CreateTransaction, Some((request.amount, &request.currency)))?;
     
     TRANSACTION_SERVICE.with(|service| {
         service.borrow().create_transaction(caller, request)
@@ -22,6 +31,7 @@ pub async fn create_transaction(request: CreateTransactionRequest) -> Result<Tra
 #[candid_method(update)]
 pub fn accept_escrow_terms(transaction_id: u64) -> Result<Transaction, ApiError> {
     let caller = msg_caller();
+    super::authorize(caller, Operation::EscrowAction, None)?;
     
     TRANSACTION_SERVICE.with(|service| {
         service.borrow_mut().accept_escrow_terms(transaction_id, caller)
@@ -32,6 +42,7 @@ pub fn accept_escrow_terms(transaction_id: u64) -> Result<Transaction, ApiError>
 #[candid_method(update)]
 pub fn submit_escrow_work(transaction_id: u64) -> Result<Transaction, ApiError> {
     let caller = msg_caller();
+    super::authorize(caller, Operation::EscrowAction, None)?;
     
     TRANSACTION_SERVICE.with(|service| {
         service.borrow_mut().submit_escrow_work(transaction_id, caller)
@@ -42,6 +53,7 @@ pub fn submit_escrow_work(transaction_id: u64) -> Result<Transaction, ApiError>
 #[candid_method(update)]
 pub fn approve_transaction(transaction_id: u64) -> Result<Transaction, ApiError> {
     let caller = msg_caller();
+    super::authorize(caller, Operation::EscrowAction, None)?;
     
     TRANSACTION_SERVICE.with(|service| {
         service.borrow_mut().approve_transaction(transaction_id, caller)
@@ -52,6 +64,7 @@ pub fn approve_transaction(transaction_id: u64) -> Result<Transaction, ApiError>
 #[candid_method(update)]
 pub fn complete_transaction(transaction_id: u64) -> Result<Transaction, ApiError> {
     let caller = msg_caller();
+    super::authorize(caller, Operation::EscrowAction, None)?;
     
     TRANSACTION_SERVICE.with(|service| {
         service.borrow_mut().complete_transaction(transaction_id, caller)
@@ -62,22 +75,137 @@ pub fn complete_transaction(transaction_id: u64) -> Result<Transaction, ApiError
 #[candid_method(update)]
 pub fn raise_dispute(transaction_id: u64, reason: String) -> Result<Transaction, ApiError> {
     let caller = msg_caller();
+    super::rate_limit(caller, EndpointClass::Write)?;
+    super::authorize(caller, Operation::EscrowAction, None)?;
     
     TRANSACTION_SERVICE.with(|service| {
         service.borrow_mut().raise_dispute(transaction_id, caller, reason)
     })
 }
 
+#[update]
+#[candid_method(update)]
+pub fn accept_agent_role(transaction_id: u64) -> Result<Transaction, ApiError> {
+    let caller = msg_caller();
+    super::authorize(caller, Operation::EscrowAction, None)?;
+    
+    TRANSACTION_SERVICE.with(|service| {
+        service.borrow().accept_agent_role(transaction_id, caller)
+    })
+}
+
+#[update]
+#[candid_method(update)]
+pub fn start_dispute_review(transaction_id: u64) -> Result<Transaction, ApiError> {
+    let caller = msg_caller();
+    super::authorize(caller, Operation::EscrowAction, None)?;
+    
+    TRANSACTION_SERVICE.with(|service| {
+        service.borrow().start_dispute_review(transaction_id, caller)
+    })
+}
+
+#[update]
+#[candid_method(update)]
+pub fn agent_resolve_dispute(
+    transaction_id: u64,
+    resolution: DisputeResolution,
+) -> Result<Transaction, ApiError> {
+    let caller = msg_caller();
+    super::authorize(caller, Operation::EscrowAction, None)?;
+    
+    TRANSACTION_SERVICE.with(|service| {
+        service.borrow().agent_resolve_dispute(transaction_id, resolution, caller)
+    })
+}
+
+#[update]
+#[candid_method(update)]
+pub fn submit_milestone_work(transaction_id: u64, milestone_id: u32) -> Result<Transaction, ApiError> {
+    let caller = msg_caller();
+    super::authorize(caller, Operation::EscrowAction, None)?;
+    
+    TRANSACTION_SERVICE.with(|service| {
+        service.borrow().submit_milestone_work(transaction_id, milestone_id, caller)
+    })
+}
+
+#[update]
+#[candid_method(update)]
+pub fn approve_milestone(transaction_id: u64, milestone_id: u32) -> Result<Transaction, ApiError> {
+    let caller = msg_caller();
+    super::authorize(caller, Operation::EscrowAction, None)?;
+    
+    TRANSACTION_SERVICE.with(|service| {
+        service.borrow().approve_milestone(transaction_id, milestone_id, caller)
+    })
+}
+
+#[update]
+#[candid_method(update)]
+pub fn dispute_milestone(
+    transaction_id: u64,
+    milestone_id: u32,
+    reason: String,
+) -> Result<Transaction, ApiError> {
+    let caller = msg_caller();
+    super::rate_limit(caller, EndpointClass::Write)?;
+    super::authorize(caller, Operation::EscrowAction, None)?;
+    
+    TRANSACTION_SERVICE.with(|service| {
+        service.borrow().dispute_milestone(transaction_id, milestone_id, caller, reason)
+    })
+}
+
+#[update]
+#[candid_method(update)]
+pub fn resolve_milestone_dispute(
+    transaction_id: u64,
+    milestone_id: u32,
+    resolution: DisputeResolution,
+) -> Result<Transaction, ApiError> {
+    let caller = msg_caller();
+    super::authorize(caller, Operation::EscrowAction, None)?;
+    
+    TRANSACTION_SERVICE.with(|service| {
+        service.borrow().resolve_milestone_dispute(transaction_id, milestone_id, resolution, caller)
+    })
+}
+
+#[update]
+#[candid_method(update)]
+pub fn refund_transaction(transaction_id: u64, amount: u64, reason: String) -> Result<Transaction, ApiError> {
+    let caller = msg_caller();
+    super::rate_limit(caller, EndpointClass::Write)?;
+    let currency = TRANSACTION_SERVICE.with(|service| {
+        service.borrow().get_transaction_unchecked(transaction_id)
+    })?.currency;
+    super::authorize(caller, Operation::Refund, Some((amount, &currency)))?;
+    
+    TRANSACTION_SERVICE.with(|service| {
+        service.borrow().refund_transaction(transaction_id, amount, reason, caller)
+    })
+}
+
 #[update]
 #[candid_method(update)]
 pub fn cancel_transaction(transaction_id: u64, reason: String) -> Result<Transaction, ApiError> {
     let caller = msg_caller();
+    super::rate_limit(caller, EndpointClass::Write)?;
+    super::authorize(caller, Operation::EscrowAction, None)?;
     
     TRANSACTION_SERVICE.with(|service| {
         service.borrow().cancel_transaction(transaction_id, caller, reason)
     })
 }
 
+/// The fee and total a transaction would cost if submitted now.
+#[query]
+#[candid_method(query)]
+pub fn quote_fee(request: FeeQuoteRequest) -> Result<FeeQuote, ApiError> {
+    FEE_SERVICE.with(|service| service.borrow().quote_request(request))
+}
+
 #[query]
 #[candid_method(query)]
 pub fn get_transaction(transaction_id: u64) -> Result<Transaction, ApiError> {
@@ -103,32 +231,112 @@ pub fn get_my_transactions(
 
 #[query]
 #[candid_method(query)]
-pub fn get_balance() -> Result<Balance, ApiError> {
+pub fn get_balance(currency: Option<Currency>) -> Result<Balance, ApiError> {
     let caller = msg_caller();
     
     BALANCE_SERVICE.with(|service| {
-        service.borrow_mut().get_balance(caller)
+        service.borrow_mut().get_balance(caller, &currency.unwrap_or_default())
     })
 }
 
-#[update]
-#[candid_method(update)]
-pub async fn deposit(amount: u64) -> Result<u64, ApiError> {
+#[query]
+#[candid_method(query)]
+pub fn get_balances() -> Result<Vec<Balance>, ApiError> {
     let caller = msg_caller();
     
     BALANCE_SERVICE.with(|service| {
-        service.borrow_mut().deposit(caller, amount)
+        service.borrow().get_balances(caller)
     })
 }
 
+#[query]
+#[candid_method(query)]
+pub fn get_my_limits(currency: Option<Currency>) -> LimitUsage {
+    let caller = msg_caller();
+    let currency = currency.unwrap_or_default();
+    let now = now();
+    let daily_since = now.saturating_sub(LIMIT_DAILY_WINDOW);
+    let monthly_since = now.saturating_sub(LIMIT_MONTHLY_WINDOW);
+    let (tier, limits) = TIER_LIMITER.with(|limiter| {
+        let limiter = limiter.borrow();
+        let tier = limiter.tier_of(caller);
+        let limits = limiter.get_limits(&tier, &currency);
+        (tier, limits)
+    });
+    
+    LimitUsage {
+        tier,
+        limits,
+        daily_volume_used: TRANSACTION_SERVICE.with(|s| s.borrow().outgoing_volume(caller, &currency, daily_since)),
+        monthly_volume_used: TRANSACTION_SERVICE.with(|s| s.borrow().outgoing_volume(caller, &currency, monthly_since)),
+        daily_withdrawn: BALANCE_SERVICE.with(|s| s.borrow().withdrawn_since(caller, &currency, daily_since)),
+        monthly_withdrawn: BALANCE_SERVICE.with(|s| s.borrow().withdrawn_since(caller, &currency, monthly_since)),
+        currency,
+    }
+}
+
 #[update]
 #[candid_method(update)]
-pub async fn withdraw(amount: u64) -> Result<u64, ApiError> {
+pub async fn deposit(amount: u64, currency: Option<Currency>) -> Result<u64, ApiError> {
     let caller = msg_caller();
+    super::rate_limit(caller, EndpointClass::Write)?;
+    let currency = currency.unwrap_or_default();
+    super::authorize(caller, Operation::Deposit, Some((amount, &currency)))?;
+    let config = LEDGER_SERVICE.with(|service| {
+        service.borrow().get_config(&currency)
+    })?;
+    let ledger = IcrcLedger::new(config.ledger_canister);
     
-    BALANCE_SERVICE.with(|service| {
-        service.borrow_mut().withdraw(caller, amount)
-    })
+    let balance = ledger_service::deposit_from_allowance(&ledger, &config, canister_self(), caller, amount).await?;
+    Ok(balance.available)
+}
+
+#[update]
+#[candid_method(update)]
+pub async fn claim_deposit(currency: Option<Currency>) -> Result<u64, ApiError> {
+    let caller = msg_caller();
+    super::rate_limit(caller, EndpointClass::Write)?;
+    super::authorize(caller, Operation::Deposit, None)?;
+    let config = LEDGER_SERVICE.with(|service| {
+        service.borrow().get_config(&currency.unwrap_or_default())
+    })?;
+    let ledger = IcrcLedger::new(config.ledger_canister);
+    
+    let balance = ledger_service::claim_subaccount_deposit(&ledger, &config, canister_self(), caller).await?;
+    Ok(balance.available)
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_deposit_account() -> Account {
+    ledger_service::deposit_account(canister_self(), msg_caller())
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_supported_ledgers() -> Vec<LedgerConfig> {
+    LEDGER_SERVICE.with(|service| service.borrow().list_configs())
+}
+
+#[update]
+#[candid_method(update)]
+pub async fn withdraw(
+    amount: u64,
+    currency: Option<Currency>,
+    to: Option<Account>,
+) -> Result<u64, ApiError> {
+    let caller = msg_caller();
+    super::rate_limit(caller, EndpointClass::Write)?;
+    let currency = currency.unwrap_or_default();
+    super::authorize(caller, Operation::Withdraw, Some((amount, &currency)))?;
+    let config = LEDGER_SERVICE.with(|service| {
+        service.borrow().get_config(&currency)
+    })?;
+    let ledger = IcrcLedger::new(config.ledger_canister);
+    let to = to.unwrap_or_else(|| Account::new(caller));
+    
+    let balance = ledger_service::withdraw(&ledger, &config, caller, amount, to).await?;
+    Ok(balance.available)
 }
 
 #[update]
@@ -138,23 +346,19 @@ pub fn create_scheduled_payment(
     amount: u64,
     schedule: PaymentSchedule,
     description: String,
+    currency: Option<Currency>,
 ) -> Result<Transaction, ApiError> {
     let caller = msg_caller();
-    
-    let request = CreateTransactionRequest {
-        transaction_type: TransactionType::ScheduledPayment { schedule },
-        to,
-        amount,
-        currency: Currency::ICP,
-        description,
-        escrow_agent: None,
-        deadline: None,
-        category: None,
-        tags: vec![],
+    super::rate_limit(caller, EndpointClass::Write)?;
+    let currency = currency.unwrap_or_default();
+    super::authorize(caller, Operation::CreateTransaction, Some((amount, &currency)))?;
+    let schedule = PaymentSchedule {
+        amount_per_payment: amount,
+        ..schedule
     };
     
     TRANSACTION_SERVICE.with(|service| {
-        service.borrow().create_transaction(caller, request)
+        service.borrow().create_scheduled_payment(caller, to, schedule, description, currency)
     })
 }
 
@@ -162,6 +366,7 @@ pub fn create_scheduled_payment(
 #[candid_method(update)]
 pub fn cancel_scheduled_payment(transaction_id: u64) -> Result<Transaction, ApiError> {
     let caller = msg_caller();
+    super::authorize(caller, Operation::EscrowAction, None)?;
     
     TRANSACTION_SERVICE.with(|service| {
         service.borrow().cancel_transaction(transaction_id, caller, "User cancelled scheduled payment".to_string())
@@ -5,15 +5,20 @@ use ic_cdk::api::msg_caller;
 use crate::types::{
     errors::ApiError,
     user::*,
-    common::PaginationParams
+    common::PaginationParams,
+    rate_limit::EndpointClass,
+    guard::Operation,
+    kyc::{KycSubmission, SubmitKycRequest},
+    referral::{ReferralCode, ReferralStats},
 };
 
-use crate::{USER_SERVICE};
+use crate::{USER_SERVICE, KYC_SERVICE, REFERRAL_SERVICE};
 
 #[update]
 #[candid_method(update)]
 pub async fn register_user(request: RegisterUserRequest) -> Result<User, ApiError> {
     let caller = msg_caller();
+    super::rate_limit(caller, EndpointClass::Write)?;
     
     USER_SERVICE.with(|service| {
         service.borrow().register(caller, request)
@@ -50,6 +55,8 @@ pub fn get_user_by_username(username: String) -> Result<User, ApiError> {
 #[candid_method(update)]
 pub fn update_profile(request: UpdateProfileRequest) -> Result<User, ApiError> {
     let caller = msg_caller();
+    super::rate_limit(caller, EndpointClass::Write)?;
+    super::authorize(caller, Operation::Profile, None)?;
     
     USER_SERVICE.with(|service| {
         service.borrow().update_profile(caller, request)
@@ -60,6 +67,7 @@ pub fn update_profile(request: UpdateProfileRequest) -> Result<User, ApiError> {
 #[candid_method(update)]
 pub fn update_notification_preferences(preferences: NotificationPreferences) -> Result<User, ApiError> {
     let caller = msg_caller();
+    super::authorize(caller, Operation::Profile, None)?;
     
     USER_SERVICE.with(|service| {
         service.borrow().update_notification_preferences(caller, preferences)
@@ -70,6 +78,7 @@ pub fn update_notification_preferences(preferences: NotificationPreferences) ->
 #[candid_method(update)]
 pub fn update_security_settings(settings: SecuritySettings) -> Result<User, ApiError> {
     let caller = msg_caller();
+    super::authorize(caller, Operation::Profile, None)?;
     
     USER_SERVICE.with(|service| {
         service.borrow().update_security_settings(caller, settings)
@@ -80,6 +89,7 @@ pub fn update_security_settings(settings: SecuritySettings) -> Result<User, ApiE
 #[candid_method(update)]
 pub fn deactivate_account() -> Result<(), ApiError> {
     let caller = msg_caller();
+    super::authorize(caller, Operation::Profile, None)?;
     
     USER_SERVICE.with(|service| {
         service.borrow().deactivate_account(caller)
@@ -92,6 +102,9 @@ pub fn search_users(
     params: UserSearchParams,
     pagination: PaginationParams,
 ) -> Result<Vec<User>, ApiError> {
+    let caller = msg_caller();
+    super::rate_limit(caller, EndpointClass::Search)?;
+    
     USER_SERVICE.with(|service| {
         service.borrow().search_users(params, pagination)
     })
@@ -103,4 +116,48 @@ pub fn is_username_available(username: String) -> bool {
     USER_SERVICE.with(|service| {
         service.borrow().get_user_by_username(&username).is_err()
     })
+}
+
+#[update]
+#[candid_method(update)]
+pub fn submit_kyc(request: SubmitKycRequest) -> Result<KycSubmission, ApiError> {
+    let caller = msg_caller();
+    super::rate_limit(caller, EndpointClass::Write)?;
+    super::authorize(caller, Operation::Profile, None)?;
+    
+    KYC_SERVICE.with(|service| {
+        service.borrow().submit(caller, request)
+    })
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_my_kyc_submission() -> Option<KycSubmission> {
+    let caller = msg_caller();
+    
+    KYC_SERVICE.with(|service| {
+        service.borrow().get_latest(caller)
+    })
+}
+
+#[update]
+#[candid_method(update)]
+pub fn generate_referral_code() -> Result<ReferralCode, ApiError> {
+    let caller = msg_caller();
+    super::rate_limit(caller, EndpointClass::Write)?;
+    super::authorize(caller, Operation::Profile, None)?;
+    
+    REFERRAL_SERVICE.with(|service| {
+        service.borrow().generate_code(caller)
+    })
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_my_referral_stats() -> ReferralStats {
+    let caller = msg_caller();
+    
+    REFERRAL_SERVICE.with(|service| {
+        service.borrow().get_stats(caller)
+    })
 }
\ No newline at end of file
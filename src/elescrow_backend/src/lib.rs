@@ -11,9 +11,20 @@ use std::sync::Once;
 pub use types::common::{SystemState, PaginationParams, AuditLog, ListResponse};
 pub use types::errors::*;
 pub use types::transaction::*;
-pub use types::user::{User, UserSearchParams, RegisterUserRequest, UpdateProfileRequest, NotificationPreferences, SecuritySettings};
+pub use types::user::{User, UserSearchParams, RegisterUserRequest, UpdateProfileRequest, NotificationPreferences, SecuritySettings, VerificationLevel, KycStatus};
 pub use types::notification::{Notification, NotificationFilter, NotificationStats};
-pub use types::messaging::{Message, PostResult, WsEvent};
+pub use types::messaging::{ContactStatus, ConversationSummary, Message, MessageRequest, MessageRevision, PostResult, ThreadMessage, WsEvent};
+pub use types::ledger::{Account, LedgerConfig};
+pub use types::role::{InitArgs, Role, RoleAssignment};
+pub use types::evidence::{EvidenceFile, EvidenceStatus, BeginEvidenceUploadRequest};
+pub use types::http::{HttpRequest, HttpResponse};
+pub use types::rate_limit::{EndpointClass, RateLimitConfig, RateLimitBlock};
+pub use types::guard::{Operation, AmountThreshold, GuardPolicy};
+pub use types::kyc::{KycDocument, KycDocumentType, KycSubmission, SubmitKycRequest};
+pub use types::limits::{LimitUsage, TierLimits};
+pub use types::referral::{Referral, ReferralCode, ReferralConfig, ReferralStats};
+pub use types::fees::{CreateFeePromotionRequest, FeePromotion, FeeQuote, FeeQuoteRequest, FeeRule, FeeTier, FeeTransactionType};
+pub use types::treasury::{FeeReport, FeeSource, TreasuryBalance, TreasuryEntry, TreasuryEntryKind};
 
 mod api;
 mod messaging;
@@ -23,14 +34,24 @@ mod services;
 mod storage;
 mod types;
 mod utils;
+#[cfg(test)]
+mod test_utils;
 
 use services::{
     user_service::UserService,
     transaction_service::TransactionService,
     notification_service::NotificationService,
     balance_service::BalanceService,
+    ledger_service::LedgerService,
+    role_service::RoleService,
+    evidence_service::EvidenceService,
+    kyc_service::KycService,
+    referral_service::ReferralService,
+    treasury_service::TreasuryService,
+    fee_service::FeeService,
+    contact_service::ContactService,
 };
-use security::audit::AuditLogger;
+use security::{audit::AuditLogger, guard::AuthorizationGuard, limits::TierLimiter, rate_limit::RateLimiter};
 
 static STORAGE_INIT: Once = Once::new();
 
@@ -39,6 +60,17 @@ thread_local! {
     pub static TRANSACTION_SERVICE: RefCell<TransactionService> = RefCell::new(TransactionService::new());
     pub static NOTIFICATION_SERVICE: RefCell<NotificationService> = RefCell::new(NotificationService::new());
     pub static BALANCE_SERVICE: RefCell<BalanceService> = RefCell::new(BalanceService::new());
+    pub static LEDGER_SERVICE: RefCell<LedgerService> = RefCell::new(LedgerService::new());
+    pub static ROLE_SERVICE: RefCell<RoleService> = RefCell::new(RoleService::new());
+    pub static EVIDENCE_SERVICE: RefCell<EvidenceService> = RefCell::new(EvidenceService::new());
+    pub static KYC_SERVICE: RefCell<KycService> = RefCell::new(KycService::new());
+    pub static REFERRAL_SERVICE: RefCell<ReferralService> = RefCell::new(ReferralService::new());
+    pub static TREASURY_SERVICE: RefCell<TreasuryService> = RefCell::new(TreasuryService::new());
+    pub static FEE_SERVICE: RefCell<FeeService> = RefCell::new(FeeService::new());
+    pub static CONTACT_SERVICE: RefCell<ContactService> = RefCell::new(ContactService::new());
+    pub static RATE_LIMITER: RefCell<RateLimiter> = RefCell::new(RateLimiter::new());
+    pub static AUTHORIZATION_GUARD: RefCell<AuthorizationGuard> = RefCell::new(AuthorizationGuard::new());
+    pub static TIER_LIMITER: RefCell<TierLimiter> = RefCell::new(TierLimiter::new());
 
     pub static SYSTEM_STATE: RefCell<SystemState> = RefCell::new(SystemState::default());
     pub static AUDIT_LOGGER: RefCell<AuditLogger> = RefCell::new(AuditLogger::with_defaults());
@@ -60,20 +92,20 @@ fn ensure_storage_initialized() {
 
 #[init]
 #[candid_method(init)]
-fn init() {
+fn init(args: Option<InitArgs>) {
     ensure_storage_initialized();
     INIT_TIMESTAMP.with(|t| *t.borrow_mut() = time());
     ic_cdk::println!("Elescrow canister initialized at {}", time());
 
     let handlers = WsHandlers {
         on_open: Some(|args: OnOpenCallbackArgs| {
-            messaging::on_client_open(args.client_principal);
+            services::events::on_client_open(args.client_principal);
         }),
         on_message: Some(|args: OnMessageCallbackArgs| {
             messaging::on_ws_message(args.client_principal, args.message);
         }),
         on_close: Some(|args: OnCloseCallbackArgs| {
-            messaging::on_client_close(args.client_principal);
+            services::events::on_client_close(args.client_principal);
         }),
     };
     let params = WsInitParams {
@@ -81,6 +113,31 @@ fn init() {
         ..WsInitParams::default()
     };
     ic_websocket_cdk::init(params);
+
+    ROLE_SERVICE.with(|s| {
+        s.borrow().bootstrap_super_admin(args.map(|a| a.super_admin), ic_cdk::api::msg_caller())
+    });
+
+    // `post_upgrade` goes through here too, which re-arms the timers the
+    // upgrade dropped.
+    services::scheduler::start();
+}
+
+/// Time of the last install or upgrade, in nanoseconds.
+pub fn init_timestamp() -> u64 {
+    INIT_TIMESTAMP.with(|t| *t.borrow())
+}
+
+// Rejected calls trap here, before the canister is charged for executing them.
+#[inspect_message]
+fn inspect_message() {
+    let method = ic_cdk::api::msg_method_name();
+    let arg_bytes = ic_cdk::api::msg_arg_data().len();
+
+    match security::inspect::check_ingress(&method, ic_cdk::api::msg_caller(), arg_bytes) {
+        Ok(()) => ic_cdk::api::accept_message(),
+        Err(reason) => ic_cdk::trap(&reason),
+    }
 }
 
 #[pre_upgrade]
@@ -89,8 +146,33 @@ fn pre_upgrade() {
 }
 
 #[post_upgrade]
-fn post_upgrade() {
-    init();
+fn post_upgrade(args: Option<InitArgs>) {
+    init(args);
+    restore_counters();
+    messaging::begin_conversation_backfill();
+
+    let migrated = BALANCE_SERVICE.with(|s| s.borrow_mut().migrate_legacy_balances());
+    if migrated > 0 {
+        ic_cdk::println!("Migrated {} legacy balances to per-currency storage", migrated);
+    }
+
+    let indexed = TRANSACTION_SERVICE.with(|s| s.borrow().index_recent_transactions())
+        + BALANCE_SERVICE.with(|s| s.borrow().index_recent_withdrawals());
+    if indexed > 0 {
+        ic_cdk::println!("Indexed {} recent transactions and withdrawals for tier limits", indexed);
+    }
+}
+
+// Counters are written through to stable memory, but builds that predate the
+// Configuration region kept them on the heap. Make sure no counter points at
+// an ID that is already taken.
+fn restore_counters() {
+    TRANSACTION_SERVICE.with(|s| s.borrow().restore_next_id());
+    NOTIFICATION_SERVICE.with(|s| s.borrow().restore_next_id());
+    AUDIT_LOGGER.with(|l| l.borrow().restore_next_id());
+    messaging::restore_next_message_id();
+
+    ic_cdk::println!("Restored {} persisted configuration entries", storage::config::entries().len());
 }
 
 export_service!();
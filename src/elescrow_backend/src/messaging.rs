@@ -1,12 +1,24 @@
 // Use the centralized memory management from your project
 use crate::storage::memory::{get_memory, Memory, MemoryRegion};
-use crate::types::messaging::{ConversationKey, Message, PaginationParams, PostResult, WsEvent, MAX_TEXT_BYTES};
+use crate::storage::config::{
+    self, StableCounter, CONVERSATION_BACKFILL_CURSOR, CONVERSATION_BACKFILL_END, MESSAGE_ID_COUNTER,
+    THREAD_MESSAGE_ID_COUNTER,
+};
+use crate::types::rate_limit::EndpointClass;
+use crate::types::guard::Operation;
+use crate::services::events::send_ws_event;
+use crate::types::messaging::{ContactStatus, ConversationKey, ConversationSummary, Message, MessageRequest, MessageRevision, PaginationParams, PostResult, ThreadMessage, WsEvent, MAX_TEXT_BYTES, MESSAGE_PREVIEW_CHARS};
+use crate::types::errors::ApiError;
+use crate::types::role::Permission;
+use crate::types::transaction::{Transaction, TransactionStatus};
+use crate::utils::constants::INDEX_BACKFILL_BATCH_SIZE;
+use crate::utils::time::now;
 use candid::{candid_method, Principal};
-use ic_cdk::api::{self, time};
+use ic_cdk::api;
 use ic_cdk_macros::*;
 // Import VirtualMemory explicitly
 use ic_stable_structures::{StableBTreeMap, Storable, DefaultMemoryImpl, memory_manager::VirtualMemory};
-use std::{borrow::Cow, cell::RefCell, collections::HashSet};
+use std::{borrow::Cow, cell::RefCell};
 use ic_websocket_cdk::{send};
 
 
@@ -18,22 +30,33 @@ thread_local! {
     static CONVERSATION_INDEX: RefCell<StableBTreeMap<Vec<u8>, (), Memory>> = RefCell::new(
         StableBTreeMap::init(get_memory(MemoryRegion::ConversationIndex))
     );
+    // Keyed by (transaction_id, message_id) so a thread is one range scan.
+    static THREAD_MESSAGES: RefCell<StableBTreeMap<(u64, u64), ThreadMessage, Memory>> = RefCell::new(
+        StableBTreeMap::init(get_memory(MemoryRegion::TransactionThreads))
+    );
+    // Keyed by (owner, counterpart): each user's conversation list, kept up
+    // to date as messages change so listing never scans CONVERSATION_INDEX.
+    static USER_CONVERSATIONS: RefCell<StableBTreeMap<(Principal, Principal), ConversationSummary, Memory>> = RefCell::new(
+        StableBTreeMap::init(get_memory(MemoryRegion::UserConversations))
+    );
+    // Keyed by (owner, u64::MAX - last_message_at, counterpart) so a page of
+    // the conversation list, newest first, is one range scan.
+    static RECENT_CONVERSATIONS: RefCell<StableBTreeMap<(Principal, u64, Principal), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(get_memory(MemoryRegion::RecentConversations))
+    );
+    // Keyed by (message_id, revision).
+    static MESSAGE_REVISIONS: RefCell<StableBTreeMap<(u64, u32), MessageRevision, Memory>> = RefCell::new(
+        StableBTreeMap::init(get_memory(MemoryRegion::MessageRevisions))
+    );
 
-    static NEXT_MESSAGE_ID: RefCell<u64> = RefCell::new(1);
-
-    static ONLINE_USERS: RefCell<HashSet<Principal>> = RefCell::new(HashSet::new());
 }
 
-pub fn on_client_open(principal: Principal) {
-    ONLINE_USERS.with_borrow_mut(|online_users| {
-        online_users.insert(principal);
-    });
-}
+const NEXT_MESSAGE_ID: StableCounter = StableCounter::new(MESSAGE_ID_COUNTER);
+const NEXT_THREAD_MESSAGE_ID: StableCounter = StableCounter::new(THREAD_MESSAGE_ID_COUNTER);
 
-pub fn on_client_close(principal: Principal) {
-    ONLINE_USERS.with_borrow_mut(|online_users| {
-        online_users.remove(&principal);
-    });
+pub fn restore_next_message_id() {
+    let last_id = MESSAGES.with_borrow(|m| m.last_key_value().map(|(id, _)| id));
+    NEXT_MESSAGE_ID.ensure_after(last_id);
 }
 
 pub fn on_ws_message(principal: Principal, message: Vec<u8>) {
@@ -42,45 +65,42 @@ pub fn on_ws_message(principal: Principal, message: Vec<u8>) {
     }
 }
 
-fn send_ws_event(user: Principal, event: WsEvent) {
-    match candid::encode_one(&event) {
-        Ok(event_bytes) => {
-            ONLINE_USERS.with_borrow(|online_users| {
-                if online_users.contains(&user) {
-                    match send(user, event_bytes) {
-                        Ok(_) => println!("WebSocket message sent to user: {}", user),
-                        Err(e) => eprintln!("Failed to send WebSocket message: {:?}", e),
-                    }
-                }
-            });
-        }
-        Err(e) => {
-            eprintln!("Failed to encode WsEvent: {:?}", e);
-        }
-    }
-}
-
 #[update]
 #[candid_method(update)]
 pub fn post_message(to: Principal, text: String) -> PostResult {
+    post_message_as(api::msg_caller(), to, text)
+}
+
+fn post_message_as(from: Principal, to: Principal, text: String) -> PostResult {
     if text.len() > MAX_TEXT_BYTES as usize {
         return PostResult::Err(format!("Message exceeds {} bytes limit", MAX_TEXT_BYTES));
     }
 
-    let from = api::caller();
-    let id = NEXT_MESSAGE_ID.with_borrow_mut(|id_ref| {
-        let id = *id_ref;
-        *id_ref += 1;
-        id
-    });
+    if let Err(e) = crate::RATE_LIMITER.with(|l| l.borrow().check(from, EndpointClass::Messaging)) {
+        return PostResult::Err(e.to_string());
+    }
+    if let Err(e) = crate::AUTHORIZATION_GUARD.with(|g| g.borrow().authorize(from, Operation::Messaging, None)) {
+        return PostResult::Err(e.to_string());
+    }
+    if crate::USER_SERVICE.with(|s| s.borrow().get_account(to)).is_none() {
+        return PostResult::Err("Recipient is not a registered user".to_string());
+    }
+    let is_request = match crate::CONTACT_SERVICE.with(|s| s.borrow().record_message(from, to)) {
+        Ok(is_request) => is_request,
+        Err(e) => return PostResult::Err(e.to_string()),
+    };
+
+    let id = NEXT_MESSAGE_ID.next();
 
     let message = Message {
         id,
         from,
         to,
         text,
-        timestamp: time(),
+        timestamp: now(),
         read: false,
+        edited_at: None,
+        deleted_at: None,
     };
 
     MESSAGES.with_borrow_mut(|m| m.insert(id, message.clone()));
@@ -88,13 +108,71 @@ pub fn post_message(to: Principal, text: String) -> PostResult {
     let conv_key = ConversationKey::new(from, to);
     let index_key = build_index_key(&conv_key, message.timestamp, id);
     CONVERSATION_INDEX.with_borrow_mut(|index| index.insert(index_key, ()));
+    record_in_conversations(&message);
 
-    send_ws_event(to, WsEvent::NewMessage(message.clone()));
+    let event = if is_request {
+        WsEvent::NewMessageRequest(message.clone())
+    } else {
+        WsEvent::NewMessage(message.clone())
+    };
+    send_ws_event(to, event);
     send_ws_event(from, WsEvent::NewMessage(message));
 
     PostResult::Ok
 }
 
+/// Senders whose first messages are waiting for the caller to accept.
+#[query]
+#[candid_method(query)]
+pub fn get_message_requests() -> Vec<MessageRequest> {
+    let me = api::msg_caller();
+    crate::CONTACT_SERVICE.with(|s| s.borrow().get_requests(me))
+}
+
+#[update]
+#[candid_method(update)]
+pub fn accept_message_request(from: Principal) -> Result<(), ApiError> {
+    let me = api::msg_caller();
+    crate::RATE_LIMITER.with(|l| l.borrow().check(me, EndpointClass::Messaging))?;
+    crate::AUTHORIZATION_GUARD.with(|g| g.borrow().authorize(me, Operation::Messaging, None))?;
+    crate::CONTACT_SERVICE.with(|s| s.borrow().accept_request(me, from))
+}
+
+#[update]
+#[candid_method(update)]
+pub fn decline_message_request(from: Principal) -> Result<(), ApiError> {
+    let me = api::msg_caller();
+    crate::RATE_LIMITER.with(|l| l.borrow().check(me, EndpointClass::Messaging))?;
+    crate::AUTHORIZATION_GUARD.with(|g| g.borrow().authorize(me, Operation::Messaging, None))?;
+    crate::CONTACT_SERVICE.with(|s| s.borrow().decline_request(me, from))
+}
+
+/// Stops `principal` from messaging the caller or sending them transactions.
+#[update]
+#[candid_method(update)]
+pub fn block_user(principal: Principal) -> Result<(), ApiError> {
+    let me = api::msg_caller();
+    crate::RATE_LIMITER.with(|l| l.borrow().check(me, EndpointClass::Messaging))?;
+    crate::AUTHORIZATION_GUARD.with(|g| g.borrow().authorize(me, Operation::Messaging, None))?;
+    crate::CONTACT_SERVICE.with(|s| s.borrow().block(me, principal))
+}
+
+#[update]
+#[candid_method(update)]
+pub fn unblock_user(principal: Principal) -> Result<(), ApiError> {
+    let me = api::msg_caller();
+    crate::RATE_LIMITER.with(|l| l.borrow().check(me, EndpointClass::Messaging))?;
+    crate::AUTHORIZATION_GUARD.with(|g| g.borrow().authorize(me, Operation::Messaging, None))?;
+    crate::CONTACT_SERVICE.with(|s| s.borrow().unblock(me, principal))
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_blocked_users() -> Vec<Principal> {
+    let me = api::msg_caller();
+    crate::CONTACT_SERVICE.with(|s| s.borrow().get_blocked(me))
+}
+
 #[query]
 #[candid_method(query)]
 pub fn get_conversation_chunk(with: Principal, params: PaginationParams) -> Vec<Message> {
@@ -122,7 +200,10 @@ pub fn get_conversation_chunk(with: Principal, params: PaginationParams) -> Vec<
 #[update]
 #[candid_method(update)]
 pub fn mark_message_read(message_id: u64) -> PostResult {
-    let caller = api::caller();
+    mark_message_read_as(api::msg_caller(), message_id)
+}
+
+fn mark_message_read_as(caller: Principal, message_id: u64) -> PostResult {
     MESSAGES.with_borrow_mut(|messages| {
         if let Some(mut message) = messages.get(&message_id) {
             if message.to != caller {
@@ -131,6 +212,9 @@ pub fn mark_message_read(message_id: u64) -> PostResult {
             if !message.read {
                 message.read = true;
                 messages.insert(message_id, message.clone());
+                if in_conversations(message_id) {
+                    decrement_unread(caller, message.from);
+                }
                 send_ws_event(message.from, WsEvent::MessageRead { message_id });
                 send_ws_event(caller, WsEvent::MessageRead { message_id });
             }
@@ -141,6 +225,420 @@ pub fn mark_message_read(message_id: u64) -> PostResult {
     })
 }
 
+/// Replaces the text of the caller's message, keeping the old text in its
+/// revision history.
+#[update]
+#[candid_method(update)]
+pub fn edit_message(message_id: u64, text: String) -> PostResult {
+    edit_message_as(api::msg_caller(), message_id, text)
+}
+
+fn edit_message_as(caller: Principal, message_id: u64, text: String) -> PostResult {
+    if text.len() > MAX_TEXT_BYTES as usize {
+        return PostResult::Err(format!("Message exceeds {} bytes limit", MAX_TEXT_BYTES));
+    }
+
+    if let Err(e) = crate::RATE_LIMITER.with(|l| l.borrow().check(caller, EndpointClass::Messaging)) {
+        return PostResult::Err(e.to_string());
+    }
+    if let Err(e) = crate::AUTHORIZATION_GUARD.with(|g| g.borrow().authorize(caller, Operation::Messaging, None)) {
+        return PostResult::Err(e.to_string());
+    }
+
+    let mut message = match own_message(message_id, caller) {
+        Ok(message) => message,
+        Err(e) => return PostResult::Err(e),
+    };
+    if message.deleted_at.is_some() {
+        return PostResult::Err("Message has been deleted".to_string());
+    }
+
+    let edited_at = now();
+    save_revision(&message, edited_at);
+    message.text = text;
+    message.edited_at = Some(edited_at);
+    MESSAGES.with_borrow_mut(|m| m.insert(message_id, message.clone()));
+    refresh_preview(&message);
+
+    send_ws_event(message.to, WsEvent::MessageEdited(message.clone()));
+    send_ws_event(message.from, WsEvent::MessageEdited(message));
+
+    PostResult::Ok
+}
+
+/// Soft-deletes the caller's message. The text moves to the revision history,
+/// where only moderators can still read it.
+#[update]
+#[candid_method(update)]
+pub fn delete_message(message_id: u64) -> PostResult {
+    delete_message_as(api::msg_caller(), message_id)
+}
+
+fn delete_message_as(caller: Principal, message_id: u64) -> PostResult {
+    if let Err(e) = crate::RATE_LIMITER.with(|l| l.borrow().check(caller, EndpointClass::Messaging)) {
+        return PostResult::Err(e.to_string());
+    }
+    if let Err(e) = crate::AUTHORIZATION_GUARD.with(|g| g.borrow().authorize(caller, Operation::Messaging, None)) {
+        return PostResult::Err(e.to_string());
+    }
+    let mut message = match own_message(message_id, caller) {
+        Ok(message) => message,
+        Err(e) => return PostResult::Err(e),
+    };
+    if message.deleted_at.is_some() {
+        return PostResult::Ok;
+    }
+
+    let deleted_at = now();
+    save_revision(&message, deleted_at);
+    message.text = String::new();
+    message.deleted_at = Some(deleted_at);
+    MESSAGES.with_borrow_mut(|m| m.insert(message_id, message.clone()));
+    refresh_preview(&message);
+
+    for user in [message.to, message.from] {
+        send_ws_event(user, WsEvent::MessageDeleted { message_id, permanent: false });
+    }
+
+    PostResult::Ok
+}
+
+/// Earlier texts of a message, oldest first.
+#[query]
+#[candid_method(query)]
+pub fn get_message_history(message_id: u64) -> Result<Vec<MessageRevision>, ApiError> {
+    let caller = api::msg_caller();
+    let message = MESSAGES
+        .with_borrow(|m| m.get(&message_id))
+        .ok_or_else(|| ApiError::NotFound { resource: format!("Message {}", message_id) })?;
+
+    let is_participant = message.from == caller || message.to == caller;
+    let can_read = (is_participant && message.deleted_at.is_none())
+        || crate::ROLE_SERVICE.with(|r| r.borrow().has_permission(caller, Permission::ModerateMessages));
+    if !can_read {
+        return Err(ApiError::Unauthorized {
+            reason: "Not authorized to view this message's history".to_string(),
+        });
+    }
+
+    Ok(MESSAGE_REVISIONS.with_borrow(|r| {
+        r.range((message_id, 0)..=(message_id, u32::MAX))
+            .map(|(_, revision)| revision)
+            .collect()
+    }))
+}
+
+/// Removes a message and its history for good. Callers check moderation
+/// rights and record the reason.
+pub(crate) fn hard_delete_message(message_id: u64) -> Result<Message, ApiError> {
+    let message = MESSAGES
+        .with_borrow_mut(|m| m.remove(&message_id))
+        .ok_or_else(|| ApiError::NotFound { resource: format!("Message {}", message_id) })?;
+
+    let index_key = build_index_key(&ConversationKey::new(message.from, message.to), message.timestamp, message_id);
+    CONVERSATION_INDEX.with_borrow_mut(|index| index.remove(&index_key));
+
+    if !message.read && in_conversations(message_id) {
+        decrement_unread(message.to, message.from);
+    }
+    for (owner, counterpart) in [(message.from, message.to), (message.to, message.from)] {
+        let is_last = USER_CONVERSATIONS
+            .with_borrow(|c| c.get(&(owner, counterpart)))
+            .is_some_and(|summary| summary.last_message_id == message_id);
+        if is_last {
+            replace_last_message(owner, counterpart);
+        }
+    }
+
+    MESSAGE_REVISIONS.with_borrow_mut(|r| {
+        let keys: Vec<(u64, u32)> = r
+            .range((message_id, 0)..=(message_id, u32::MAX))
+            .map(|(key, _)| key)
+            .collect();
+        for key in keys {
+            r.remove(&key);
+        }
+    });
+
+    for user in [message.to, message.from] {
+        send_ws_event(user, WsEvent::MessageDeleted { message_id, permanent: true });
+    }
+
+    Ok(message)
+}
+
+fn own_message(message_id: u64, caller: Principal) -> Result<Message, String> {
+    match MESSAGES.with_borrow(|m| m.get(&message_id)) {
+        Some(message) if message.from == caller => Ok(message),
+        Some(_) => Err("Not message author".to_string()),
+        None => Err("Message not found".to_string()),
+    }
+}
+
+fn save_revision(message: &Message, replaced_at: u64) {
+    MESSAGE_REVISIONS.with_borrow_mut(|r| {
+        let revision = r.range((message.id, 0)..=(message.id, u32::MAX)).count() as u32;
+        r.insert((message.id, revision), MessageRevision {
+            message_id: message.id,
+            revision,
+            text: message.text.clone(),
+            replaced_at,
+        });
+    });
+}
+
+/// Posts to the chat thread of a transaction. Only the sender, recipient and
+/// escrow agent take part in the thread.
+#[update]
+#[candid_method(update)]
+pub fn post_transaction_message(transaction_id: u64, text: String) -> Result<ThreadMessage, ApiError> {
+    post_transaction_message_as(api::msg_caller(), transaction_id, text)
+}
+
+fn post_transaction_message_as(from: Principal, transaction_id: u64, text: String) -> Result<ThreadMessage, ApiError> {
+    if text.len() > MAX_TEXT_BYTES as usize {
+        return Err(ApiError::ValidationError {
+            field: "text".to_string(),
+            message: format!("Message exceeds {} bytes limit", MAX_TEXT_BYTES),
+        });
+    }
+
+    crate::RATE_LIMITER.with(|l| l.borrow().check(from, EndpointClass::Messaging))?;
+    crate::AUTHORIZATION_GUARD.with(|g| g.borrow().authorize(from, Operation::Messaging, None))?;
+
+    let transaction = crate::TRANSACTION_SERVICE.with(|s| s.borrow().get_transaction_unchecked(transaction_id))?;
+    let participants = thread_participants(&transaction);
+    if !participants.contains(&from) {
+        return Err(ApiError::Unauthorized {
+            reason: "Only the parties and the escrow agent can post to a transaction thread".to_string(),
+        });
+    }
+
+    let message = ThreadMessage {
+        id: NEXT_THREAD_MESSAGE_ID.next(),
+        transaction_id,
+        from,
+        text,
+        timestamp: now(),
+    };
+    THREAD_MESSAGES.with_borrow_mut(|m| m.insert((transaction_id, message.id), message.clone()));
+
+    for participant in participants {
+        send_ws_event(participant, WsEvent::NewThreadMessage(message.clone()));
+    }
+
+    Ok(message)
+}
+
+/// Newest messages first. Arbiters can read the thread of a disputed
+/// transaction, where it serves as evidence.
+#[query]
+#[candid_method(query)]
+pub fn get_transaction_thread(transaction_id: u64, params: PaginationParams) -> Result<Vec<ThreadMessage>, ApiError> {
+    get_transaction_thread_as(api::msg_caller(), transaction_id, params)
+}
+
+fn get_transaction_thread_as(
+    me: Principal,
+    transaction_id: u64,
+    params: PaginationParams,
+) -> Result<Vec<ThreadMessage>, ApiError> {
+    let transaction = crate::TRANSACTION_SERVICE.with(|s| s.borrow().get_transaction_unchecked(transaction_id))?;
+
+    let in_dispute = matches!(
+        transaction.status,
+        TransactionStatus::Disputed { .. } | TransactionStatus::UnderReview { .. }
+    );
+    let can_read = thread_participants(&transaction).contains(&me)
+        || (in_dispute && crate::ROLE_SERVICE.with(|r| r.borrow().has_permission(me, Permission::ResolveDisputes)));
+    if !can_read {
+        return Err(ApiError::Unauthorized {
+            reason: "Not authorized to view this transaction thread".to_string(),
+        });
+    }
+
+    let limit = params.limit.unwrap_or(50).min(100) as usize;
+    let offset = params.offset.unwrap_or(0) as usize;
+
+    Ok(THREAD_MESSAGES.with_borrow(|m| {
+        m.range((transaction_id, 0)..=(transaction_id, u64::MAX))
+            .rev()
+            .skip(offset)
+            .take(limit)
+            .map(|(_, message)| message)
+            .collect()
+    }))
+}
+
+fn thread_participants(transaction: &Transaction) -> Vec<Principal> {
+    std::iter::once(transaction.from)
+        .chain(std::iter::once(transaction.to))
+        .chain(transaction.escrow_agent)
+        .collect()
+}
+
+/// The caller's conversations, most recent first. Pending message requests
+/// are listed by `get_message_requests` instead.
+#[query]
+#[candid_method(query)]
+pub fn get_my_conversations(params: PaginationParams) -> Vec<ConversationSummary> {
+    get_conversations_of(api::msg_caller(), params)
+}
+
+fn get_conversations_of(me: Principal, params: PaginationParams) -> Vec<ConversationSummary> {
+    let limit = params.limit.unwrap_or(50).min(100) as usize;
+    let offset = params.offset.unwrap_or(0) as usize;
+
+    crate::CONTACT_SERVICE.with(|s| {
+        let contacts = s.borrow();
+        RECENT_CONVERSATIONS.with_borrow(|recent| {
+            recent
+                .range((me, 0, Principal::management_canister())..)
+                .take_while(|((owner, _, _), _)| *owner == me)
+                .filter(|((_, _, counterpart), _)| {
+                    !matches!(contacts.get_status(me, *counterpart), Some(ContactStatus::Requested { .. }))
+                })
+                .skip(offset)
+                .take(limit)
+                .filter_map(|((_, _, counterpart), _)| USER_CONVERSATIONS.with_borrow(|c| c.get(&(me, counterpart))))
+                .collect()
+        })
+    })
+}
+
+/// Marks the messages stored so far for `backfill_user_conversations` after
+/// upgrading from a build that did not keep conversation lists.
+pub fn begin_conversation_backfill() {
+    if config::get(CONVERSATION_BACKFILL_END).is_some() {
+        return;
+    }
+    let end = if USER_CONVERSATIONS.with_borrow(|c| c.is_empty()) { NEXT_MESSAGE_ID.peek() } else { 0 };
+    config::set(CONVERSATION_BACKFILL_END, end);
+}
+
+/// Adds the next batch of older messages to the conversation lists. Returns
+/// true once all of them have been added.
+pub fn backfill_user_conversations() -> bool {
+    let Some(end) = config::get(CONVERSATION_BACKFILL_END) else {
+        return true;
+    };
+    let start = config::get_or(CONVERSATION_BACKFILL_CURSOR, 0);
+    if start >= end {
+        return true;
+    }
+
+    let batch_end = start.saturating_add(INDEX_BACKFILL_BATCH_SIZE).min(end);
+    let messages: Vec<Message> = MESSAGES.with_borrow(|m| m.range(start..batch_end).map(|(_, message)| message).collect());
+    for message in &messages {
+        record_in_conversations(message);
+    }
+
+    config::set(CONVERSATION_BACKFILL_CURSOR, batch_end);
+    batch_end >= end
+}
+
+/// Whether the message is counted in the conversation lists yet; older
+/// messages are only once the backfill reaches them.
+fn in_conversations(message_id: u64) -> bool {
+    config::get(CONVERSATION_BACKFILL_END).is_none_or(|end| {
+        message_id >= end || message_id < config::get_or(CONVERSATION_BACKFILL_CURSOR, 0)
+    })
+}
+
+fn record_in_conversations(message: &Message) {
+    for (owner, counterpart) in [(message.from, message.to), (message.to, message.from)] {
+        let current = USER_CONVERSATIONS.with_borrow(|c| c.get(&(owner, counterpart)));
+        let mut unread_count = current.as_ref().map_or(0, |summary| summary.unread_count);
+        if owner == message.to && !message.read {
+            unread_count += 1;
+        }
+
+        // The backfill can reach a message after newer ones were recorded.
+        let summary = match current {
+            Some(summary) if (summary.last_message_at, summary.last_message_id) > (message.timestamp, message.id) => {
+                ConversationSummary { unread_count, ..summary }
+            }
+            _ => ConversationSummary {
+                counterpart,
+                last_message_id: message.id,
+                last_message_from: message.from,
+                last_message_preview: preview(message),
+                last_message_at: message.timestamp,
+                unread_count,
+            },
+        };
+        save_summary(owner, summary);
+    }
+}
+
+/// Stores a conversation summary and moves it to its place in the owner's
+/// recency order.
+fn save_summary(owner: Principal, summary: ConversationSummary) {
+    let replaced = USER_CONVERSATIONS.with_borrow_mut(|c| c.insert((owner, summary.counterpart), summary.clone()));
+    RECENT_CONVERSATIONS.with_borrow_mut(|recent| {
+        if let Some(replaced) = replaced {
+            recent.remove(&recency_key(owner, &replaced));
+        }
+        recent.insert(recency_key(owner, &summary), ());
+    });
+}
+
+fn remove_summary(owner: Principal, counterpart: Principal) {
+    if let Some(removed) = USER_CONVERSATIONS.with_borrow_mut(|c| c.remove(&(owner, counterpart))) {
+        RECENT_CONVERSATIONS.with_borrow_mut(|recent| recent.remove(&recency_key(owner, &removed)));
+    }
+}
+
+fn recency_key(owner: Principal, summary: &ConversationSummary) -> (Principal, u64, Principal) {
+    (owner, u64::MAX - summary.last_message_at, summary.counterpart)
+}
+
+fn refresh_preview(message: &Message) {
+    for (owner, counterpart) in [(message.from, message.to), (message.to, message.from)] {
+        if let Some(mut summary) = USER_CONVERSATIONS.with_borrow(|c| c.get(&(owner, counterpart))) {
+            if summary.last_message_id == message.id {
+                summary.last_message_preview = preview(message);
+                save_summary(owner, summary);
+            }
+        }
+    }
+}
+
+fn decrement_unread(owner: Principal, counterpart: Principal) {
+    if let Some(mut summary) = USER_CONVERSATIONS.with_borrow(|c| c.get(&(owner, counterpart))) {
+        summary.unread_count = summary.unread_count.saturating_sub(1);
+        save_summary(owner, summary);
+    }
+}
+
+/// Points the summary at the newest remaining message of the conversation,
+/// or drops it once the conversation is empty.
+fn replace_last_message(owner: Principal, counterpart: Principal) {
+    let prefix = ConversationKey::new(owner, counterpart).to_bytes().into_owned();
+    let latest = CONVERSATION_INDEX.with_borrow(|index| {
+        index
+            .range(prefix.clone()..)
+            .next()
+            .filter(|(key, _)| key.starts_with(&prefix))
+            .and_then(|(key, _)| MESSAGES.with_borrow(|m| m.get(&parse_index_key(&key).2)))
+    });
+
+    match (latest, USER_CONVERSATIONS.with_borrow(|c| c.get(&(owner, counterpart)))) {
+        (Some(message), Some(mut summary)) => {
+            summary.last_message_id = message.id;
+            summary.last_message_from = message.from;
+            summary.last_message_preview = preview(&message);
+            summary.last_message_at = message.timestamp;
+            save_summary(owner, summary);
+        }
+        _ => remove_summary(owner, counterpart),
+    }
+}
+
+fn preview(message: &Message) -> String {
+    message.text.chars().take(MESSAGE_PREVIEW_CHARS).collect()
+}
+
 fn build_index_key(conv_key: &ConversationKey, timestamp: u64, message_id: u64) -> Vec<u8> {
     let mut key = conv_key.to_bytes().into_owned();
     key.extend((u64::MAX - timestamp).to_be_bytes());
@@ -161,4 +659,208 @@ fn parse_index_key(key: &[u8]) -> (ConversationKey, u64, u64) {
 #[candid_method(query)]
 pub fn get_message_count() -> u64 {
     MESSAGES.with(|messages| messages.borrow().len())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{
+        admin, agent as carol, agent_escrow_request, disputed_escrow, fund, recipient as bob, register, sender as alice,
+    };
+    use crate::types::role::Role;
+    use crate::types::transaction::DisputeResolution;
+    use crate::utils::constants::NANOS_PER_SECOND;
+    use crate::utils::time::advance_mock_time;
+
+    fn setup() {
+        for user in [alice(), bob(), carol()] {
+            register(user);
+        }
+    }
+
+    /// Posts and returns the new message's ID, one second after the last one.
+    fn post(from: Principal, to: Principal, text: &str) -> u64 {
+        advance_mock_time(NANOS_PER_SECOND);
+        assert_eq!(post_message_as(from, to, text.to_string()), PostResult::Ok);
+        MESSAGES.with_borrow(|m| m.last_key_value()).unwrap().0
+    }
+
+    fn summary(owner: Principal, counterpart: Principal) -> Option<ConversationSummary> {
+        USER_CONVERSATIONS.with_borrow(|c| c.get(&(owner, counterpart)))
+    }
+
+    fn counterparts(owner: Principal, offset: u64, limit: u64) -> Vec<Principal> {
+        get_conversations_of(owner, PaginationParams { offset: Some(offset), limit: Some(limit) })
+            .into_iter()
+            .map(|summary| summary.counterpart)
+            .collect()
+    }
+
+    #[test]
+    fn test_unread_count_follows_posts_and_reads() {
+        setup();
+        let first = post(alice(), bob(), "Hi");
+        post(alice(), bob(), "Are you there?");
+
+        assert_eq!(summary(bob(), alice()).unwrap().unread_count, 2);
+        assert_eq!(summary(alice(), bob()).unwrap().unread_count, 0);
+
+        assert_eq!(mark_message_read_as(bob(), first), PostResult::Ok);
+        assert_eq!(mark_message_read_as(bob(), first), PostResult::Ok);
+        assert_eq!(summary(bob(), alice()).unwrap().unread_count, 1);
+    }
+
+    #[test]
+    fn test_preview_follows_edits_and_soft_deletes() {
+        setup();
+        let first = post(alice(), bob(), "First");
+        let last = post(alice(), bob(), "Second");
+
+        assert_eq!(edit_message_as(alice(), first, "First, edited".to_string()), PostResult::Ok);
+        assert_eq!(summary(bob(), alice()).unwrap().last_message_preview, "Second");
+
+        assert_eq!(edit_message_as(alice(), last, "Second, edited".to_string()), PostResult::Ok);
+        assert_eq!(summary(bob(), alice()).unwrap().last_message_preview, "Second, edited");
+        assert_eq!(summary(alice(), bob()).unwrap().last_message_preview, "Second, edited");
+
+        assert_eq!(delete_message_as(alice(), last), PostResult::Ok);
+        let deleted = summary(bob(), alice()).unwrap();
+        assert_eq!(deleted.last_message_id, last);
+        assert_eq!(deleted.last_message_preview, "");
+    }
+
+    #[test]
+    fn test_hard_delete_falls_back_to_previous_message() {
+        setup();
+        let first = post(alice(), bob(), "First");
+        let last = post(bob(), alice(), "Reply");
+
+        hard_delete_message(last).unwrap();
+        let remaining = summary(alice(), bob()).unwrap();
+        assert_eq!(remaining.last_message_id, first);
+        assert_eq!(remaining.last_message_preview, "First");
+        assert_eq!(remaining.unread_count, 0);
+        assert_eq!(counterparts(alice(), 0, 10), vec![bob()]);
+
+        hard_delete_message(first).unwrap();
+        assert!(summary(alice(), bob()).is_none());
+        assert!(summary(bob(), alice()).is_none());
+        assert!(counterparts(alice(), 0, 10).is_empty());
+        assert!(RECENT_CONVERSATIONS.with_borrow(|r| r.is_empty()));
+    }
+
+    #[test]
+    fn test_conversations_are_paged_newest_first_without_requests() {
+        setup();
+        post(alice(), bob(), "Hi Bob");
+        post(bob(), alice(), "Hi Alice");
+        post(carol(), alice(), "Hi, we have not met");
+        post(alice(), carol(), "Hello Carol");
+        post(carol(), bob(), "Hi, we have not met either");
+
+        assert_eq!(counterparts(alice(), 0, 10), vec![carol(), bob()]);
+        assert_eq!(counterparts(alice(), 1, 10), vec![bob()]);
+        assert_eq!(counterparts(alice(), 0, 1), vec![carol()]);
+
+        // Carol's first message to Bob is still a request.
+        assert_eq!(counterparts(bob(), 0, 10), vec![alice()]);
+        assert_eq!(counterparts(carol(), 0, 10), vec![bob(), alice()]);
+
+        post(bob(), alice(), "Still there?");
+        assert_eq!(counterparts(alice(), 0, 10), vec![bob(), carol()]);
+        assert_eq!(RECENT_CONVERSATIONS.with_borrow(|r| r.len()), 6);
+    }
+
+    #[test]
+    fn test_backfill_builds_conversations_from_older_messages() {
+        setup();
+        let read = post(alice(), bob(), "Old and read");
+        post(alice(), bob(), "Old and unread");
+        post(carol(), bob(), "Old request");
+        mark_message_read_as(bob(), read);
+
+        // Start over as if upgrading from a build without conversation lists.
+        USER_CONVERSATIONS.with_borrow_mut(|c| c.clear_new());
+        RECENT_CONVERSATIONS.with_borrow_mut(|r| r.clear_new());
+        begin_conversation_backfill();
+
+        let live = post(alice(), bob(), "New");
+        assert_eq!(summary(bob(), alice()).unwrap().unread_count, 1);
+
+        assert!(backfill_user_conversations());
+        let from_alice = summary(bob(), alice()).unwrap();
+        assert_eq!(from_alice.last_message_id, live);
+        assert_eq!(from_alice.unread_count, 2);
+        assert_eq!(summary(bob(), carol()).unwrap().unread_count, 1);
+        assert_eq!(counterparts(carol(), 0, 10), vec![bob()]);
+
+        assert!(backfill_user_conversations());
+        begin_conversation_backfill();
+        assert_eq!(summary(bob(), alice()).unwrap().unread_count, 2);
+    }
+
+    fn dave() -> Principal {
+        Principal::from_slice(&[4; 29])
+    }
+
+    fn eve() -> Principal {
+        Principal::from_slice(&[5; 29])
+    }
+
+    /// An escrow from Alice to Bob with Carol as agent, disputed by Alice.
+    /// Dave arbitrates disputes; Eve has nothing to do with it.
+    fn disputed_thread() -> Transaction {
+        setup();
+        for user in [dave(), eve()] {
+            register(user);
+        }
+        crate::ROLE_SERVICE.with(|s| {
+            let roles = s.borrow();
+            roles.bootstrap_super_admin(Some(admin()), admin());
+            roles.grant_role(admin(), dave(), Role::DisputeArbiter).unwrap();
+        });
+        fund(alice(), 1_000_000);
+
+        crate::TRANSACTION_SERVICE.with(|s| disputed_escrow(&mut s.borrow_mut(), agent_escrow_request()))
+    }
+
+    fn thread_len(reader: Principal, transaction_id: u64) -> Result<usize, ApiError> {
+        get_transaction_thread_as(reader, transaction_id, PaginationParams::default()).map(|thread| thread.len())
+    }
+
+    #[test]
+    fn test_only_participants_post_to_a_thread() {
+        let tx = disputed_thread();
+        for participant in [alice(), bob(), carol()] {
+            post_transaction_message_as(participant, tx.id, "About the delivery".to_string()).unwrap();
+        }
+
+        for outsider in [dave(), eve()] {
+            assert!(matches!(
+                post_transaction_message_as(outsider, tx.id, "Let me in".to_string()),
+                Err(ApiError::Unauthorized { .. })
+            ));
+        }
+        assert_eq!(thread_len(alice(), tx.id).unwrap(), 3);
+        assert!(matches!(thread_len(eve(), tx.id), Err(ApiError::Unauthorized { .. })));
+    }
+
+    #[test]
+    fn test_arbiter_reads_thread_only_during_dispute() {
+        let tx = disputed_thread();
+        post_transaction_message_as(alice(), tx.id, "The logo is missing".to_string()).unwrap();
+        assert_eq!(thread_len(dave(), tx.id).unwrap(), 1);
+
+        crate::TRANSACTION_SERVICE.with(|s| {
+            let service = s.borrow();
+            service.accept_agent_role(tx.id, carol()).unwrap();
+            service.start_dispute_review(tx.id, carol()).unwrap();
+        });
+        assert_eq!(thread_len(dave(), tx.id).unwrap(), 1);
+
+        crate::TRANSACTION_SERVICE.with(|s| {
+            s.borrow_mut().resolve_dispute(tx.id, DisputeResolution::ReleaseToRecipient, carol())
+        }).unwrap();
+        assert!(matches!(thread_len(dave(), tx.id), Err(ApiError::Unauthorized { .. })));
+        assert_eq!(thread_len(bob(), tx.id).unwrap(), 1);
+    }
+}
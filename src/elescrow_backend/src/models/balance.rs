@@ -1,6 +1,10 @@
+use candid::Principal;
 use ic_stable_structures::Storable;
 use std::borrow::Cow;
-use crate::types::transaction::{Balance};
+use crate::types::transaction::{Balance, Currency};
+use crate::types::messaging::MAX_PRINCIPAL_BYTES;
+
+pub const MAX_CURRENCY_CODE_BYTES: u32 = 32;
 
 impl Storable for Balance {
     const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
@@ -16,4 +20,95 @@ impl Storable for Balance {
     fn from_bytes(bytes: Cow<[u8]>) -> Self {
         serde_cbor::from_slice(&bytes).expect("Failed to deserialize Balance")
     }
-}
\ No newline at end of file
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct BalanceKey {
+    pub principal: Principal,
+    pub currency_code: String,
+}
+
+impl BalanceKey {
+    pub fn new(principal: Principal, currency: &Currency) -> Self {
+        Self {
+            principal,
+            currency_code: currency.code(),
+        }
+    }
+
+    /// Smallest key for `principal`, used as the lower bound when scanning
+    /// all currencies a user holds.
+    pub fn first_for(principal: Principal) -> Self {
+        Self {
+            principal,
+            currency_code: String::new(),
+        }
+    }
+}
+
+impl Storable for BalanceKey {
+    const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
+        max_size: 1 + MAX_PRINCIPAL_BYTES + MAX_CURRENCY_CODE_BYTES,
+        is_fixed_size: false,
+    };
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let principal_slice = self.principal.as_slice();
+        let mut bytes = Vec::with_capacity(1 + principal_slice.len() + self.currency_code.len());
+        bytes.push(principal_slice.len() as u8);
+        bytes.extend(principal_slice);
+        bytes.extend(self.currency_code.as_bytes());
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        let principal_len = bytes[0] as usize;
+        let principal = Principal::from_slice(&bytes[1..1 + principal_len]);
+        let currency_code = String::from_utf8_lossy(&bytes[1 + principal_len..]).into_owned();
+        Self {
+            principal,
+            currency_code,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_balance_key_roundtrip() {
+        let principal = Principal::from_text("2vxsx-fae").unwrap();
+        let currencies = [
+            Currency::ICP,
+            Currency::Cycles,
+            Currency::USDT,
+            Currency::Custom { symbol: "CKBTC".to_string(), decimals: 8 },
+        ];
+
+        for currency in currencies.iter() {
+            let key = BalanceKey::new(principal, currency);
+            let decoded = BalanceKey::from_bytes(key.to_bytes());
+            assert_eq!(decoded, key);
+            assert_eq!(decoded.currency_code, currency.code());
+        }
+    }
+
+    #[test]
+    fn test_balance_keys_group_by_principal() {
+        let alice = Principal::from_slice(&[1; 29]);
+        let bob = Principal::from_slice(&[2; 29]);
+
+        let mut keys = [
+            BalanceKey::new(bob, &Currency::ICP),
+            BalanceKey::new(alice, &Currency::USDT),
+            BalanceKey::new(alice, &Currency::ICP),
+            BalanceKey::first_for(alice),
+        ];
+        keys.sort();
+
+        assert_eq!(keys[0], BalanceKey::first_for(alice));
+        assert!(keys[1..3].iter().all(|k| k.principal == alice));
+        assert_eq!(keys[3].principal, bob);
+    }
+}
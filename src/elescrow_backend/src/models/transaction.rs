@@ -21,11 +21,21 @@ pub struct TransactionModel {
     pub completed_at: Option<u64>,
     pub deadline: Option<u64>,
     pub metadata: TransactionMetadata,
+    // Due time the last `EscrowExpiring` notice was sent for, so the
+    // scheduler announces each deadline once.
+    #[serde(default)]
+    pub expiry_notice_for: Option<u64>,
+    #[serde(default)]
+    pub agent_fee_bps: Option<u64>,
+    #[serde(default)]
+    pub agent_accepted_at: Option<u64>,
 }
 
 impl Storable for TransactionModel {
+    // Room for MAX_MILESTONES milestones, each disputed with a full-length
+    // reason, and a full set of evidence attachments.
     const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
-        max_size: 4096,
+        max_size: 16384,
         is_fixed_size: false,
     };
 
@@ -57,6 +67,8 @@ impl From<TransactionModel> for Transaction {
             completed_at: model.completed_at,
             deadline: model.deadline,
             metadata: model.metadata,
+            agent_fee_bps: model.agent_fee_bps,
+            agent_accepted_at: model.agent_accepted_at,
         }
     }
 }
@@ -79,6 +91,9 @@ impl From<Transaction> for TransactionModel {
             completed_at: tx.completed_at,
             deadline: tx.deadline,
             metadata: tx.metadata,
+            expiry_notice_for: None,
+            agent_fee_bps: tx.agent_fee_bps,
+            agent_accepted_at: tx.agent_accepted_at,
         }
     }
 }
\ No newline at end of file
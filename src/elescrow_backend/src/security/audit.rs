@@ -1,12 +1,15 @@
 use candid::{CandidType, Principal};
 use serde::{Deserialize, Serialize};
-use ic_cdk::api::time;
+use crate::utils::time::now;
 use crate::types::common::{AuditLog, AuditAction, PaginationParams};
 use crate::types::errors::ApiError;
-use crate::storage::{stable_storage::StableStorage, memory::MemoryRegion};
+use crate::storage::{
+    stable_storage::StableStorage,
+    memory::MemoryRegion,
+    config::{StableCounter, AUDIT_LOG_ID_COUNTER},
+};
 use ic_stable_structures::Storable;
 use std::borrow::Cow;
-use std::cell::RefCell;
 
 #[derive(Clone, Debug)]
 pub struct AuditConfig {
@@ -44,7 +47,7 @@ impl Storable for AuditLog {
 pub struct AuditLogger {
     storage: StableStorage<u64, AuditLog>,
     config: AuditConfig,
-    next_id: RefCell<u64>,
+    next_id: StableCounter,
 }
 
 impl AuditLogger {
@@ -52,7 +55,7 @@ impl AuditLogger {
         Self {
             storage: StableStorage::new(MemoryRegion::AuditLogs),
             config,
-            next_id: RefCell::new(1),
+            next_id: StableCounter::new(AUDIT_LOG_ID_COUNTER),
         }
     }
     
@@ -71,7 +74,7 @@ impl AuditLogger {
         
         let log = AuditLog {
             id,
-            timestamp: time(),
+            timestamp: now(),
             principal,
             action,
             resource: resource.to_string(),
@@ -102,7 +105,7 @@ impl AuditLogger {
         
         let log = AuditLog {
             id,
-            timestamp: time(),
+            timestamp: now(),
             principal,
             action,
             resource: resource.to_string(),
@@ -209,7 +212,7 @@ impl AuditLogger {
     
     pub fn get_statistics(&self) -> AuditStatistics {
         let total_entries = self.storage.len();
-        let now = time();
+        let now = now();
         let day_ago = now - (24 * 60 * 60 * 1_000_000_000);
         let week_ago = now - (7 * 24 * 60 * 60 * 1_000_000_000);
         
@@ -259,15 +262,16 @@ impl AuditLogger {
     }
     
     fn get_next_id(&self) -> u64 {
-        let mut id = self.next_id.borrow_mut();
-        let current = *id;
-        *id += 1;
-        current
+        self.next_id.next()
+    }
+
+    pub fn restore_next_id(&self) {
+        self.next_id.ensure_after(self.storage.last_key());
     }
     
     fn cleanup_old_entries(&self) -> u64 {
         let retention_ns = self.config.retention_days as u64 * 24 * 60 * 60 * 1_000_000_000;
-        let cutoff_time = time() - retention_ns;
+        let cutoff_time = now() - retention_ns;
 
         let to_remove: Vec<u64> = self.storage
             .filter(|_, log| log.timestamp < cutoff_time)
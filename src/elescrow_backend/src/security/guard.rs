@@ -0,0 +1,242 @@
+use candid::Principal;
+
+use crate::types::{
+    errors::ApiError,
+    common::AuditAction,
+    guard::*,
+    transaction::Currency,
+    user::VerificationLevel,
+};
+use crate::storage::config;
+use crate::{AUDIT_LOGGER, USER_SERVICE};
+
+const THRESHOLD_PREFIX: &str = "config.guard.threshold.";
+
+/// Central check run by every user-facing update endpoint before it touches
+/// funds or state. The caller must be registered, active and not frozen, and
+/// verified to at least the operation's level, raised further by any amount
+/// threshold the request crosses. Levels and thresholds are admin-configurable
+/// and kept in the `Configuration` region.
+pub struct AuthorizationGuard;
+
+impl AuthorizationGuard {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// `amount` is the value moved by the request, if any, in base units.
+    pub fn authorize(
+        &self,
+        caller: Principal,
+        operation: Operation,
+        amount: Option<(u64, &Currency)>,
+    ) -> Result<(), ApiError> {
+        if caller == Principal::anonymous() {
+            return Err(ApiError::Unauthorized {
+                reason: "Anonymous callers are not allowed".to_string(),
+            });
+        }
+
+        let account = USER_SERVICE
+            .with(|s| s.borrow().get_account(caller))
+            .ok_or_else(|| ApiError::Unauthorized {
+                reason: "User is not registered".to_string(),
+            })?;
+
+        if !account.is_active {
+            return Err(ApiError::Unauthorized {
+                reason: "Account is deactivated".to_string(),
+            });
+        }
+
+        if account.is_frozen {
+            return Err(ApiError::AccountFrozen {
+                reason: account.freeze_reason.unwrap_or_default(),
+            });
+        }
+
+        if account.verification_level < self.required_level(operation, amount) {
+            return Err(ApiError::AccountNotVerified);
+        }
+
+        Ok(())
+    }
+
+    pub fn required_level(&self, operation: Operation, amount: Option<(u64, &Currency)>) -> VerificationLevel {
+        let mut required = self.get_operation_level(operation);
+
+        if let Some((amount, currency)) = amount {
+            for level in [VerificationLevel::Standard, VerificationLevel::Enhanced] {
+                let threshold = config::get(&threshold_key(currency, &level));
+                if threshold.is_some_and(|min_amount| amount >= min_amount) && level > required {
+                    required = level;
+                }
+            }
+        }
+
+        required
+    }
+
+    pub fn get_operation_level(&self, operation: Operation) -> VerificationLevel {
+        level_from_u64(config::get_or(&operation_key(operation), 0))
+    }
+
+    pub fn set_operation_level(&self, admin: Principal, operation: Operation, level: VerificationLevel) {
+        config::set(&operation_key(operation), level_to_u64(&level));
+
+        AUDIT_LOGGER.with(|l| l.borrow().log(
+            admin,
+            AuditAction::ConfigurationChanged,
+            "authorization_guard",
+            Some(format!("{} requires {:?}", operation.key(), level)),
+        ));
+    }
+
+    /// Sets or, with `min_amount: None`, removes the amount from which `level`
+    /// is required for `currency`. Basic is everyone's floor, so it cannot be
+    /// used as a threshold.
+    pub fn set_amount_threshold(
+        &self,
+        admin: Principal,
+        currency: Currency,
+        level: VerificationLevel,
+        min_amount: Option<u64>,
+    ) -> Result<(), ApiError> {
+        if level == VerificationLevel::Basic {
+            return Err(ApiError::ValidationError {
+                field: "level".to_string(),
+                message: "Thresholds can only require Standard or Enhanced verification".to_string(),
+            });
+        }
+
+        let key = threshold_key(&currency, &level);
+        match min_amount {
+            Some(min_amount) => config::set(&key, min_amount),
+            None => config::remove(&key),
+        }
+
+        AUDIT_LOGGER.with(|l| l.borrow().log(
+            admin,
+            AuditAction::ConfigurationChanged,
+            "authorization_guard",
+            Some(format!("{} {:?} threshold: {:?}", currency.code(), level, min_amount)),
+        ));
+
+        Ok(())
+    }
+
+    pub fn get_policy(&self) -> GuardPolicy {
+        let operation_levels = Operation::ALL
+            .iter()
+            .map(|operation| (*operation, self.get_operation_level(*operation)))
+            .collect();
+
+        let amount_thresholds = config::entries()
+            .into_iter()
+            .filter_map(|(key, min_amount)| {
+                let (currency, level) = key.strip_prefix(THRESHOLD_PREFIX)?.rsplit_once('.')?;
+                Some(AmountThreshold {
+                    currency: Currency::from_code(currency)?,
+                    level: level_from_u64(level.parse().ok()?),
+                    min_amount,
+                })
+            })
+            .collect();
+
+        GuardPolicy {
+            operation_levels,
+            amount_thresholds,
+        }
+    }
+}
+
+fn operation_key(operation: Operation) -> String {
+    format!("config.guard.{}.min_level", operation.key())
+}
+
+fn threshold_key(currency: &Currency, level: &VerificationLevel) -> String {
+    format!("{}{}.{}", THRESHOLD_PREFIX, currency.code(), level_to_u64(level))
+}
+
+fn level_to_u64(level: &VerificationLevel) -> u64 {
+    match level {
+        VerificationLevel::Basic => 0,
+        VerificationLevel::Standard => 1,
+        VerificationLevel::Enhanced => 2,
+    }
+}
+
+fn level_from_u64(value: u64) -> VerificationLevel {
+    match value {
+        0 => VerificationLevel::Basic,
+        1 => VerificationLevel::Standard,
+        _ => VerificationLevel::Enhanced,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{admin, register};
+
+    #[test]
+    fn test_rejects_unregistered_frozen_and_inactive() {
+        let guard = AuthorizationGuard::new();
+        let user = Principal::from_slice(&[1; 29]);
+
+        assert!(matches!(guard.authorize(user, Operation::Deposit, None), Err(ApiError::Unauthorized { .. })));
+        assert!(matches!(
+            guard.authorize(Principal::anonymous(), Operation::Deposit, None),
+            Err(ApiError::Unauthorized { .. })
+        ));
+
+        register(user);
+        assert!(guard.authorize(user, Operation::Deposit, None).is_ok());
+
+        USER_SERVICE.with(|s| s.borrow().freeze_account(user, "Chargeback".to_string(), admin())).unwrap();
+        assert!(matches!(
+            guard.authorize(user, Operation::Messaging, None),
+            Err(ApiError::AccountFrozen { reason }) if reason == "Chargeback"
+        ));
+
+        USER_SERVICE.with(|s| s.borrow().unfreeze_account(user, admin())).unwrap();
+        USER_SERVICE.with(|s| s.borrow().deactivate_account(user)).unwrap();
+        assert!(matches!(guard.authorize(user, Operation::Profile, None), Err(ApiError::Unauthorized { .. })));
+    }
+
+    #[test]
+    fn test_operation_levels_and_amount_thresholds() {
+        let guard = AuthorizationGuard::new();
+        let user = Principal::from_slice(&[2; 29]);
+        register(user);
+
+        guard.set_operation_level(admin(), Operation::Withdraw, VerificationLevel::Standard);
+        assert!(matches!(guard.authorize(user, Operation::Withdraw, None), Err(ApiError::AccountNotVerified)));
+        assert!(guard.authorize(user, Operation::Deposit, None).is_ok());
+
+        guard.set_amount_threshold(admin(), Currency::ICP, VerificationLevel::Enhanced, Some(1_000)).unwrap();
+        assert!(guard.authorize(user, Operation::CreateTransaction, Some((999, &Currency::ICP))).is_ok());
+        assert!(guard.authorize(user, Operation::CreateTransaction, Some((1_000, &Currency::USDT))).is_ok());
+        assert!(matches!(
+            guard.authorize(user, Operation::CreateTransaction, Some((1_000, &Currency::ICP))),
+            Err(ApiError::AccountNotVerified)
+        ));
+
+        USER_SERVICE.with(|s| {
+            s.borrow().admin_update_verification_status(user, VerificationLevel::Enhanced, admin())
+        }).unwrap();
+        assert!(guard.authorize(user, Operation::CreateTransaction, Some((1_000, &Currency::ICP))).is_ok());
+
+        let policy = guard.get_policy();
+        assert!(policy.operation_levels.contains(&(Operation::Withdraw, VerificationLevel::Standard)));
+        assert_eq!(policy.amount_thresholds, vec![AmountThreshold {
+            currency: Currency::ICP,
+            level: VerificationLevel::Enhanced,
+            min_amount: 1_000,
+        }]);
+
+        assert!(guard.set_amount_threshold(admin(), Currency::ICP, VerificationLevel::Basic, Some(1)).is_err());
+        guard.set_amount_threshold(admin(), Currency::ICP, VerificationLevel::Enhanced, None).unwrap();
+        assert!(guard.get_policy().amount_thresholds.is_empty());
+    }
+}
@@ -0,0 +1,90 @@
+use candid::Principal;
+
+use crate::types::messaging::MAX_TEXT_BYTES;
+use crate::utils::constants::{
+    DEFAULT_MAX_INGRESS_ARG_BYTES, MAX_CREATE_TRANSACTION_ARG_BYTES, MAX_EVIDENCE_CHUNK_SIZE,
+};
+use crate::{ROLE_SERVICE, USER_SERVICE};
+
+// Room for the Candid header and the other arguments around a payload.
+const ARG_OVERHEAD_BYTES: usize = 1024;
+
+/// Decides whether an ingress update call is worth executing. Runs before
+/// the canister pays for the call, so it only does cheap reads; the methods
+/// themselves still perform the full checks.
+pub fn check_ingress(method: &str, caller: Principal, arg_bytes: usize) -> Result<(), String> {
+    // Websocket gateway calls authenticate themselves inside the CDK.
+    if method.starts_with("ws_") {
+        return Ok(());
+    }
+
+    let limit = max_arg_bytes(method);
+    if arg_bytes > limit {
+        return Err(format!("{}: argument of {} bytes exceeds the {} byte limit", method, arg_bytes, limit));
+    }
+
+    if caller == Principal::anonymous() {
+        return Err(format!("{}: anonymous callers are not allowed", method));
+    }
+
+    if method.starts_with("admin_") && ROLE_SERVICE.with(|s| s.borrow().get_roles(caller).is_empty()) {
+        return Err(format!("{}: caller has no admin role", method));
+    }
+
+    if USER_SERVICE.with(|s| s.borrow().is_frozen(caller)) {
+        return Err(format!("{}: account is frozen", method));
+    }
+
+    Ok(())
+}
+
+fn max_arg_bytes(method: &str) -> usize {
+    match method {
+        "upload_evidence_chunk" => MAX_EVIDENCE_CHUNK_SIZE + ARG_OVERHEAD_BYTES,
+        "post_message" => MAX_TEXT_BYTES as usize + ARG_OVERHEAD_BYTES,
+        "create_transaction" => MAX_CREATE_TRANSACTION_ARG_BYTES,
+        _ => DEFAULT_MAX_INGRESS_ARG_BYTES,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::user::RegisterUserRequest;
+
+    fn user() -> Principal {
+        Principal::from_slice(&[4; 29])
+    }
+
+    #[test]
+    fn test_rejects_anonymous_oversized_and_non_admin_calls() {
+        assert!(check_ingress("create_transaction", user(), 2_000).is_ok());
+        assert!(check_ingress("create_transaction", Principal::anonymous(), 2_000).is_err());
+        assert!(check_ingress("ws_open", Principal::anonymous(), 100).is_ok());
+
+        assert!(check_ingress("post_message", user(), MAX_TEXT_BYTES as usize + 100).is_ok());
+        assert!(check_ingress("post_message", user(), MAX_TEXT_BYTES as usize + 2 * ARG_OVERHEAD_BYTES).is_err());
+        assert!(check_ingress("upload_evidence_chunk", user(), MAX_EVIDENCE_CHUNK_SIZE).is_ok());
+
+        assert!(check_ingress("admin_pause_system", user(), 100).is_err());
+        ROLE_SERVICE.with(|s| s.borrow().bootstrap_super_admin(Some(user()), user()));
+        assert!(check_ingress("admin_pause_system", user(), 100).is_ok());
+    }
+
+    #[test]
+    fn test_rejects_frozen_accounts() {
+        let request = RegisterUserRequest {
+            username: "frozen_user".to_string(),
+            email: None,
+            display_name: None,
+            referral_code: None,
+        };
+        USER_SERVICE.with(|s| {
+            let service = s.borrow();
+            service.register(user(), request).unwrap();
+            service.freeze_account(user(), "Chargeback fraud".to_string(), Principal::management_canister()).unwrap();
+        });
+
+        assert!(check_ingress("withdraw", user(), 100).is_err());
+    }
+}
@@ -0,0 +1,301 @@
+use candid::Principal;
+
+use crate::types::{
+    errors::ApiError,
+    common::AuditAction,
+    limits::TierLimits,
+    transaction::Currency,
+    user::VerificationLevel,
+};
+use crate::storage::config;
+use crate::utils::constants::{
+    DEFAULT_BASIC_DAILY_VOLUME, DEFAULT_BASIC_DAILY_WITHDRAWAL, DEFAULT_BASIC_MAX_PER_TRANSACTION,
+    DEFAULT_BASIC_MONTHLY_VOLUME, DEFAULT_BASIC_MONTHLY_WITHDRAWAL, TIER_LIMIT_DEFAULT_DECIMALS,
+    TIER_LIMIT_MULTIPLIER,
+};
+use crate::{AUDIT_LOGGER, USER_SERVICE};
+
+const TIERS: [VerificationLevel; 3] = [
+    VerificationLevel::Basic,
+    VerificationLevel::Standard,
+    VerificationLevel::Enhanced,
+];
+
+/// Per-tier transaction and withdrawal caps, set per currency and kept in the
+/// `Configuration` region. The services that move funds measure usage and
+/// ask the limiter whether a request still fits.
+pub struct TierLimiter;
+
+impl TierLimiter {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// The user's verification level; unknown principals count as Basic.
+    pub fn tier_of(&self, principal: Principal) -> VerificationLevel {
+        USER_SERVICE
+            .with(|s| s.borrow().get_account(principal))
+            .map_or(VerificationLevel::Basic, |account| account.verification_level)
+    }
+
+    pub fn get_limits(&self, tier: &VerificationLevel, currency: &Currency) -> TierLimits {
+        let defaults = default_limits(tier, currency);
+        let key = |field| config_key(tier, currency, field);
+
+        TierLimits {
+            max_per_transaction: config::get_or(&key("max_per_transaction"), defaults.max_per_transaction),
+            daily_volume: config::get_or(&key("daily_volume"), defaults.daily_volume),
+            monthly_volume: config::get_or(&key("monthly_volume"), defaults.monthly_volume),
+            daily_withdrawal: config::get_or(&key("daily_withdrawal"), defaults.daily_withdrawal),
+            monthly_withdrawal: config::get_or(&key("monthly_withdrawal"), defaults.monthly_withdrawal),
+        }
+    }
+
+    pub fn get_all_limits(&self, currency: &Currency) -> Vec<(VerificationLevel, TierLimits)> {
+        TIERS.iter().map(|tier| (tier.clone(), self.get_limits(tier, currency))).collect()
+    }
+
+    pub fn set_limits(
+        &self,
+        admin: Principal,
+        tier: VerificationLevel,
+        currency: Currency,
+        limits: TierLimits,
+    ) -> Result<(), ApiError> {
+        if limits.max_per_transaction > limits.daily_volume
+            || limits.daily_volume > limits.monthly_volume
+            || limits.daily_withdrawal > limits.monthly_withdrawal
+        {
+            return Err(ApiError::ValidationError {
+                field: "limits".to_string(),
+                message: "Per-transaction cap must not exceed the daily volume, and daily limits must not exceed monthly ones".to_string(),
+            });
+        }
+
+        let key = |field| config_key(&tier, &currency, field);
+        config::set(&key("max_per_transaction"), limits.max_per_transaction);
+        config::set(&key("daily_volume"), limits.daily_volume);
+        config::set(&key("monthly_volume"), limits.monthly_volume);
+        config::set(&key("daily_withdrawal"), limits.daily_withdrawal);
+        config::set(&key("monthly_withdrawal"), limits.monthly_withdrawal);
+
+        AUDIT_LOGGER.with(|l| l.borrow().log(
+            admin,
+            AuditAction::ConfigurationChanged,
+            "tier_limits",
+            Some(format!("{:?} {}: {:?}", tier, currency.code(), limits)),
+        ));
+
+        Ok(())
+    }
+
+    /// Checks a new outgoing transaction of `amount` against the sender's
+    /// tier, given what they already sent in the last day and month.
+    pub fn check_transaction(
+        &self,
+        principal: Principal,
+        amount: u64,
+        currency: &Currency,
+        daily_used: u64,
+        monthly_used: u64,
+    ) -> Result<(), ApiError> {
+        let tier = self.tier_of(principal);
+        let limits = self.get_limits(&tier, currency);
+
+        ensure_within(&tier, "per-transaction limit", limits.max_per_transaction, 0, amount)?;
+        ensure_within(&tier, "daily volume limit", limits.daily_volume, daily_used, amount)?;
+        ensure_within(&tier, "monthly volume limit", limits.monthly_volume, monthly_used, amount)
+    }
+
+    pub fn check_withdrawal(
+        &self,
+        principal: Principal,
+        amount: u64,
+        currency: &Currency,
+        daily_used: u64,
+        monthly_used: u64,
+    ) -> Result<(), ApiError> {
+        let tier = self.tier_of(principal);
+        let limits = self.get_limits(&tier, currency);
+
+        ensure_within(&tier, "daily withdrawal limit", limits.daily_withdrawal, daily_used, amount)?;
+        ensure_within(&tier, "monthly withdrawal limit", limits.monthly_withdrawal, monthly_used, amount)
+    }
+}
+
+fn ensure_within(tier: &VerificationLevel, limit: &str, cap: u64, used: u64, requested: u64) -> Result<(), ApiError> {
+    let remaining = cap.saturating_sub(used);
+    if requested > remaining {
+        return Err(ApiError::LimitExceeded {
+            limit: format!("{:?} {}", tier, limit),
+            requested,
+            remaining,
+        });
+    }
+
+    Ok(())
+}
+
+fn default_limits(tier: &VerificationLevel, currency: &Currency) -> TierLimits {
+    let factor = match tier {
+        VerificationLevel::Basic => 1,
+        VerificationLevel::Standard => TIER_LIMIT_MULTIPLIER,
+        VerificationLevel::Enhanced => TIER_LIMIT_MULTIPLIER * TIER_LIMIT_MULTIPLIER,
+    };
+    let scale = |amount: u64| scale_to_decimals(amount * factor, currency.decimals());
+
+    TierLimits {
+        max_per_transaction: scale(DEFAULT_BASIC_MAX_PER_TRANSACTION),
+        daily_volume: scale(DEFAULT_BASIC_DAILY_VOLUME),
+        monthly_volume: scale(DEFAULT_BASIC_MONTHLY_VOLUME),
+        daily_withdrawal: scale(DEFAULT_BASIC_DAILY_WITHDRAWAL),
+        monthly_withdrawal: scale(DEFAULT_BASIC_MONTHLY_WITHDRAWAL),
+    }
+}
+
+/// Converts an amount expressed with `TIER_LIMIT_DEFAULT_DECIMALS` into base
+/// units of a currency with `decimals`, saturating at `u64::MAX`.
+fn scale_to_decimals(amount: u64, decimals: u8) -> u64 {
+    let amount = amount as u128;
+    let scaled = if decimals >= TIER_LIMIT_DEFAULT_DECIMALS {
+        amount * 10u128.pow((decimals - TIER_LIMIT_DEFAULT_DECIMALS) as u32)
+    } else {
+        amount / 10u128.pow((TIER_LIMIT_DEFAULT_DECIMALS - decimals) as u32)
+    };
+
+    u64::try_from(scaled).unwrap_or(u64::MAX)
+}
+
+fn config_key(tier: &VerificationLevel, currency: &Currency, field: &str) -> String {
+    let tier = format!("{:?}", tier).to_lowercase();
+    format!("config.tier_limits.{}.{}.{}", tier, currency.code(), field)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::transaction_service::TransactionService;
+    use crate::test_utils::{admin, escrow_request, fund, funded_service, sender};
+    use crate::types::transaction::{CreateTransactionRequest, Currency};
+    use crate::utils::{constants::NANOS_PER_DAY, time::advance_mock_time};
+
+    fn set_basic(limits: TierLimits) {
+        TierLimiter::new().set_limits(admin(), VerificationLevel::Basic, Currency::ICP, limits).unwrap();
+    }
+
+    fn payment(amount: u64) -> CreateTransactionRequest {
+        CreateTransactionRequest { amount, ..escrow_request(None, None) }
+    }
+
+    #[test]
+    fn test_transaction_caps_and_rolling_volume() {
+        set_basic(TierLimits {
+            max_per_transaction: 5_000,
+            daily_volume: 12_000,
+            monthly_volume: 18_000,
+            daily_withdrawal: 1_000,
+            monthly_withdrawal: 1_000,
+        });
+        let service = funded_service();
+
+        assert!(matches!(
+            service.create_transaction(sender(), payment(6_000)),
+            Err(ApiError::LimitExceeded { requested: 6_000, remaining: 5_000, .. })
+        ));
+
+        service.create_transaction(sender(), payment(5_000)).unwrap();
+        service.create_transaction(sender(), payment(5_000)).unwrap();
+        assert!(matches!(
+            service.create_transaction(sender(), payment(5_000)),
+            Err(ApiError::LimitExceeded { remaining: 2_000, .. })
+        ));
+
+        advance_mock_time(NANOS_PER_DAY + 1);
+        service.create_transaction(sender(), payment(5_000)).unwrap();
+        assert!(matches!(
+            service.create_transaction(sender(), payment(5_000)),
+            Err(ApiError::LimitExceeded { limit, remaining: 3_000, .. }) if limit == "Basic monthly volume limit"
+        ));
+    }
+
+    #[test]
+    fn test_withdrawal_limits_ignore_failed_transfers() {
+        set_basic(TierLimits {
+            max_per_transaction: 1_000,
+            daily_volume: 1_000,
+            monthly_volume: 1_000,
+            daily_withdrawal: 3_000,
+            monthly_withdrawal: 10_000,
+        });
+        fund(sender(), 100_000);
+        crate::BALANCE_SERVICE.with(|s| {
+            let mut balances = s.borrow_mut();
+            balances.begin_withdrawal(sender(), 2_000, &Currency::ICP).unwrap();
+            assert!(matches!(
+                balances.begin_withdrawal(sender(), 2_000, &Currency::ICP),
+                Err(ApiError::LimitExceeded { remaining: 1_000, .. })
+            ));
+
+            balances.fail_withdrawal(sender(), 2_000, &Currency::ICP, "Ledger down").unwrap();
+            balances.begin_withdrawal(sender(), 2_000, &Currency::ICP).unwrap();
+            assert_eq!(balances.withdrawn_since(sender(), &Currency::ICP, 0), 2_000);
+        });
+
+        assert!(TierLimiter::new().set_limits(admin(), VerificationLevel::Standard, Currency::ICP, TierLimits {
+            max_per_transaction: 10,
+            daily_volume: 5,
+            monthly_volume: 100,
+            daily_withdrawal: 0,
+            monthly_withdrawal: 0,
+        }).is_err());
+    }
+
+    #[test]
+    fn test_limits_are_kept_per_currency() {
+        let limiter = TierLimiter::new();
+        let icp = limiter.get_limits(&VerificationLevel::Basic, &Currency::ICP);
+        let usdt = limiter.get_limits(&VerificationLevel::Basic, &Currency::USDT);
+        let cycles = limiter.get_limits(&VerificationLevel::Basic, &Currency::Cycles);
+        assert_eq!(icp.max_per_transaction, DEFAULT_BASIC_MAX_PER_TRANSACTION);
+        assert_eq!(usdt.max_per_transaction, DEFAULT_BASIC_MAX_PER_TRANSACTION / 100);
+        assert_eq!(cycles.max_per_transaction, DEFAULT_BASIC_MAX_PER_TRANSACTION * 10_000);
+        let custom = Currency::Custom { symbol: "CKETH".to_string(), decimals: 18 };
+        assert_eq!(limiter.get_limits(&VerificationLevel::Enhanced, &custom).monthly_volume, u64::MAX);
+
+        set_basic(TierLimits {
+            max_per_transaction: 5_000,
+            daily_volume: 12_000,
+            monthly_volume: 18_000,
+            daily_withdrawal: 1_000,
+            monthly_withdrawal: 1_000,
+        });
+        assert_eq!(limiter.get_limits(&VerificationLevel::Basic, &Currency::ICP).max_per_transaction, 5_000);
+        assert_eq!(limiter.get_limits(&VerificationLevel::Basic, &Currency::USDT), usdt);
+        assert!(limiter.check_transaction(sender(), 6_000, &Currency::USDT, 0, 0).is_ok());
+        assert!(limiter.check_transaction(sender(), 6_000, &Currency::ICP, 0, 0).is_err());
+    }
+
+    #[test]
+    fn test_usage_indexes_are_rebuilt_after_upgrade() {
+        crate::BALANCE_SERVICE.with(|s| {
+            let mut balances = s.borrow_mut();
+            balances.credit_funds(sender(), 1_000_000, &Currency::ICP, 0, "Test funds").unwrap();
+            balances.begin_withdrawal(sender(), 2_000, &Currency::ICP).unwrap();
+        });
+        let service = TransactionService::new();
+        service.create_transaction(sender(), payment(5_000)).unwrap();
+
+        let storage = crate::storage::stable_storage::StorageManager::instance();
+        storage.outgoing_transactions().clear();
+        assert_eq!(service.outgoing_volume(sender(), &Currency::ICP, 0), 0);
+
+        assert_eq!(service.index_recent_transactions(), 1);
+        assert_eq!(service.index_recent_transactions(), 0);
+        assert_eq!(service.outgoing_volume(sender(), &Currency::ICP, 0), 5_000);
+        crate::BALANCE_SERVICE.with(|s| {
+            let balances = s.borrow();
+            assert_eq!(balances.index_recent_withdrawals(), 0);
+            assert_eq!(balances.withdrawn_since(sender(), &Currency::ICP, 0), 2_000);
+        });
+    }
+}
@@ -1,2 +1,6 @@
 pub mod audit;
-pub mod validation;
\ No newline at end of file
+pub mod validation;
+pub mod rate_limit;
+pub mod inspect;
+pub mod guard;
+pub mod limits;
\ No newline at end of file
@@ -0,0 +1,277 @@
+use candid::Principal;
+
+use crate::types::{
+    errors::ApiError,
+    common::AuditAction,
+    rate_limit::*,
+};
+use crate::storage::{config, memory::MemoryRegion, stable_storage::StableStorage};
+use crate::utils::constants::{
+    DEFAULT_MESSAGING_RATE_LIMIT_REQUESTS, DEFAULT_MESSAGING_RATE_LIMIT_WINDOW,
+    DEFAULT_RATE_LIMIT_BLOCK_DURATION, DEFAULT_RATE_LIMIT_REQUESTS, DEFAULT_RATE_LIMIT_WINDOW,
+    DEFAULT_SEARCH_RATE_LIMIT_REQUESTS, DEFAULT_SEARCH_RATE_LIMIT_WINDOW, ENABLE_RATE_LIMITING,
+    NANOS_PER_SECOND,
+};
+use crate::utils::time::now;
+use crate::AUDIT_LOGGER;
+
+/// Sliding-window request limiter, keyed by principal and endpoint class.
+/// Counters live in the `RateLimits` region so blocks survive upgrades.
+///
+/// State written during a query call is discarded, so on query endpoints the
+/// limiter can only turn away callers that are already blocked.
+pub struct RateLimiter {
+    entries: StableStorage<RateLimitKey, RateLimitEntry>,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self {
+            entries: StableStorage::new(MemoryRegion::RateLimits),
+        }
+    }
+
+    /// Counts one request from `principal` and fails once the class limit is
+    /// exceeded, blocking the principal for the configured duration.
+    pub fn check(&self, principal: Principal, class: EndpointClass) -> Result<(), ApiError> {
+        if !ENABLE_RATE_LIMITING {
+            return Ok(());
+        }
+
+        let key = RateLimitKey { principal, class };
+        let limits = self.get_config(class);
+        let now = now();
+        let mut entry = self.entries.get(&key).unwrap_or_default();
+
+        if let Some(blocked_until) = entry.blocked_until {
+            if now < blocked_until {
+                return Err(rate_limited(blocked_until - now));
+            }
+            entry.blocked_until = None;
+        }
+
+        roll_window(&mut entry, now, limits.window);
+
+        if sliding_count(&entry, now, limits.window) >= limits.max_requests as u64 {
+            let blocked_until = now + limits.block_duration;
+            entry.blocked_until = Some(blocked_until);
+            entry.violations += 1;
+            self.entries.insert(key, entry.clone());
+
+            AUDIT_LOGGER.with(|l| l.borrow().log(
+                principal,
+                AuditAction::RateLimitExceeded,
+                class.key(),
+                Some(format!(
+                    "{} requests per {}s exceeded; violation {}",
+                    limits.max_requests,
+                    limits.window / NANOS_PER_SECOND,
+                    entry.violations,
+                )),
+            ));
+
+            return Err(rate_limited(limits.block_duration));
+        }
+
+        entry.current_count += 1;
+        self.entries.insert(key, entry);
+
+        Ok(())
+    }
+
+    pub fn get_config(&self, class: EndpointClass) -> RateLimitConfig {
+        let (max_requests, window) = match class {
+            EndpointClass::Write => (DEFAULT_RATE_LIMIT_REQUESTS, DEFAULT_RATE_LIMIT_WINDOW),
+            EndpointClass::Messaging => (DEFAULT_MESSAGING_RATE_LIMIT_REQUESTS, DEFAULT_MESSAGING_RATE_LIMIT_WINDOW),
+            EndpointClass::Search => (DEFAULT_SEARCH_RATE_LIMIT_REQUESTS, DEFAULT_SEARCH_RATE_LIMIT_WINDOW),
+        };
+
+        RateLimitConfig {
+            max_requests: config::get_or(&config_key(class, "max_requests"), max_requests as u64) as u32,
+            window: config::get_or(&config_key(class, "window"), window),
+            block_duration: config::get_or(&config_key(class, "block_duration"), DEFAULT_RATE_LIMIT_BLOCK_DURATION),
+        }
+    }
+
+    pub fn get_all_configs(&self) -> Vec<(EndpointClass, RateLimitConfig)> {
+        EndpointClass::ALL.iter().map(|class| (*class, self.get_config(*class))).collect()
+    }
+
+    pub fn set_config(&self, admin: Principal, class: EndpointClass, limits: RateLimitConfig) -> Result<(), ApiError> {
+        if limits.max_requests == 0 || limits.window == 0 {
+            return Err(ApiError::ValidationError {
+                field: "limits".to_string(),
+                message: "Max requests and window must be greater than zero".to_string(),
+            });
+        }
+
+        config::set(&config_key(class, "max_requests"), limits.max_requests as u64);
+        config::set(&config_key(class, "window"), limits.window);
+        config::set(&config_key(class, "block_duration"), limits.block_duration);
+
+        AUDIT_LOGGER.with(|l| l.borrow().log(
+            admin,
+            AuditAction::ConfigurationChanged,
+            "rate_limits",
+            Some(format!("{}: {:?}", class.key(), limits)),
+        ));
+
+        Ok(())
+    }
+
+    pub fn list_blocks(&self) -> Vec<RateLimitBlock> {
+        let now = now();
+
+        self.entries
+            .filter(|_, entry| entry.blocked_until.is_some_and(|until| until > now))
+            .into_iter()
+            .map(|(key, entry)| RateLimitBlock {
+                principal: key.principal,
+                class: key.class,
+                blocked_until: entry.blocked_until.unwrap_or_default(),
+                violations: entry.violations,
+            })
+            .collect()
+    }
+
+    /// Lifts blocks and resets counters for `principal`, in one class or all.
+    pub fn clear(&self, admin: Principal, principal: Principal, class: Option<EndpointClass>) -> u64 {
+        let classes = match class {
+            Some(class) => vec![class],
+            None => EndpointClass::ALL.to_vec(),
+        };
+
+        let cleared = classes
+            .into_iter()
+            .filter_map(|class| self.entries.remove(&RateLimitKey { principal, class }))
+            .count() as u64;
+
+        AUDIT_LOGGER.with(|l| l.borrow().log(
+            admin,
+            AuditAction::ConfigurationChanged,
+            &principal.to_text(),
+            Some(format!("Rate limits cleared: {:?}", class)),
+        ));
+
+        cleared
+    }
+
+    /// Drops entries with no active block and no requests in the last two
+    /// windows; they carry no information.
+    pub fn cleanup_stale(&self) -> u64 {
+        let now = now();
+        let stale: Vec<RateLimitKey> = self.entries
+            .filter(|key, entry| {
+                let window = self.get_config(key.class).window;
+                entry.blocked_until.is_none_or(|until| until <= now)
+                    && now.saturating_sub(entry.window_start) >= 2 * window
+            })
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+
+        for key in &stale {
+            self.entries.remove(key);
+        }
+
+        stale.len() as u64
+    }
+}
+
+fn config_key(class: EndpointClass, field: &str) -> String {
+    format!("config.rate_limit.{}.{}", class.key(), field)
+}
+
+fn rate_limited(remaining: u64) -> ApiError {
+    ApiError::RateLimited {
+        retry_after: remaining.div_ceil(NANOS_PER_SECOND),
+    }
+}
+
+fn roll_window(entry: &mut RateLimitEntry, now: u64, window: u64) {
+    let elapsed = now.saturating_sub(entry.window_start);
+    if elapsed < window {
+        return;
+    }
+
+    if elapsed < 2 * window {
+        entry.previous_count = entry.current_count;
+        entry.window_start += window;
+    } else {
+        entry.previous_count = 0;
+        entry.window_start = now;
+    }
+    entry.current_count = 0;
+}
+
+fn sliding_count(entry: &RateLimitEntry, now: u64, window: u64) -> u64 {
+    let elapsed = now.saturating_sub(entry.window_start).min(window);
+    let previous_weight = window - elapsed;
+
+    entry.current_count as u64 + entry.previous_count as u64 * previous_weight / window
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{constants::NANOS_PER_MINUTE, time::advance_mock_time};
+
+    fn user() -> Principal {
+        Principal::from_slice(&[7; 29])
+    }
+
+    fn admin() -> Principal {
+        Principal::from_slice(&[8; 29])
+    }
+
+    fn strict() -> RateLimitConfig {
+        RateLimitConfig {
+            max_requests: 3,
+            window: NANOS_PER_MINUTE,
+            block_duration: 5 * NANOS_PER_MINUTE,
+        }
+    }
+
+    #[test]
+    fn test_blocks_after_limit_and_unblocks_later() {
+        let limiter = RateLimiter::new();
+        limiter.set_config(admin(), EndpointClass::Messaging, strict()).unwrap();
+
+        for _ in 0..3 {
+            limiter.check(user(), EndpointClass::Messaging).unwrap();
+        }
+        assert!(matches!(
+            limiter.check(user(), EndpointClass::Messaging),
+            Err(ApiError::RateLimited { retry_after: 300 })
+        ));
+        // Other classes keep their own counters.
+        assert!(limiter.check(user(), EndpointClass::Write).is_ok());
+
+        let blocks = limiter.list_blocks();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].class, EndpointClass::Messaging);
+
+        advance_mock_time(5 * NANOS_PER_MINUTE);
+        assert!(limiter.check(user(), EndpointClass::Messaging).is_ok());
+    }
+
+    #[test]
+    fn test_previous_window_still_counts() {
+        let limiter = RateLimiter::new();
+        limiter.set_config(admin(), EndpointClass::Search, strict()).unwrap();
+
+        for _ in 0..3 {
+            limiter.check(user(), EndpointClass::Search).unwrap();
+        }
+        // Half way into the next window, half of the previous 3 requests
+        // (rounded down) still count, leaving room for two more.
+        advance_mock_time(NANOS_PER_MINUTE + NANOS_PER_MINUTE / 2);
+        limiter.check(user(), EndpointClass::Search).unwrap();
+        limiter.check(user(), EndpointClass::Search).unwrap();
+        assert!(limiter.check(user(), EndpointClass::Search).is_err());
+
+        assert_eq!(limiter.clear(admin(), user(), None), 1);
+        assert!(limiter.list_blocks().is_empty());
+        assert!(limiter.check(user(), EndpointClass::Search).is_ok());
+    }
+}
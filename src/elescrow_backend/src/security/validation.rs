@@ -2,6 +2,7 @@ use candid::Principal;
 use regex::Regex;
 use lazy_static::lazy_static;
 use crate::types::errors::ApiError;
+use crate::types::transaction::{Currency, PaymentFrequency, PaymentSchedule};
 
 lazy_static! {
     static ref USERNAME_REGEX: Regex = Regex::new(r"^[a-zA-Z0-9_-]{3,30}$").unwrap();
@@ -12,6 +13,7 @@ lazy_static! {
     static ref URL_REGEX: Regex = Regex::new(
         r"^https?://(?:www\.)?[-a-zA-Z0-9@:%._\+~#=]{1,256}\.[a-zA-Z0-9()]{1,6}\b(?:[-a-zA-Z0-9()@:%_\+.~#?&/=]*)$"
     ).unwrap();
+    static ref CURRENCY_SYMBOL_REGEX: Regex = Regex::new(r"^[A-Z0-9]{2,16}$").unwrap();
     static ref PRINCIPAL_REGEX: Regex = Regex::new(
         r"^[a-z0-9]{5}-[a-z0-9]{5}-[a-z0-9]{5}-[a-z0-9]{5}-[a-z0-9]{5}-[a-z0-9]{5}-[a-z0-9]{5}-[a-z0-9]{5}-[a-z0-9]{5}-[a-z0-9]{5}-[a-z0-9]{3}$"
     ).unwrap();
@@ -130,6 +132,58 @@ pub fn validate_amount(amount: u64, min: Option<u64>, max: Option<u64>) -> Resul
     Ok(())
 }
 
+pub fn validate_currency(currency: &Currency) -> Result<(), ApiError> {
+    if let Currency::Custom { symbol, decimals } = currency {
+        if !CURRENCY_SYMBOL_REGEX.is_match(symbol) {
+            return Err(ApiError::ValidationError {
+                field: "currency".to_string(),
+                message: "Currency symbol must be 2-16 uppercase letters or digits".to_string(),
+            });
+        }
+        
+        if *decimals > 18 {
+            return Err(ApiError::ValidationError {
+                field: "currency".to_string(),
+                message: "Currency decimals cannot exceed 18".to_string(),
+            });
+        }
+    }
+    
+    Ok(())
+}
+
+pub fn validate_payment_schedule(schedule: &PaymentSchedule) -> Result<(), ApiError> {
+    validate_timestamp(schedule.start_date, "start_date")?;
+
+    if let PaymentFrequency::Custom { interval_days } = schedule.frequency {
+        if interval_days == 0 || interval_days > 3650 {
+            return Err(ApiError::ValidationError {
+                field: "frequency".to_string(),
+                message: "Custom interval must be between 1 and 3650 days".to_string(),
+            });
+        }
+    }
+
+    if let Some(end_date) = schedule.end_date {
+        validate_timestamp(end_date, "end_date")?;
+        if end_date < schedule.start_date {
+            return Err(ApiError::ValidationError {
+                field: "end_date".to_string(),
+                message: "End date cannot be before the start date".to_string(),
+            });
+        }
+    }
+
+    if schedule.total_payments == Some(0) {
+        return Err(ApiError::ValidationError {
+            field: "total_payments".to_string(),
+            message: "Total payments must be at least 1".to_string(),
+        });
+    }
+
+    Ok(())
+}
+
 pub fn validate_text(text: &str, field: &str, min_length: usize, max_length: usize) -> Result<String, ApiError> {
     let trimmed = text.trim();
     
@@ -155,9 +209,7 @@ pub fn validate_text(text: &str, field: &str, min_length: usize, max_length: usi
 }
 
 pub fn validate_timestamp(timestamp: u64, field: &str) -> Result<(), ApiError> {
-    use ic_cdk::api::time;
-    
-    let now = time();
+    let now = crate::utils::time::now();
     
     let min_timestamp = 1_577_836_800_000_000_000; 
     if timestamp < min_timestamp {
@@ -198,4 +250,36 @@ mod tests {
         assert!(validate_email("invalid.email").is_err());
         assert!(validate_email("@example.com").is_err());
     }
+    
+    #[test]
+    fn test_currency_validation() {
+        assert!(validate_currency(&Currency::ICP).is_ok());
+        assert!(validate_currency(&Currency::Custom { symbol: "CKBTC".to_string(), decimals: 8 }).is_ok());
+        assert!(validate_currency(&Currency::Custom { symbol: "ckbtc".to_string(), decimals: 8 }).is_err());
+        assert!(validate_currency(&Currency::Custom { symbol: "X".to_string(), decimals: 8 }).is_err());
+        assert!(validate_currency(&Currency::Custom { symbol: "CKETH".to_string(), decimals: 19 }).is_err());
+    }
+    
+    #[test]
+    fn test_payment_schedule_validation() {
+        let start = crate::utils::time::now();
+        let schedule = PaymentSchedule {
+            frequency: PaymentFrequency::Monthly,
+            start_date: start,
+            end_date: None,
+            amount_per_payment: 5_000,
+            total_payments: Some(12),
+            payments_completed: 0,
+            next_payment_date: start,
+            failed_attempts: None,
+            retry_at: None,
+        };
+        assert!(validate_payment_schedule(&schedule).is_ok());
+        assert!(validate_payment_schedule(&PaymentSchedule { total_payments: Some(0), ..schedule.clone() }).is_err());
+        assert!(validate_payment_schedule(&PaymentSchedule { end_date: Some(start - 1), ..schedule.clone() }).is_err());
+        assert!(validate_payment_schedule(&PaymentSchedule {
+            frequency: PaymentFrequency::Custom { interval_days: 0 },
+            ..schedule
+        }).is_err());
+    }
 }
\ No newline at end of file
@@ -1,20 +1,27 @@
 use candid::Principal;
-use ic_cdk::api::time;
+use crate::utils::time::now;
 
 use crate::types::{
     errors::ApiError,
-    transaction::{Balance, BalanceHistoryEntry, Currency},
+    transaction::{Balance, BalanceEntryStatus, BalanceHistoryEntry, Currency, TransactionType},
     common::PaginationParams,
+    messaging::WsEvent,
 };
+use crate::models::balance::BalanceKey;
+use crate::services::events::send_ws_event;
 use crate::storage::{
     stable_storage::{StableStorage, TimeSeriesStorage},
     memory::MemoryRegion,
 };
 use crate::security::validation;
+use crate::utils::constants::{LIMIT_DAILY_WINDOW, LIMIT_MONTHLY_WINDOW};
 
 pub struct BalanceService {
-    balances: StableStorage<Principal, Balance>,
+    balances: StableStorage<BalanceKey, Balance>,
+    legacy_balances: StableStorage<Principal, Balance>,
     balance_history: TimeSeriesStorage<BalanceHistoryEntry>,
+    // (principal, balance_history key) of each withdrawal entry.
+    withdrawals: StableStorage<(Principal, u64), ()>,
     
     min_balance: u64,
     max_balance: u64,
@@ -23,17 +30,19 @@ pub struct BalanceService {
 impl BalanceService {
     pub fn new() -> Self {
         Self {
-            balances: StableStorage::new(MemoryRegion::Balances),
+            balances: StableStorage::new(MemoryRegion::CurrencyBalances),
+            legacy_balances: StableStorage::new(MemoryRegion::Balances),
             balance_history: TimeSeriesStorage::new(MemoryRegion::BalanceHistory),
+            withdrawals: StableStorage::new(MemoryRegion::WithdrawalIndex),
             min_balance: 0,
             max_balance: u64::MAX,
         }
     }
     
-    pub fn get_or_create_balance(&mut self, principal: Principal) -> Balance {
-        self.balances.get(&principal).unwrap_or_else(|| Balance {
+    pub fn get_or_create_balance(&mut self, principal: Principal, currency: &Currency) -> Balance {
+        self.balances.get(&BalanceKey::new(principal, currency)).unwrap_or_else(|| Balance {
             principal,
-            currency: Currency::ICP,
+            currency: currency.clone(),
             available: 0,
             locked: 0,
             pending_incoming: 0,
@@ -41,56 +50,128 @@ impl BalanceService {
             total_received: 0,
             total_sent: 0,
             last_transaction_id: None,
-            updated_at: time(),
+            updated_at: now(),
         })
     }
     
-    pub fn get_balance(&mut self, principal: Principal) -> Result<Balance, ApiError> {
+    pub fn get_balance(&mut self, principal: Principal, currency: &Currency) -> Result<Balance, ApiError> {
         validation::validate_principal(&principal)?;
-        Ok(self.get_or_create_balance(principal))
+        validation::validate_currency(currency)?;
+        Ok(self.get_or_create_balance(principal, currency))
     }
 
-    pub fn deposit(&mut self, principal: Principal, amount: u64) -> Result<u64, ApiError> {
+    pub fn get_balances(&self, principal: Principal) -> Result<Vec<Balance>, ApiError> {
+        validation::validate_principal(&principal)?;
+
+        let balances = self.balances
+            .range_while(BalanceKey::first_for(principal), |key, _| key.principal == principal)
+            .into_iter()
+            .map(|(_, balance)| balance)
+            .collect();
+
+        Ok(balances)
+    }
+
+    pub fn begin_deposit(&mut self, principal: Principal, amount: u64, currency: &Currency) -> Result<Balance, ApiError> {
         if crate::SYSTEM_STATE.with(|s| s.borrow().is_paused) {
             return Err(ApiError::SystemPaused {
                 reason: crate::SYSTEM_STATE.with(|s| s.borrow().reason.clone().unwrap_or_default()),
             });
         }
         validation::validate_principal(&principal)?;
-        let mut balance = self.get_or_create_balance(principal);
+        validation::validate_currency(currency)?;
+        validation::validate_amount(amount, Some(1), None)?;
+        let mut balance = self.get_or_create_balance(principal, currency);
+
+        balance.pending_incoming = balance.pending_incoming.checked_add(amount)
+            .ok_or_else(|| ApiError::InternalError {
+                details: "Pending balance overflow".to_string(),
+            })?;
+        balance.updated_at = now();
+
+        self.save_balance(&balance);
+
+        self.record_ledger_history(
+            &balance,
+            balance.available,
+            amount as i64,
+            TransactionType::Deposit,
+            BalanceEntryStatus::Pending,
+            "Deposit pending",
+        );
+
+        Ok(balance)
+    }
+
+    pub fn complete_deposit(
+        &mut self,
+        principal: Principal,
+        amount: u64,
+        currency: &Currency,
+        ledger_block: u64,
+    ) -> Result<Balance, ApiError> {
+        let mut balance = self.get_or_create_balance(principal, currency);
         let balance_before = balance.available;
 
+        balance.pending_incoming = balance.pending_incoming.saturating_sub(amount);
         balance.available = balance.available.checked_add(amount)
             .ok_or_else(|| ApiError::InternalError {
                 details: "Balance overflow".to_string(),
             })?;
-        
         balance.total_received = balance.total_received.saturating_add(amount);
         balance.last_transaction_id = None;
-        balance.updated_at = time();
-        
-        self.balances.insert(principal, balance.clone());
-        
-        self.record_history(
-            principal,
+        balance.updated_at = now();
+
+        self.save_balance(&balance);
+
+        self.record_ledger_history(
+            &balance,
             balance_before,
-            balance.available,
             amount as i64,
-            0,
+            TransactionType::Deposit,
+            BalanceEntryStatus::Completed { ledger_block: Some(ledger_block) },
             "Deposit",
         );
-        
-        Ok(balance.available)
+
+        Ok(balance)
+    }
+
+    pub fn fail_deposit(&mut self, principal: Principal, amount: u64, currency: &Currency, reason: &str) -> Balance {
+        let mut balance = self.get_or_create_balance(principal, currency);
+
+        balance.pending_incoming = balance.pending_incoming.saturating_sub(amount);
+        balance.updated_at = now();
+
+        self.save_balance(&balance);
+
+        self.record_ledger_history(
+            &balance,
+            balance.available,
+            0,
+            TransactionType::Deposit,
+            BalanceEntryStatus::Failed { reason: reason.to_string() },
+            "Deposit failed",
+        );
+
+        balance
     }
 
-    pub fn withdraw(&mut self, principal: Principal, amount: u64) -> Result<u64, ApiError> {
+    /// Moves `amount` from available into `pending_outgoing` while the ledger
+    /// transfer is in flight, so it cannot be spent twice.
+    pub fn begin_withdrawal(&mut self, principal: Principal, amount: u64, currency: &Currency) -> Result<Balance, ApiError> {
         if crate::SYSTEM_STATE.with(|s| s.borrow().is_paused) {
             return Err(ApiError::SystemPaused {
                 reason: crate::SYSTEM_STATE.with(|s| s.borrow().reason.clone().unwrap_or_default()),
             });
         }
         validation::validate_principal(&principal)?;
-        let mut balance = self.get_or_create_balance(principal);
+        validation::validate_currency(currency)?;
+        validation::validate_amount(amount, Some(1), None)?;
+        let daily = self.withdrawn_since(principal, currency, now().saturating_sub(LIMIT_DAILY_WINDOW));
+        let monthly = self.withdrawn_since(principal, currency, now().saturating_sub(LIMIT_MONTHLY_WINDOW));
+        crate::TIER_LIMITER.with(|l| l.borrow().check_withdrawal(principal, amount, currency, daily, monthly))?;
+
+        let mut balance = self.get_or_create_balance(principal, currency);
         let balance_before = balance.available;
         
         if balance.available < amount {
@@ -101,34 +182,116 @@ impl BalanceService {
         }
 
         balance.available -= amount;
-        balance.total_sent = balance.total_sent.saturating_add(amount);
+        balance.pending_outgoing = balance.pending_outgoing.saturating_add(amount);
         balance.last_transaction_id = None;
-        balance.updated_at = time();
+        balance.updated_at = now();
 
-        self.balances.insert(principal, balance.clone());
+        self.save_balance(&balance);
 
-        self.record_history(
-            principal,
+        self.record_ledger_history(
+            &balance,
             balance_before,
-            balance.available,
             -(amount as i64),
+            TransactionType::Withdrawal,
+            BalanceEntryStatus::Pending,
+            "Withdrawal pending",
+        );
+        
+        Ok(balance)
+    }
+
+    /// Amount withdrawn to the ledger since `since`, including transfers still
+    /// in flight. Withdrawals the ledger rejected are not counted.
+    pub fn withdrawn_since(&self, principal: Principal, currency: &Currency, since: u64) -> u64 {
+        self.withdrawals
+            .range_while((principal, since), |(p, _), _| *p == principal)
+            .into_iter()
+            .filter_map(|((_, key), _)| self.balance_history.get(key))
+            .filter(|entry| entry.currency.as_ref().unwrap_or(&Currency::ICP) == currency)
+            .fold(0u64, |total, entry| match entry.status {
+                Some(BalanceEntryStatus::Pending) => total.saturating_add(entry.change.unsigned_abs()),
+                Some(BalanceEntryStatus::Failed { .. }) => total.saturating_sub(entry.change.unsigned_abs()),
+                _ => total,
+            })
+    }
+
+    pub fn complete_withdrawal(
+        &mut self,
+        principal: Principal,
+        amount: u64,
+        currency: &Currency,
+        ledger_block: u64,
+    ) -> Balance {
+        let mut balance = self.get_or_create_balance(principal, currency);
+
+        balance.pending_outgoing = balance.pending_outgoing.saturating_sub(amount);
+        balance.total_sent = balance.total_sent.saturating_add(amount);
+        balance.updated_at = now();
+
+        self.save_balance(&balance);
+
+        self.record_ledger_history(
+            &balance,
+            balance.available,
             0,
+            TransactionType::Withdrawal,
+            BalanceEntryStatus::Completed { ledger_block: Some(ledger_block) },
             "Withdrawal",
         );
-        
-        Ok(balance.available)
+
+        balance
+    }
+
+    /// Returns the reserved amount to the available balance after the ledger
+    /// rejected the transfer.
+    pub fn fail_withdrawal(
+        &mut self,
+        principal: Principal,
+        amount: u64,
+        currency: &Currency,
+        reason: &str,
+    ) -> Result<Balance, ApiError> {
+        let mut balance = self.get_or_create_balance(principal, currency);
+        let balance_before = balance.available;
+
+        if balance.pending_outgoing < amount {
+            return Err(ApiError::InternalError {
+                details: "Insufficient pending outgoing funds".to_string(),
+            });
+        }
+
+        balance.pending_outgoing -= amount;
+        balance.available = balance.available.checked_add(amount)
+            .ok_or_else(|| ApiError::InternalError {
+                details: "Balance overflow".to_string(),
+            })?;
+        balance.updated_at = now();
+
+        self.save_balance(&balance);
+
+        self.record_ledger_history(
+            &balance,
+            balance_before,
+            amount as i64,
+            TransactionType::Withdrawal,
+            BalanceEntryStatus::Failed { reason: reason.to_string() },
+            "Withdrawal failed",
+        );
+
+        Ok(balance)
     }
     
     pub fn credit_funds(
         &mut self,
         principal: Principal,
         amount: u64,
+        currency: &Currency,
         transaction_id: u64,
         description: &str,
     ) -> Result<Balance, ApiError> {
         validation::validate_amount(amount, Some(1), None)?;
         
-        let mut balance = self.get_or_create_balance(principal);
+        let mut balance = self.get_or_create_balance(principal, currency);
         let balance_before = balance.available;
         
         balance.available = balance.available.checked_add(amount)
@@ -138,14 +301,13 @@ impl BalanceService {
         
         balance.total_received = balance.total_received.saturating_add(amount);
         balance.last_transaction_id = Some(transaction_id);
-        balance.updated_at = time();
+        balance.updated_at = now();
         
-        self.balances.insert(principal, balance.clone());
+        self.save_balance(&balance);
         
         self.record_history(
-            principal,
+            &balance,
             balance_before,
-            balance.available,
             amount as i64,
             transaction_id,
             description,
@@ -158,12 +320,13 @@ impl BalanceService {
         &mut self,
         principal: Principal,
         amount: u64,
+        currency: &Currency,
         transaction_id: u64,
         description: &str,
     ) -> Result<Balance, ApiError> {
         validation::validate_amount(amount, Some(1), None)?;
         
-        let mut balance = self.get_or_create_balance(principal);
+        let mut balance = self.get_or_create_balance(principal, currency);
         let balance_before = balance.available;
 
         if balance.available < amount {
@@ -176,14 +339,13 @@ impl BalanceService {
         balance.available -= amount;
         balance.total_sent = balance.total_sent.saturating_add(amount);
         balance.last_transaction_id = Some(transaction_id);
-        balance.updated_at = time();
+        balance.updated_at = now();
 
-        self.balances.insert(principal, balance.clone());
+        self.save_balance(&balance);
 
         self.record_history(
-            principal,
+            &balance,
             balance_before,
-            balance.available,
             -(amount as i64),
             transaction_id,
             description,
@@ -196,11 +358,12 @@ impl BalanceService {
         &mut self,
         principal: Principal,
         amount: u64,
+        currency: &Currency,
         transaction_id: u64,
     ) -> Result<Balance, ApiError> {
         validation::validate_amount(amount, Some(1), None)?;
         
-        let mut balance = self.get_or_create_balance(principal);
+        let mut balance = self.get_or_create_balance(principal, currency);
 
         if balance.available < amount {
             return Err(ApiError::InsufficientFunds {
@@ -216,9 +379,9 @@ impl BalanceService {
             })?;
         
         balance.last_transaction_id = Some(transaction_id);
-        balance.updated_at = time();
+        balance.updated_at = now();
         
-        self.balances.insert(principal, balance.clone());
+        self.save_balance(&balance);
         
         Ok(balance)
     }
@@ -227,11 +390,12 @@ impl BalanceService {
         &mut self,
         principal: Principal,
         amount: u64,
+        currency: &Currency,
         transaction_id: u64,
     ) -> Result<Balance, ApiError> {
         validation::validate_amount(amount, Some(1), None)?;
         
-        let mut balance = self.get_or_create_balance(principal);
+        let mut balance = self.get_or_create_balance(principal, currency);
 
         if balance.locked < amount {
             return Err(ApiError::InternalError {
@@ -246,9 +410,9 @@ impl BalanceService {
             })?;
         
         balance.last_transaction_id = Some(transaction_id);
-        balance.updated_at = time();
+        balance.updated_at = now();
         
-        self.balances.insert(principal, balance.clone());
+        self.save_balance(&balance);
         
         Ok(balance)
     }
@@ -258,12 +422,13 @@ impl BalanceService {
         from: Principal,
         to: Principal,
         amount: u64,
+        currency: &Currency,
         transaction_id: u64,
         description: &str,
     ) -> Result<(Balance, Balance), ApiError> {
         validation::validate_amount(amount, Some(1), None)?;
 
-        let mut from_balance = self.get_or_create_balance(from);
+        let mut from_balance = self.get_or_create_balance(from, currency);
         
         if from_balance.locked < amount {
             return Err(ApiError::InternalError {
@@ -274,9 +439,9 @@ impl BalanceService {
         from_balance.locked -= amount;
         from_balance.total_sent = from_balance.total_sent.saturating_add(amount);
         from_balance.last_transaction_id = Some(transaction_id);
-        from_balance.updated_at = time();
+        from_balance.updated_at = now();
 
-        let mut to_balance = self.get_or_create_balance(to);
+        let mut to_balance = self.get_or_create_balance(to, currency);
         let to_balance_before = to_balance.available;
         
         to_balance.available = to_balance.available.checked_add(amount)
@@ -286,15 +451,14 @@ impl BalanceService {
         
         to_balance.total_received = to_balance.total_received.saturating_add(amount);
         to_balance.last_transaction_id = Some(transaction_id);
-        to_balance.updated_at = time();
+        to_balance.updated_at = now();
 
-        self.balances.insert(from, from_balance.clone());
-        self.balances.insert(to, to_balance.clone());
+        self.save_balance(&from_balance);
+        self.save_balance(&to_balance);
 
         self.record_history(
-            to,
+            &to_balance,
             to_balance_before,
-            to_balance.available,
             amount as i64,
             transaction_id,
             description,
@@ -303,9 +467,103 @@ impl BalanceService {
         Ok((from_balance, to_balance))
     }
 
+    /// Takes a platform fee out of `principal`'s locked funds. The fee leaves
+    /// user balances entirely; the treasury books it.
+    pub fn collect_locked_fee(
+        &mut self,
+        principal: Principal,
+        amount: u64,
+        currency: &Currency,
+        transaction_id: u64,
+    ) -> Result<Balance, ApiError> {
+        validation::validate_amount(amount, Some(1), None)?;
+
+        let mut balance = self.get_or_create_balance(principal, currency);
+
+        if balance.locked < amount {
+            return Err(ApiError::InternalError {
+                details: "Insufficient locked funds".to_string(),
+            });
+        }
+
+        balance.locked -= amount;
+        balance.total_sent = balance.total_sent.saturating_add(amount);
+        balance.last_transaction_id = Some(transaction_id);
+        balance.updated_at = now();
+
+        self.save_balance(&balance);
+
+        Ok(balance)
+    }
+
+    /// Returns available funds from the original recipient (`from`) to the
+    /// original sender (`to`), recording a refund entry on both sides.
+    pub fn transfer_refund(
+        &mut self,
+        from: Principal,
+        to: Principal,
+        amount: u64,
+        currency: &Currency,
+        transaction_id: u64,
+        original_transaction_id: u64,
+    ) -> Result<(Balance, Balance), ApiError> {
+        validation::validate_amount(amount, Some(1), None)?;
+
+        let mut from_balance = self.get_or_create_balance(from, currency);
+        let from_balance_before = from_balance.available;
+
+        if from_balance.available < amount {
+            return Err(ApiError::InsufficientFunds {
+                available: from_balance.available,
+                required: amount,
+            });
+        }
+
+        let mut to_balance = self.get_or_create_balance(to, currency);
+        let to_balance_before = to_balance.available;
+
+        to_balance.available = to_balance.available.checked_add(amount)
+            .ok_or_else(|| ApiError::InternalError {
+                details: "Balance overflow".to_string(),
+            })?;
+        to_balance.total_received = to_balance.total_received.saturating_add(amount);
+        to_balance.last_transaction_id = Some(transaction_id);
+        to_balance.updated_at = now();
+
+        from_balance.available -= amount;
+        from_balance.total_sent = from_balance.total_sent.saturating_add(amount);
+        from_balance.last_transaction_id = Some(transaction_id);
+        from_balance.updated_at = now();
+
+        self.save_balance(&from_balance);
+        self.save_balance(&to_balance);
+
+        let transaction_type = TransactionType::Refund { original_transaction_id };
+        let description = format!("Refund for transaction {}", original_transaction_id);
+        self.record_typed_history(
+            &from_balance,
+            from_balance_before,
+            -(amount as i64),
+            transaction_id,
+            transaction_type.clone(),
+            &description,
+        );
+        self.record_typed_history(
+            &to_balance,
+            to_balance_before,
+            amount as i64,
+            transaction_id,
+            transaction_type,
+            &description,
+        );
+
+        Ok((from_balance, to_balance))
+    }
+
     pub fn get_balance_history(
         &self,
         principal: Principal,
+        currency: Option<Currency>,
         start_date: Option<u64>,
         end_date: Option<u64>,
         pagination: PaginationParams,
@@ -313,13 +571,17 @@ impl BalanceService {
         pagination.validate()?;
         
         let start = start_date.unwrap_or(0);
-        let end = end_date.unwrap_or(time());
+        let end = end_date.unwrap_or(now());
         
         let history: Vec<BalanceHistoryEntry> = self.balance_history
             .range(start, end)
             .into_iter()
             .map(|(_, entry)| entry)
             .filter(|entry| entry.principal == principal)
+            .filter(|entry| match &currency {
+                Some(c) => entry.currency.as_ref().unwrap_or(&Currency::ICP) == c,
+                None => true,
+            })
             .skip(pagination.offset as usize)
             .take(pagination.limit as usize)
             .collect();
@@ -349,27 +611,151 @@ impl BalanceService {
         stats
     }
 
+    pub fn get_currency_statistics(&self) -> Vec<(Currency, BalanceStatistics)> {
+        let mut stats: Vec<(Currency, BalanceStatistics)> = vec![];
+
+        for (_, balance) in self.balances.entries() {
+            let index = match stats.iter().position(|(c, _)| c == &balance.currency) {
+                Some(index) => index,
+                None => {
+                    stats.push((balance.currency.clone(), BalanceStatistics {
+                        total_users: 0,
+                        total_available: 0,
+                        total_locked: 0,
+                        total_pending_incoming: 0,
+                        total_pending_outgoing: 0,
+                        total_volume: 0,
+                    }));
+                    stats.len() - 1
+                }
+            };
+            let entry = &mut stats[index].1;
+            entry.total_users += 1;
+            entry.total_available += balance.available;
+            entry.total_locked += balance.locked;
+            entry.total_pending_incoming += balance.pending_incoming;
+            entry.total_pending_outgoing += balance.pending_outgoing;
+            entry.total_volume += balance.total_sent + balance.total_received;
+        }
+
+        stats
+    }
+
+    /// Moves balances stored by principal alone (always ICP) into the
+    /// per-currency map. Safe to call on every upgrade.
+    pub fn migrate_legacy_balances(&mut self) -> u64 {
+        let legacy = self.legacy_balances.entries();
+        let count = legacy.len() as u64;
+
+        for (principal, balance) in legacy {
+            let key = BalanceKey::new(principal, &balance.currency);
+            if !self.balances.contains_key(&key) {
+                self.balances.insert(key, balance);
+            }
+            self.legacy_balances.remove(&principal);
+        }
+
+        count
+    }
+
+    /// Indexes the withdrawals of the last limit window, for canisters
+    /// upgraded from builds without the index. Returns how many were indexed.
+    pub fn index_recent_withdrawals(&self) -> u64 {
+        if !self.withdrawals.is_empty() {
+            return 0;
+        }
+
+        let mut indexed = 0;
+        for (key, entry) in self.balance_history.since(now().saturating_sub(LIMIT_MONTHLY_WINDOW)) {
+            if entry.transaction_type == TransactionType::Withdrawal {
+                self.withdrawals.insert((entry.principal, key), ());
+                indexed += 1;
+            }
+        }
+
+        indexed
+    }
+
+    fn add_history(&self, entry: BalanceHistoryEntry) {
+        let principal = entry.principal;
+        let is_withdrawal = entry.transaction_type == TransactionType::Withdrawal;
+        let key = self.balance_history.add(entry.timestamp, entry);
+        if is_withdrawal {
+            self.withdrawals.insert((principal, key), ());
+        }
+    }
+
+    fn save_balance(&self, balance: &Balance) {
+        self.balances.insert(BalanceKey::new(balance.principal, &balance.currency), balance.clone());
+        send_ws_event(balance.principal, WsEvent::BalanceUpdated(balance.clone()));
+    }
+
     fn record_history(
         &mut self,
-        principal: Principal,
+        balance: &Balance,
         balance_before: u64,
-        balance_after: u64,
         change: i64,
         transaction_id: u64,
         description: &str,
+    ) {
+        self.record_typed_history(
+            balance,
+            balance_before,
+            change,
+            transaction_id,
+            TransactionType::DirectPayment,
+            description,
+        );
+    }
+
+    fn record_typed_history(
+        &mut self,
+        balance: &Balance,
+        balance_before: u64,
+        change: i64,
+        transaction_id: u64,
+        transaction_type: TransactionType,
+        description: &str,
     ) {
         let entry = BalanceHistoryEntry {
-            principal,
-            timestamp: time(),
+            principal: balance.principal,
+            timestamp: now(),
             balance_before,
-            balance_after,
+            balance_after: balance.available,
             change,
             transaction_id,
-            transaction_type: crate::types::transaction::TransactionType::DirectPayment,
+            transaction_type,
             description: description.to_string(),
+            currency: Some(balance.currency.clone()),
+            status: Some(BalanceEntryStatus::Completed { ledger_block: None }),
         };
         
-        self.balance_history.add(time(), entry);
+        self.add_history(entry);
+    }
+
+    fn record_ledger_history(
+        &mut self,
+        balance: &Balance,
+        balance_before: u64,
+        change: i64,
+        transaction_type: TransactionType,
+        status: BalanceEntryStatus,
+        description: &str,
+    ) {
+        let entry = BalanceHistoryEntry {
+            principal: balance.principal,
+            timestamp: now(),
+            balance_before,
+            balance_after: balance.available,
+            change,
+            transaction_id: 0,
+            transaction_type,
+            description: description.to_string(),
+            currency: Some(balance.currency.clone()),
+            status: Some(status),
+        };
+
+        self.add_history(entry);
     }
 }
 
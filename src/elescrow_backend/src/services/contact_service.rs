@@ -0,0 +1,204 @@
+use candid::Principal;
+
+use crate::types::{
+    errors::ApiError,
+    messaging::{ContactStatus, MessageRequest},
+};
+use crate::storage::{
+    stable_storage::StableStorage,
+    memory::MemoryRegion,
+};
+use crate::utils::time::now;
+
+/// Per-user contact lists for direct messages, keyed by (owner, other).
+/// Writing to someone makes them your contact; their reply lands in your
+/// inbox directly, while first messages from strangers wait as requests.
+pub struct ContactService {
+    statuses: StableStorage<(Principal, Principal), ContactStatus>,
+}
+
+impl ContactService {
+    pub fn new() -> Self {
+        Self {
+            statuses: StableStorage::new(MemoryRegion::Contacts),
+        }
+    }
+
+    pub fn get_status(&self, owner: Principal, other: Principal) -> Option<ContactStatus> {
+        self.statuses.get(&(owner, other))
+    }
+
+    pub fn is_blocked(&self, owner: Principal, other: Principal) -> bool {
+        matches!(self.get_status(owner, other), Some(ContactStatus::Blocked { .. }))
+    }
+
+    /// Fails if `to` blocked `from`.
+    pub fn ensure_not_blocked(&self, from: Principal, to: Principal) -> Result<(), ApiError> {
+        if self.is_blocked(to, from) {
+            return Err(ApiError::Unauthorized {
+                reason: "This user has blocked you".to_string(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Updates both sides for a direct message and returns whether it is a
+    /// request the recipient still has to accept.
+    pub fn record_message(&self, from: Principal, to: Principal) -> Result<bool, ApiError> {
+        self.ensure_not_blocked(from, to)?;
+        match self.get_status(from, to) {
+            Some(ContactStatus::Blocked { .. }) => {
+                return Err(ApiError::InvalidState {
+                    current_state: "Blocked".to_string(),
+                    required_state: "Unblock this user before messaging them".to_string(),
+                });
+            }
+            Some(ContactStatus::Contact) => {}
+            // Replying to a request accepts it.
+            _ => {
+                self.statuses.insert((from, to), ContactStatus::Contact);
+            }
+        }
+
+        let status = match self.get_status(to, from) {
+            Some(ContactStatus::Contact) => return Ok(false),
+            Some(ContactStatus::Requested { first_message_at, message_count }) => ContactStatus::Requested {
+                first_message_at,
+                message_count: message_count.saturating_add(1),
+            },
+            _ => ContactStatus::Requested {
+                first_message_at: now(),
+                message_count: 1,
+            },
+        };
+        self.statuses.insert((to, from), status);
+
+        Ok(true)
+    }
+
+    pub fn accept_request(&self, owner: Principal, from: Principal) -> Result<(), ApiError> {
+        self.ensure_requested(owner, from)?;
+        self.statuses.insert((owner, from), ContactStatus::Contact);
+        Ok(())
+    }
+
+    /// Drops the request; the sender's next message opens a new one.
+    pub fn decline_request(&self, owner: Principal, from: Principal) -> Result<(), ApiError> {
+        self.ensure_requested(owner, from)?;
+        self.statuses.remove(&(owner, from));
+        Ok(())
+    }
+
+    pub fn get_requests(&self, owner: Principal) -> Vec<MessageRequest> {
+        self.entries_of(owner)
+            .into_iter()
+            .filter_map(|(from, status)| match status {
+                ContactStatus::Requested { first_message_at, message_count } => Some(MessageRequest {
+                    from,
+                    first_message_at,
+                    message_count,
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn block(&self, owner: Principal, other: Principal) -> Result<(), ApiError> {
+        crate::security::validation::validate_principal(&other)?;
+        if owner == other {
+            return Err(ApiError::ValidationError {
+                field: "principal".to_string(),
+                message: "Cannot block yourself".to_string(),
+            });
+        }
+
+        self.statuses.insert((owner, other), ContactStatus::Blocked { blocked_at: now() });
+        Ok(())
+    }
+
+    pub fn unblock(&self, owner: Principal, other: Principal) -> Result<(), ApiError> {
+        if !self.is_blocked(owner, other) {
+            return Err(ApiError::NotFound {
+                resource: format!("Block on {}", other.to_text()),
+            });
+        }
+
+        self.statuses.remove(&(owner, other));
+        Ok(())
+    }
+
+    pub fn get_blocked(&self, owner: Principal) -> Vec<Principal> {
+        self.entries_of(owner)
+            .into_iter()
+            .filter(|(_, status)| matches!(status, ContactStatus::Blocked { .. }))
+            .map(|(other, _)| other)
+            .collect()
+    }
+
+    fn ensure_requested(&self, owner: Principal, from: Principal) -> Result<(), ApiError> {
+        match self.get_status(owner, from) {
+            Some(ContactStatus::Requested { .. }) => Ok(()),
+            _ => Err(ApiError::NotFound {
+                resource: format!("Message request from {}", from.to_text()),
+            }),
+        }
+    }
+
+    fn entries_of(&self, owner: Principal) -> Vec<(Principal, ContactStatus)> {
+        self.statuses
+            .range_while((owner, Principal::management_canister()), |(o, _), _| *o == owner)
+            .into_iter()
+            .map(|((_, other), status)| (other, status))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn principal(n: u8) -> Principal {
+        Principal::from_slice(&[n; 29])
+    }
+
+    #[test]
+    fn test_first_message_is_a_request_until_accepted() {
+        let service = ContactService::new();
+        let (alice, bob) = (principal(1), principal(2));
+
+        assert!(service.record_message(alice, bob).unwrap());
+        assert!(service.record_message(alice, bob).unwrap());
+        let requests = service.get_requests(bob);
+        assert_eq!(requests.len(), 1);
+        assert_eq!((requests[0].from, requests[0].message_count), (alice, 2));
+
+        // Alice wrote first, so Bob's reply goes straight through and
+        // accepts her request.
+        assert!(!service.record_message(bob, alice).unwrap());
+        assert!(service.get_requests(bob).is_empty());
+        assert!(!service.record_message(alice, bob).unwrap());
+
+        let carol = principal(3);
+        service.record_message(carol, bob).unwrap();
+        service.decline_request(bob, carol).unwrap();
+        assert!(service.get_requests(bob).is_empty());
+        assert!(matches!(service.accept_request(bob, carol), Err(ApiError::NotFound { .. })));
+    }
+
+    #[test]
+    fn test_blocked_sender_is_rejected() {
+        let service = ContactService::new();
+        let (alice, bob) = (principal(1), principal(2));
+
+        service.block(bob, alice).unwrap();
+        assert!(matches!(service.record_message(alice, bob), Err(ApiError::Unauthorized { .. })));
+        assert!(matches!(service.record_message(bob, alice), Err(ApiError::InvalidState { .. })));
+        assert_eq!(service.get_blocked(bob), vec![alice]);
+        assert!(service.get_blocked(alice).is_empty());
+
+        service.unblock(bob, alice).unwrap();
+        assert!(service.record_message(alice, bob).unwrap());
+        assert!(service.block(bob, bob).is_err());
+    }
+}
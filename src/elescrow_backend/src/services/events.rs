@@ -0,0 +1,99 @@
+use candid::Principal;
+use ic_websocket_cdk::send;
+use std::{cell::RefCell, collections::HashSet};
+
+use crate::types::messaging::WsEvent;
+
+thread_local! {
+    static ONLINE_USERS: RefCell<HashSet<Principal>> = RefCell::new(HashSet::new());
+}
+
+// Every event pushed during a test, whether or not its recipient is online.
+#[cfg(test)]
+thread_local! {
+    static SENT_EVENTS: RefCell<Vec<(Principal, WsEvent)>> = const { RefCell::new(Vec::new()) };
+}
+
+pub fn on_client_open(principal: Principal) {
+    ONLINE_USERS.with_borrow_mut(|online_users| {
+        online_users.insert(principal);
+    });
+}
+
+pub fn on_client_close(principal: Principal) {
+    ONLINE_USERS.with_borrow_mut(|online_users| {
+        online_users.remove(&principal);
+    });
+}
+
+pub fn online_user_count() -> u64 {
+    ONLINE_USERS.with_borrow(|online_users| online_users.len() as u64)
+}
+
+/// Pushes `event` to `user` if they have a WebSocket open; offline users
+/// pick the change up on their next query.
+pub(crate) fn send_ws_event(user: Principal, event: WsEvent) {
+    #[cfg(test)]
+    SENT_EVENTS.with_borrow_mut(|sent| sent.push((user, event.clone())));
+
+    if !ONLINE_USERS.with_borrow(|online_users| online_users.contains(&user)) {
+        return;
+    }
+
+    match candid::encode_one(&event) {
+        Ok(event_bytes) => match send(user, event_bytes) {
+            Ok(_) => println!("WebSocket message sent to user: {}", user),
+            Err(e) => eprintln!("Failed to send WebSocket message: {:?}", e),
+        },
+        Err(e) => {
+            eprintln!("Failed to encode WsEvent: {:?}", e);
+        }
+    }
+}
+
+/// Drains the events recorded so far on this test thread.
+#[cfg(test)]
+pub(crate) fn take_sent_events() -> Vec<(Principal, WsEvent)> {
+    SENT_EVENTS.with_borrow_mut(std::mem::take)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{agent, agent_escrow_request, fund, funded_service, recipient, sender};
+    use crate::types::transaction::TransactionStatus;
+
+    #[test]
+    fn test_balance_changes_reach_their_owner() {
+        fund(sender(), 5_000);
+
+        let events = take_sent_events();
+        assert_eq!(events.len(), 1);
+        assert!(matches!(
+            &events[0],
+            (user, WsEvent::BalanceUpdated(balance)) if *user == sender() && balance.available == 5_000
+        ));
+    }
+
+    #[test]
+    fn test_transaction_changes_reach_every_party() {
+        let mut service = funded_service();
+        let tx = service.create_transaction(sender(), agent_escrow_request()).unwrap();
+        take_sent_events();
+
+        service.accept_escrow_terms(tx.id, recipient()).unwrap();
+
+        let updates: Vec<Principal> = take_sent_events()
+            .into_iter()
+            .filter_map(|(user, event)| match event {
+                WsEvent::TransactionUpdated(updated) => {
+                    assert_eq!(updated.id, tx.id);
+                    assert_eq!(updated.status, TransactionStatus::InEscrow);
+                    Some(user)
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(updates, vec![sender(), recipient(), agent()]);
+    }
+}
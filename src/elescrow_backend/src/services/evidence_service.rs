@@ -0,0 +1,469 @@
+use candid::Principal;
+use sha2::{Digest, Sha256};
+
+use crate::types::{
+    errors::ApiError,
+    evidence::*,
+    common::AuditAction,
+    role::Permission,
+    transaction::{Attachment, MilestoneStatus, TransactionStatus, TransactionType},
+};
+use crate::models::transaction::TransactionModel;
+use crate::storage::{
+    stable_storage::{StableStorage, StorageManager},
+    memory::MemoryRegion,
+    config::{StableCounter, EVIDENCE_ID_COUNTER},
+};
+use crate::security::validation;
+use crate::utils::constants::{
+    ALLOWED_EVIDENCE_MIME_TYPES, EVIDENCE_UPLOAD_TIMEOUT, MAX_EVIDENCE_CHUNK_SIZE, MAX_EVIDENCE_FILE_SIZE,
+    MAX_EVIDENCE_FILES_PER_TRANSACTION, MAX_EVIDENCE_UPLOADS_IN_PROGRESS,
+};
+use crate::utils::time::now;
+use crate::{AUDIT_LOGGER, TRANSACTION_SERVICE};
+
+/// Stores dispute evidence uploaded in chunks. File metadata and content live
+/// in separate regions so listing files never loads their bytes. Completed
+/// files are attached to the disputed transaction; abandoned uploads expire.
+pub struct EvidenceService {
+    files: StableStorage<u64, EvidenceFile>,
+    chunks: StableStorage<(u64, u32), Vec<u8>>,
+    next_id: StableCounter,
+}
+
+impl EvidenceService {
+    pub fn new() -> Self {
+        Self {
+            files: StableStorage::new(MemoryRegion::Evidence),
+            chunks: StableStorage::new(MemoryRegion::EvidenceChunks),
+            next_id: StableCounter::new(EVIDENCE_ID_COUNTER),
+        }
+    }
+
+    pub fn begin_upload(
+        &self,
+        uploader: Principal,
+        request: BeginEvidenceUploadRequest,
+    ) -> Result<EvidenceFile, ApiError> {
+        let transaction = self.get_transaction(request.transaction_id)?;
+
+        if transaction.from != uploader && transaction.to != uploader && transaction.escrow_agent != Some(uploader) {
+            return Err(ApiError::Unauthorized {
+                reason: "Only the parties or the escrow agent can submit evidence.".to_string(),
+            });
+        }
+
+        if !has_open_dispute(&transaction, request.milestone_id) {
+            return Err(ApiError::InvalidState {
+                current_state: format!("{:?}", transaction.status),
+                required_state: "Disputed or UnderReview".to_string(),
+            });
+        }
+
+        let name = validation::validate_text(&request.name, "name", 1, 255)?;
+        let mime_type = request.mime_type.trim().to_lowercase();
+        if !ALLOWED_EVIDENCE_MIME_TYPES.contains(&mime_type.as_str()) {
+            return Err(ApiError::ValidationError {
+                field: "mime_type".to_string(),
+                message: format!("Allowed types are: {}", ALLOWED_EVIDENCE_MIME_TYPES.join(", ")),
+            });
+        }
+        if request.size == 0 || request.size > MAX_EVIDENCE_FILE_SIZE {
+            return Err(ApiError::ValidationError {
+                field: "size".to_string(),
+                message: format!("File size must be between 1 and {} bytes", MAX_EVIDENCE_FILE_SIZE),
+            });
+        }
+
+        self.ensure_room_for_evidence(request.transaction_id)?;
+        let in_progress = self.files
+            .filter(|_, f| {
+                f.transaction_id == request.transaction_id
+                    && f.uploaded_by == uploader
+                    && f.status == EvidenceStatus::Uploading
+            })
+            .len();
+        if in_progress >= MAX_EVIDENCE_UPLOADS_IN_PROGRESS {
+            return Err(ApiError::ValidationError {
+                field: "transaction_id".to_string(),
+                message: format!(
+                    "Finish your other uploads first; at most {} can be in progress",
+                    MAX_EVIDENCE_UPLOADS_IN_PROGRESS,
+                ),
+            });
+        }
+
+        let file = EvidenceFile {
+            id: self.next_id.next(),
+            transaction_id: request.transaction_id,
+            milestone_id: request.milestone_id,
+            uploaded_by: uploader,
+            name,
+            mime_type,
+            size: request.size,
+            received_bytes: 0,
+            chunk_count: 0,
+            sha256: None,
+            status: EvidenceStatus::Uploading,
+            created_at: now(),
+            completed_at: None,
+        };
+        self.files.insert(file.id, file.clone());
+
+        Ok(file)
+    }
+
+    /// Appends the next chunk of an upload; `chunk_index` must follow the
+    /// last chunk received.
+    pub fn upload_chunk(
+        &self,
+        uploader: Principal,
+        evidence_id: u64,
+        chunk_index: u32,
+        data: Vec<u8>,
+    ) -> Result<EvidenceFile, ApiError> {
+        let mut file = self.get_uploading(uploader, evidence_id)?;
+
+        if chunk_index != file.chunk_count {
+            return Err(ApiError::ValidationError {
+                field: "chunk_index".to_string(),
+                message: format!("Expected chunk {}", file.chunk_count),
+            });
+        }
+        if data.is_empty() || data.len() > MAX_EVIDENCE_CHUNK_SIZE {
+            return Err(ApiError::ValidationError {
+                field: "data".to_string(),
+                message: format!("Chunks must be between 1 and {} bytes", MAX_EVIDENCE_CHUNK_SIZE),
+            });
+        }
+        if file.received_bytes + data.len() as u64 > file.size {
+            return Err(ApiError::ValidationError {
+                field: "data".to_string(),
+                message: format!("Upload exceeds the declared size of {} bytes", file.size),
+            });
+        }
+
+        file.received_bytes += data.len() as u64;
+        file.chunk_count += 1;
+        self.chunks.insert((evidence_id, chunk_index), data);
+        self.files.insert(evidence_id, file.clone());
+
+        Ok(file)
+    }
+
+    /// Seals an upload once all declared bytes arrived. When the client sends
+    /// its own hash, the content must match it.
+    pub fn finish_upload(
+        &self,
+        uploader: Principal,
+        evidence_id: u64,
+        expected_sha256: Option<String>,
+    ) -> Result<EvidenceFile, ApiError> {
+        let mut file = self.get_uploading(uploader, evidence_id)?;
+
+        if file.received_bytes != file.size {
+            return Err(ApiError::ValidationError {
+                field: "size".to_string(),
+                message: format!("Received {} of {} bytes", file.received_bytes, file.size),
+            });
+        }
+
+        if let Err(err) = self.ensure_room_for_evidence(file.transaction_id) {
+            self.discard(&file);
+            return Err(err);
+        }
+
+        let mut hasher = Sha256::new();
+        for index in 0..file.chunk_count {
+            hasher.update(self.chunks.get(&(evidence_id, index)).unwrap_or_default());
+        }
+        let sha256 = hex::encode(hasher.finalize());
+
+        if let Some(expected) = expected_sha256 {
+            if !expected.trim().eq_ignore_ascii_case(&sha256) {
+                self.discard(&file);
+                return Err(ApiError::ValidationError {
+                    field: "sha256".to_string(),
+                    message: "Content hash does not match; upload discarded".to_string(),
+                });
+            }
+        }
+
+        file.sha256 = Some(sha256);
+        file.status = EvidenceStatus::Complete;
+        file.completed_at = Some(now());
+        self.files.insert(evidence_id, file.clone());
+        self.attach_to_transaction(&file)?;
+
+        AUDIT_LOGGER.with(|l| l.borrow().log(
+            uploader,
+            AuditAction::EvidenceUploaded,
+            &format!("transaction_{}", file.transaction_id),
+            Some(format!("Evidence {}: {} ({} bytes)", file.id, file.name, file.size)),
+        ));
+
+        if let Ok(transaction) = self.get_transaction(file.transaction_id) {
+            let mut recipients = vec![transaction.from, transaction.to];
+            recipients.extend(transaction.escrow_agent);
+            for recipient in recipients.into_iter().filter(|p| *p != uploader) {
+                let _ = crate::NOTIFICATION_SERVICE.with(|s| {
+                    s.borrow().create_transaction_notification(
+                        recipient,
+                        file.transaction_id,
+                        &format!("New dispute evidence submitted: {}", file.name),
+                    )
+                });
+            }
+        }
+
+        Ok(file)
+    }
+
+    pub fn list_evidence(&self, viewer: Principal, transaction_id: u64) -> Result<Vec<EvidenceFile>, ApiError> {
+        let transaction = self.get_transaction(transaction_id)?;
+        ensure_can_view(&transaction, viewer)?;
+
+        Ok(self.files
+            .filter(|_, f| {
+                f.transaction_id == transaction_id
+                    && (f.status == EvidenceStatus::Complete || f.uploaded_by == viewer)
+            })
+            .into_iter()
+            .map(|(_, f)| f)
+            .collect())
+    }
+
+    pub fn get_evidence(&self, viewer: Principal, evidence_id: u64) -> Result<EvidenceFile, ApiError> {
+        let file = self.files.get_or_error(&evidence_id, &format!("Evidence {}", evidence_id))?;
+        let transaction = self.get_transaction(file.transaction_id)?;
+        ensure_can_view(&transaction, viewer)?;
+
+        Ok(file)
+    }
+
+    pub fn get_chunk(&self, viewer: Principal, evidence_id: u64, chunk_index: u32) -> Result<Vec<u8>, ApiError> {
+        let file = self.get_evidence(viewer, evidence_id)?;
+
+        if file.status != EvidenceStatus::Complete {
+            return Err(ApiError::InvalidState {
+                current_state: format!("{:?}", file.status),
+                required_state: "Complete".to_string(),
+            });
+        }
+
+        self.chunks.get_or_error(
+            &(evidence_id, chunk_index),
+            &format!("Chunk {} of evidence {}", chunk_index, evidence_id),
+        )
+    }
+
+    /// Drops uploads that were not finished within `EVIDENCE_UPLOAD_TIMEOUT`,
+    /// along with the chunks received so far. Returns how many were removed.
+    pub fn expire_stale_uploads(&self) -> usize {
+        let cutoff = now().saturating_sub(EVIDENCE_UPLOAD_TIMEOUT);
+        let stale = self.files.filter(|_, f| f.status == EvidenceStatus::Uploading && f.created_at <= cutoff);
+
+        for (_, file) in &stale {
+            self.discard(file);
+        }
+
+        stale.len()
+    }
+
+    /// Only completed files count towards the per-dispute limit, so pending
+    /// uploads from one party cannot lock the other out.
+    fn ensure_room_for_evidence(&self, transaction_id: u64) -> Result<(), ApiError> {
+        let completed = self.files
+            .filter(|_, f| f.transaction_id == transaction_id && f.status == EvidenceStatus::Complete)
+            .len();
+        if completed >= MAX_EVIDENCE_FILES_PER_TRANSACTION {
+            return Err(ApiError::ValidationError {
+                field: "transaction_id".to_string(),
+                message: format!("A dispute can have at most {} evidence files", MAX_EVIDENCE_FILES_PER_TRANSACTION),
+            });
+        }
+
+        Ok(())
+    }
+
+    fn attach_to_transaction(&self, file: &EvidenceFile) -> Result<(), ApiError> {
+        let mut transaction = self.get_transaction(file.transaction_id)?;
+        transaction.metadata.attachments.push(Attachment {
+            id: file.id.to_string(),
+            name: file.name.clone(),
+            mime_type: file.mime_type.clone(),
+            size: file.size,
+            url: format!("evidence:{}", file.id),
+            uploaded_by: file.uploaded_by,
+            uploaded_at: file.completed_at.unwrap_or_else(now),
+        });
+        TRANSACTION_SERVICE.with(|s| s.borrow().save_transaction(&transaction));
+
+        Ok(())
+    }
+
+    fn get_uploading(&self, uploader: Principal, evidence_id: u64) -> Result<EvidenceFile, ApiError> {
+        let file = self.files.get_or_error(&evidence_id, &format!("Evidence {}", evidence_id))?;
+
+        if file.uploaded_by != uploader {
+            return Err(ApiError::Unauthorized {
+                reason: "Only the uploader can modify this evidence.".to_string(),
+            });
+        }
+        if file.status != EvidenceStatus::Uploading {
+            return Err(ApiError::InvalidState {
+                current_state: format!("{:?}", file.status),
+                required_state: "Uploading".to_string(),
+            });
+        }
+
+        Ok(file)
+    }
+
+    fn discard(&self, file: &EvidenceFile) {
+        for index in 0..file.chunk_count {
+            self.chunks.remove(&(file.id, index));
+        }
+        self.files.remove(&file.id);
+    }
+
+    fn get_transaction(&self, transaction_id: u64) -> Result<TransactionModel, ApiError> {
+        StorageManager::instance()
+            .transactions()
+            .get_or_error(&transaction_id, &format!("Transaction {}", transaction_id))
+    }
+}
+
+fn has_open_dispute(transaction: &TransactionModel, milestone_id: Option<u32>) -> bool {
+    match (milestone_id, &transaction.transaction_type) {
+        (Some(id), TransactionType::Escrow { milestones: Some(milestones), .. }) => milestones
+            .iter()
+            .any(|m| m.id == id && matches!(m.status, MilestoneStatus::Disputed { .. })),
+        (Some(_), _) => false,
+        (None, _) => matches!(
+            transaction.status,
+            TransactionStatus::Disputed { .. } | TransactionStatus::UnderReview { .. }
+        ),
+    }
+}
+
+fn ensure_can_view(transaction: &TransactionModel, viewer: Principal) -> Result<(), ApiError> {
+    let is_participant = transaction.from == viewer
+        || transaction.to == viewer
+        || transaction.escrow_agent == Some(viewer);
+    let is_arbiter = crate::ROLE_SERVICE.with(|r| r.borrow().has_permission(viewer, Permission::ResolveDisputes));
+
+    if !is_participant && !is_arbiter {
+        return Err(ApiError::Unauthorized {
+            reason: "Only the parties, the escrow agent or an arbiter can view dispute evidence.".to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{disputed_escrow, escrow_request, funded_service, recipient, sender};
+    use crate::utils::time::advance_mock_time;
+
+    fn outsider() -> Principal {
+        Principal::from_slice(&[9; 29])
+    }
+
+    fn disputed_transaction() -> u64 {
+        disputed_escrow(&mut funded_service(), escrow_request(None, None)).id
+    }
+
+    fn upload_request(transaction_id: u64, mime_type: &str, size: u64) -> BeginEvidenceUploadRequest {
+        BeginEvidenceUploadRequest {
+            transaction_id,
+            milestone_id: None,
+            name: "screenshot.png".to_string(),
+            mime_type: mime_type.to_string(),
+            size,
+        }
+    }
+
+    #[test]
+    fn test_chunked_upload_is_hashed_and_downloadable() {
+        let service = EvidenceService::new();
+        let transaction_id = disputed_transaction();
+        let content = b"first half|second half".to_vec();
+
+        let file = service.begin_upload(sender(), upload_request(transaction_id, "image/png", content.len() as u64)).unwrap();
+        service.upload_chunk(sender(), file.id, 0, content[..11].to_vec()).unwrap();
+        assert!(service.finish_upload(sender(), file.id, None).is_err());
+        service.upload_chunk(sender(), file.id, 1, content[11..].to_vec()).unwrap();
+
+        let expected = hex::encode(Sha256::digest(&content));
+        let file = service.finish_upload(sender(), file.id, Some(expected.clone())).unwrap();
+        assert_eq!(file.sha256, Some(expected));
+        assert_eq!(file.status, EvidenceStatus::Complete);
+
+        let listed = service.list_evidence(recipient(), transaction_id).unwrap();
+        assert_eq!(listed, vec![file.clone()]);
+        let downloaded = [
+            service.get_chunk(recipient(), file.id, 0).unwrap(),
+            service.get_chunk(recipient(), file.id, 1).unwrap(),
+        ].concat();
+        assert_eq!(downloaded, content);
+
+        assert!(matches!(
+            service.get_chunk(outsider(), file.id, 0),
+            Err(ApiError::Unauthorized { .. })
+        ));
+    }
+
+    #[test]
+    fn test_pending_uploads_do_not_block_the_other_party() {
+        let service = EvidenceService::new();
+        let transaction_id = disputed_transaction();
+
+        let pending: Vec<EvidenceFile> = (0..MAX_EVIDENCE_UPLOADS_IN_PROGRESS)
+            .map(|_| service.begin_upload(recipient(), upload_request(transaction_id, "image/png", 4)).unwrap())
+            .collect();
+        service.upload_chunk(recipient(), pending[0].id, 0, vec![1; 2]).unwrap();
+        assert!(service.begin_upload(recipient(), upload_request(transaction_id, "image/png", 4)).is_err());
+
+        let file = service.begin_upload(sender(), upload_request(transaction_id, "image/png", 4)).unwrap();
+        service.upload_chunk(sender(), file.id, 0, vec![2; 4]).unwrap();
+        let file = service.finish_upload(sender(), file.id, None).unwrap();
+
+        let attachments = crate::storage::stable_storage::StorageManager::instance()
+            .transactions()
+            .get(&transaction_id)
+            .unwrap()
+            .metadata
+            .attachments;
+        assert_eq!(attachments.len(), 1);
+        assert_eq!(attachments[0].id, file.id.to_string());
+
+        advance_mock_time(EVIDENCE_UPLOAD_TIMEOUT);
+        assert_eq!(service.expire_stale_uploads(), MAX_EVIDENCE_UPLOADS_IN_PROGRESS);
+        assert!(service.chunks.get(&(pending[0].id, 0)).is_none());
+        assert_eq!(service.get_evidence(recipient(), file.id).unwrap().status, EvidenceStatus::Complete);
+        service.begin_upload(recipient(), upload_request(transaction_id, "image/png", 4)).unwrap();
+    }
+
+    #[test]
+    fn test_upload_validation() {
+        let service = EvidenceService::new();
+        let transaction_id = disputed_transaction();
+
+        assert!(service.begin_upload(sender(), upload_request(transaction_id, "application/x-msdownload", 10)).is_err());
+        assert!(service.begin_upload(sender(), upload_request(transaction_id, "application/pdf", MAX_EVIDENCE_FILE_SIZE + 1)).is_err());
+        assert!(matches!(
+            service.begin_upload(outsider(), upload_request(transaction_id, "application/pdf", 10)),
+            Err(ApiError::Unauthorized { .. })
+        ));
+
+        let file = service.begin_upload(recipient(), upload_request(transaction_id, "application/pdf", 4)).unwrap();
+        assert!(service.upload_chunk(recipient(), file.id, 1, vec![1]).is_err());
+        assert!(service.upload_chunk(recipient(), file.id, 0, vec![1; 5]).is_err());
+        service.upload_chunk(recipient(), file.id, 0, vec![1; 4]).unwrap();
+        assert!(service.finish_upload(recipient(), file.id, Some("00".repeat(32))).is_err());
+        assert!(service.get_evidence(recipient(), file.id).is_err());
+    }
+}
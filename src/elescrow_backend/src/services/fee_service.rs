@@ -0,0 +1,385 @@
+use candid::Principal;
+
+use crate::types::{
+    errors::ApiError,
+    common::AuditAction,
+    fees::*,
+    transaction::Currency,
+};
+use crate::storage::{
+    stable_storage::StableStorage,
+    memory::MemoryRegion,
+    config::{self, StableCounter, FEE_PERCENTAGE_BPS, FEE_PROMOTION_ID_COUNTER},
+};
+use crate::security::validation;
+use crate::utils::constants::{DEFAULT_TRANSACTION_FEE_BPS, MAX_FEE_PROMOTION_CURRENCIES, MAX_FEE_TIERS};
+use crate::utils::time::now;
+use crate::AUDIT_LOGGER;
+
+/// Fee schedules per transaction type and currency. A currency-specific rule
+/// wins over the type's catch-all rule; with neither, the flat default rate
+/// applies. Active promotions waive the fee entirely.
+pub struct FeeService {
+    rules: StableStorage<String, FeeRule>,
+    promotions: StableStorage<u64, FeePromotion>,
+    next_promotion_id: StableCounter,
+}
+
+impl FeeService {
+    pub fn new() -> Self {
+        Self {
+            rules: StableStorage::new(MemoryRegion::FeeRules),
+            promotions: StableStorage::new(MemoryRegion::FeePromotions),
+            next_promotion_id: StableCounter::new(FEE_PROMOTION_ID_COUNTER),
+        }
+    }
+
+    pub fn quote(&self, transaction_type: FeeTransactionType, amount: u64, currency: &Currency) -> FeeQuote {
+        let at = now();
+        let promotion = self
+            .promotions
+            .values()
+            .into_iter()
+            .find(|promotion| promotion.applies(transaction_type, currency, at));
+
+        let (fee_bps, fee) = match self.find_rule(transaction_type, currency) {
+            Some(rule) => {
+                let fee_bps = rule
+                    .tiers
+                    .iter()
+                    .rev()
+                    .find(|tier| amount >= tier.min_amount)
+                    .map_or(0, |tier| tier.fee_bps);
+                let mut fee = apply_bps(amount, fee_bps);
+                if let Some(min_fee) = rule.min_fee {
+                    fee = fee.max(min_fee);
+                }
+                if let Some(max_fee) = rule.max_fee {
+                    fee = fee.min(max_fee);
+                }
+                (fee_bps, fee)
+            }
+            None => {
+                let fee_bps = config::get_or(FEE_PERCENTAGE_BPS, DEFAULT_TRANSACTION_FEE_BPS);
+                (fee_bps, apply_bps(amount, fee_bps))
+            }
+        };
+        let fee = if promotion.is_some() { 0 } else { fee };
+
+        FeeQuote {
+            transaction_type,
+            currency: currency.clone(),
+            amount,
+            fee,
+            total: amount.saturating_add(fee),
+            fee_bps,
+            promotion,
+        }
+    }
+
+    pub fn quote_request(&self, request: FeeQuoteRequest) -> Result<FeeQuote, ApiError> {
+        validation::validate_amount(request.amount, Some(1), None)?;
+        let currency = request.currency.unwrap_or_default();
+        validation::validate_currency(&currency)?;
+
+        Ok(self.quote(request.transaction_type, request.amount, &currency))
+    }
+
+    pub fn set_rule(&self, admin: Principal, rule: FeeRule) -> Result<(), ApiError> {
+        validate_rule(&rule)?;
+
+        let key = rule_key(rule.transaction_type, rule.currency.as_ref());
+        self.rules.insert(key.clone(), rule.clone());
+
+        AUDIT_LOGGER.with(|l| l.borrow().log(
+            admin,
+            AuditAction::ConfigurationChanged,
+            "fee_rule",
+            Some(format!(
+                "{}: {} tier(s) from {} bps, min_fee {:?}, max_fee {:?}",
+                key,
+                rule.tiers.len(),
+                rule.tiers[0].fee_bps,
+                rule.min_fee,
+                rule.max_fee,
+            )),
+        ));
+
+        Ok(())
+    }
+
+    pub fn remove_rule(
+        &self,
+        admin: Principal,
+        transaction_type: FeeTransactionType,
+        currency: Option<Currency>,
+    ) -> Result<(), ApiError> {
+        let key = rule_key(transaction_type, currency.as_ref());
+        self.rules.remove(&key).ok_or_else(|| ApiError::NotFound {
+            resource: format!("Fee rule {}", key),
+        })?;
+
+        AUDIT_LOGGER.with(|l| l.borrow().log(
+            admin,
+            AuditAction::ConfigurationChanged,
+            "fee_rule",
+            Some(format!("Removed {}", key)),
+        ));
+
+        Ok(())
+    }
+
+    pub fn get_rules(&self) -> Vec<FeeRule> {
+        self.rules.values()
+    }
+
+    pub fn create_promotion(&self, admin: Principal, request: CreateFeePromotionRequest) -> Result<FeePromotion, ApiError> {
+        let name = validation::validate_text(&request.name, "name", 1, 100)?;
+        if request.starts_at >= request.ends_at {
+            return Err(ApiError::ValidationError {
+                field: "ends_at".to_string(),
+                message: "Promotion must end after it starts".to_string(),
+            });
+        }
+        if request.ends_at <= now() {
+            return Err(ApiError::ValidationError {
+                field: "ends_at".to_string(),
+                message: "Promotion must end in the future".to_string(),
+            });
+        }
+        if request.currencies.len() > MAX_FEE_PROMOTION_CURRENCIES {
+            return Err(ApiError::ValidationError {
+                field: "currencies".to_string(),
+                message: format!("A promotion can list at most {} currencies", MAX_FEE_PROMOTION_CURRENCIES),
+            });
+        }
+        for currency in &request.currencies {
+            validation::validate_currency(currency)?;
+        }
+        // Each type at most once, which also bounds the list.
+        let mut transaction_types: Vec<FeeTransactionType> = vec![];
+        for transaction_type in request.transaction_types {
+            if !transaction_types.contains(&transaction_type) {
+                transaction_types.push(transaction_type);
+            }
+        }
+
+        let promotion = FeePromotion {
+            id: self.next_promotion_id.next(),
+            name,
+            starts_at: request.starts_at,
+            ends_at: request.ends_at,
+            transaction_types,
+            currencies: request.currencies,
+            created_by: admin,
+            created_at: now(),
+        };
+        self.promotions.insert(promotion.id, promotion.clone());
+
+        AUDIT_LOGGER.with(|l| l.borrow().log(
+            admin,
+            AuditAction::ConfigurationChanged,
+            "fee_promotion",
+            Some(format!("Created promotion {} ({} to {})", promotion.id, promotion.starts_at, promotion.ends_at)),
+        ));
+
+        Ok(promotion)
+    }
+
+    pub fn remove_promotion(&self, admin: Principal, promotion_id: u64) -> Result<(), ApiError> {
+        self.promotions.remove(&promotion_id).ok_or_else(|| ApiError::NotFound {
+            resource: format!("Fee promotion {}", promotion_id),
+        })?;
+
+        AUDIT_LOGGER.with(|l| l.borrow().log(
+            admin,
+            AuditAction::ConfigurationChanged,
+            "fee_promotion",
+            Some(format!("Removed promotion {}", promotion_id)),
+        ));
+
+        Ok(())
+    }
+
+    pub fn get_promotions(&self) -> Vec<FeePromotion> {
+        self.promotions.values()
+    }
+
+    fn find_rule(&self, transaction_type: FeeTransactionType, currency: &Currency) -> Option<FeeRule> {
+        self.rules
+            .get(&rule_key(transaction_type, Some(currency)))
+            .or_else(|| self.rules.get(&rule_key(transaction_type, None)))
+    }
+}
+
+fn rule_key(transaction_type: FeeTransactionType, currency: Option<&Currency>) -> String {
+    format!("{:?}/{}", transaction_type, currency.map_or("*".to_string(), |c| c.code()))
+}
+
+/// `fee_bps` basis points of `amount`, worked out in u128 so large amounts
+/// of high-decimal tokens cannot overflow.
+pub(crate) fn apply_bps(amount: u64, fee_bps: u64) -> u64 {
+    (amount as u128 * fee_bps as u128 / 10_000) as u64
+}
+
+fn validate_rule(rule: &FeeRule) -> Result<(), ApiError> {
+    if let Some(currency) = &rule.currency {
+        validation::validate_currency(currency)?;
+    }
+
+    if rule.tiers.len() > MAX_FEE_TIERS {
+        return Err(ApiError::ValidationError {
+            field: "tiers".to_string(),
+            message: format!("A rule can have at most {} tiers", MAX_FEE_TIERS),
+        });
+    }
+    if rule.tiers.first().is_none_or(|tier| tier.min_amount != 0) {
+        return Err(ApiError::ValidationError {
+            field: "tiers".to_string(),
+            message: "The first tier must start at 0".to_string(),
+        });
+    }
+    if rule.tiers.windows(2).any(|pair| pair[0].min_amount >= pair[1].min_amount) {
+        return Err(ApiError::ValidationError {
+            field: "tiers".to_string(),
+            message: "Tiers must be sorted by strictly increasing min_amount".to_string(),
+        });
+    }
+    if rule.tiers.iter().any(|tier| tier.fee_bps > 10000) {
+        return Err(ApiError::ValidationError {
+            field: "tiers".to_string(),
+            message: "Fee cannot exceed 10000 bps (100%)".to_string(),
+        });
+    }
+
+    if let (Some(min_fee), Some(max_fee)) = (rule.min_fee, rule.max_fee) {
+        if min_fee > max_fee {
+            return Err(ApiError::ValidationError {
+                field: "min_fee".to_string(),
+                message: "Minimum fee cannot exceed the maximum fee".to_string(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{constants::NANOS_PER_DAY, time::advance_mock_time};
+    use ic_stable_structures::Storable;
+
+    fn admin() -> Principal {
+        Principal::from_slice(&[9; 29])
+    }
+
+    #[test]
+    fn test_tiered_rule_with_caps_and_currency_override() {
+        let service = FeeService::new();
+        service.set_rule(admin(), FeeRule {
+            transaction_type: FeeTransactionType::Escrow,
+            currency: None,
+            tiers: vec![
+                FeeTier { min_amount: 0, fee_bps: 200 },
+                FeeTier { min_amount: 100_000, fee_bps: 100 },
+            ],
+            min_fee: Some(50),
+            max_fee: Some(5_000),
+        }).unwrap();
+        service.set_rule(admin(), FeeRule {
+            transaction_type: FeeTransactionType::Escrow,
+            currency: Some(Currency::USDT),
+            tiers: vec![FeeTier { min_amount: 0, fee_bps: 10 }],
+            min_fee: None,
+            max_fee: None,
+        }).unwrap();
+
+        let escrow = FeeTransactionType::Escrow;
+        assert_eq!(service.quote(escrow, 1_000, &Currency::ICP).fee, 50);
+        assert_eq!(service.quote(escrow, 10_000, &Currency::ICP).fee, 200);
+        let quote = service.quote(escrow, 200_000, &Currency::ICP);
+        assert_eq!((quote.fee_bps, quote.fee, quote.total), (100, 2_000, 202_000));
+        assert_eq!(service.quote(escrow, 10_000_000, &Currency::ICP).fee, 5_000);
+        assert_eq!(service.quote(escrow, 10_000_000, &Currency::USDT).fee, 10_000);
+
+        // No rule for direct payments: the flat default rate.
+        assert_eq!(service.quote(FeeTransactionType::DirectPayment, 10_000, &Currency::ICP).fee, 100);
+
+        let unsorted = FeeRule {
+            transaction_type: FeeTransactionType::DirectPayment,
+            currency: None,
+            tiers: vec![FeeTier { min_amount: 0, fee_bps: 100 }, FeeTier { min_amount: 0, fee_bps: 50 }],
+            min_fee: None,
+            max_fee: None,
+        };
+        assert!(matches!(service.set_rule(admin(), unsorted), Err(ApiError::ValidationError { .. })));
+
+        let tiers = |count: u64| (0..count).map(|i| FeeTier { min_amount: i * (u64::MAX / 32), fee_bps: 10_000 }).collect();
+        let largest = FeeRule {
+            transaction_type: FeeTransactionType::ScheduledPayment,
+            currency: Some(Currency::Custom { symbol: "A".repeat(16), decimals: 18 }),
+            tiers: tiers(MAX_FEE_TIERS as u64),
+            min_fee: Some(u64::MAX),
+            max_fee: Some(u64::MAX),
+        };
+        assert!(largest.to_bytes().len() as u32 <= FeeRule::BOUND.max_size());
+        service.set_rule(admin(), largest.clone()).unwrap();
+        let oversized = FeeRule { tiers: tiers(MAX_FEE_TIERS as u64 + 1), ..largest };
+        assert!(matches!(service.set_rule(admin(), oversized), Err(ApiError::ValidationError { .. })));
+    }
+
+    #[test]
+    fn test_apply_bps_does_not_overflow() {
+        assert_eq!(apply_bps(u64::MAX, 10_000), u64::MAX);
+        assert_eq!(apply_bps(u64::MAX / 2, 5_000), u64::MAX / 4);
+        assert_eq!(apply_bps(10_000, 250), 250);
+    }
+
+    #[test]
+    fn test_largest_promotion_fits_storage_bound() {
+        let service = FeeService::new();
+        let custom = |i: usize| Currency::Custom { symbol: format!("{:A>16}", i), decimals: 18 };
+        let request = |currencies: usize| CreateFeePromotionRequest {
+            name: "n".repeat(100),
+            starts_at: u64::MAX - 1,
+            ends_at: u64::MAX,
+            transaction_types: [FeeTransactionType::DirectPayment, FeeTransactionType::Escrow, FeeTransactionType::ScheduledPayment]
+                .repeat(10),
+            currencies: (0..currencies).map(custom).collect(),
+        };
+
+        let promotion = service.create_promotion(admin(), request(MAX_FEE_PROMOTION_CURRENCIES)).unwrap();
+        assert_eq!(promotion.transaction_types.len(), 3);
+        assert!(promotion.to_bytes().len() as u32 <= FeePromotion::BOUND.max_size());
+        assert!(matches!(
+            service.create_promotion(admin(), request(MAX_FEE_PROMOTION_CURRENCIES + 1)),
+            Err(ApiError::ValidationError { .. })
+        ));
+    }
+
+    #[test]
+    fn test_promotion_waives_fee_inside_window() {
+        let service = FeeService::new();
+        let start = now() + NANOS_PER_DAY;
+        service.create_promotion(admin(), CreateFeePromotionRequest {
+            name: "Launch week".to_string(),
+            starts_at: start,
+            ends_at: start + 7 * NANOS_PER_DAY,
+            transaction_types: vec![FeeTransactionType::DirectPayment],
+            currencies: vec![],
+        }).unwrap();
+
+        let direct = FeeTransactionType::DirectPayment;
+        assert_eq!(service.quote(direct, 10_000, &Currency::ICP).fee, 100);
+
+        advance_mock_time(NANOS_PER_DAY);
+        let quote = service.quote(direct, 10_000, &Currency::ICP);
+        assert_eq!((quote.fee, quote.total), (0, 10_000));
+        assert!(quote.promotion.is_some());
+        assert_eq!(service.quote(FeeTransactionType::Escrow, 10_000, &Currency::ICP).fee, 100);
+
+        advance_mock_time(7 * NANOS_PER_DAY);
+        assert_eq!(service.quote(direct, 10_000, &Currency::ICP).fee, 100);
+    }
+}
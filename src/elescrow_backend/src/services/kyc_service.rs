@@ -0,0 +1,473 @@
+use candid::Principal;
+
+use crate::types::{
+    errors::ApiError,
+    kyc::*,
+    notification::{NotificationPriority, NotificationType, RelatedResource},
+    user::{KycStatus, VerificationLevel},
+};
+use crate::storage::{
+    stable_storage::StableStorage,
+    memory::MemoryRegion,
+    config::{StableCounter, KYC_SUBMISSION_ID_COUNTER},
+};
+use crate::security::validation;
+use crate::utils::constants::{
+    KYC_APPROVAL_VALIDITY, MAX_KYC_DOCUMENTS, MAX_KYC_DOCUMENT_TYPE_LENGTH, MAX_KYC_FILE_NAME_LENGTH,
+    MAX_KYC_REJECTION_REASON_LENGTH,
+};
+use crate::utils::time::now;
+use crate::{NOTIFICATION_SERVICE, USER_SERVICE};
+
+/// Moves users through KYC: a submission starts Pending, is claimed by a
+/// reviewer (UnderReview), then Approved or Rejected. Approvals lapse to
+/// Expired after `KYC_APPROVAL_VALIDITY`. The account's `kyc_status` mirrors
+/// the user's latest submission, and every transition is audited and notified.
+pub struct KycService {
+    submissions: StableStorage<u64, KycSubmission>,
+    next_id: StableCounter,
+}
+
+impl KycService {
+    pub fn new() -> Self {
+        Self {
+            submissions: StableStorage::new(MemoryRegion::KycSubmissions),
+            next_id: StableCounter::new(KYC_SUBMISSION_ID_COUNTER),
+        }
+    }
+
+    pub fn submit(&self, user: Principal, request: SubmitKycRequest) -> Result<KycSubmission, ApiError> {
+        let account = USER_SERVICE
+            .with(|s| s.borrow().get_account(user))
+            .ok_or_else(|| ApiError::NotFound { resource: "User".to_string() })?;
+
+        if let Some(open) = self.get_latest(user).filter(|s| matches!(s.status, KycStatus::Pending | KycStatus::UnderReview)) {
+            return Err(ApiError::InvalidState {
+                current_state: format!("{:?}", open.status),
+                required_state: "NotStarted, Approved, Rejected or Expired".to_string(),
+            });
+        }
+
+        if request.requested_level == VerificationLevel::Basic {
+            return Err(ApiError::ValidationError {
+                field: "requested_level".to_string(),
+                message: "Basic verification does not require KYC".to_string(),
+            });
+        }
+
+        if account.kyc_status == KycStatus::Approved && request.requested_level <= account.verification_level {
+            return Err(ApiError::ValidationError {
+                field: "requested_level".to_string(),
+                message: format!("Account is already verified to {:?}", account.verification_level),
+            });
+        }
+
+        let documents = validate_documents(request.documents)?;
+
+        let submission = KycSubmission {
+            id: self.next_id.next(),
+            user,
+            requested_level: request.requested_level,
+            documents,
+            status: KycStatus::Pending,
+            reviewer: None,
+            submitted_at: now(),
+            claimed_at: None,
+            reviewed_at: None,
+            expires_at: None,
+        };
+
+        self.submissions.insert(submission.id, submission.clone());
+        self.transition(&submission, user, None)?;
+
+        Ok(submission)
+    }
+
+    /// The user's most recent submission, whatever its status.
+    pub fn get_latest(&self, user: Principal) -> Option<KycSubmission> {
+        self.submissions
+            .filter(|_, s| s.user == user)
+            .into_iter()
+            .map(|(_, s)| s)
+            .max_by_key(|s| s.id)
+    }
+
+    pub fn get_submission(&self, id: u64) -> Result<KycSubmission, ApiError> {
+        self.submissions.get_or_error(&id, "KYC submission")
+    }
+
+    /// Submissions awaiting a decision, oldest first.
+    pub fn get_review_queue(&self) -> Vec<KycSubmission> {
+        let mut queue: Vec<KycSubmission> = self.submissions
+            .filter(|_, s| matches!(s.status, KycStatus::Pending | KycStatus::UnderReview))
+            .into_iter()
+            .map(|(_, s)| s)
+            .collect();
+
+        queue.sort_by_key(|s| s.submitted_at);
+        queue
+    }
+
+    pub fn claim(&self, reviewer: Principal, id: u64) -> Result<KycSubmission, ApiError> {
+        let mut submission = self.get_submission(id)?;
+
+        if submission.user == reviewer {
+            return Err(ApiError::Forbidden {
+                action: "review your own KYC submission".to_string(),
+            });
+        }
+
+        if submission.status != KycStatus::Pending {
+            return Err(ApiError::InvalidState {
+                current_state: format!("{:?}", submission.status),
+                required_state: "Pending".to_string(),
+            });
+        }
+
+        submission.status = KycStatus::UnderReview;
+        submission.reviewer = Some(reviewer);
+        submission.claimed_at = Some(now());
+
+        self.submissions.insert(id, submission.clone());
+        self.transition(&submission, reviewer, None)?;
+
+        Ok(submission)
+    }
+
+    pub fn approve(&self, reviewer: Principal, id: u64) -> Result<KycSubmission, ApiError> {
+        let mut submission = self.get_claimed(reviewer, id)?;
+        let now = now();
+
+        submission.status = KycStatus::Approved;
+        submission.reviewed_at = Some(now);
+        submission.expires_at = Some(now + KYC_APPROVAL_VALIDITY);
+
+        // An upgrade replaces the approval it builds on.
+        for (previous_id, mut previous) in self.submissions.filter(|_, s| s.user == submission.user && s.status == KycStatus::Approved) {
+            previous.status = KycStatus::Expired;
+            self.submissions.insert(previous_id, previous);
+        }
+
+        self.submissions.insert(id, submission.clone());
+        self.transition(&submission, reviewer, Some(submission.requested_level.clone()))?;
+
+        Ok(submission)
+    }
+
+    pub fn reject(&self, reviewer: Principal, id: u64, reason: String) -> Result<KycSubmission, ApiError> {
+        let reason = validation::validate_text(&reason, "reason", 1, MAX_KYC_REJECTION_REASON_LENGTH)?;
+        let mut submission = self.get_claimed(reviewer, id)?;
+
+        submission.status = KycStatus::Rejected { reason };
+        submission.reviewed_at = Some(now());
+
+        self.submissions.insert(id, submission.clone());
+        self.transition(&submission, reviewer, None)?;
+
+        Ok(submission)
+    }
+
+    /// Expires approvals past their validity and drops the users back to
+    /// Basic verification. Returns how many approvals expired.
+    pub fn expire_approvals(&self) -> u64 {
+        let now = now();
+        let due: Vec<KycSubmission> = self.submissions
+            .filter(|_, s| s.status == KycStatus::Approved && s.expires_at.is_some_and(|at| at <= now))
+            .into_iter()
+            .map(|(_, s)| s)
+            .collect();
+
+        let mut expired = 0;
+        for mut submission in due {
+            submission.status = KycStatus::Expired;
+            self.submissions.insert(submission.id, submission.clone());
+
+            // The account's status follows the newest submission, e.g. a pending
+            // upgrade; the lapsed approval only takes its level away.
+            let kyc_status = self.get_latest(submission.user)
+                .filter(|latest| latest.id != submission.id)
+                .map_or(KycStatus::Expired, |latest| latest.status);
+
+            let updated = USER_SERVICE.with(|s| {
+                s.borrow().update_kyc_status(
+                    submission.user,
+                    kyc_status,
+                    Some(VerificationLevel::Basic),
+                    Principal::management_canister(),
+                )
+            });
+            if updated.is_ok() {
+                self.notify(&submission);
+                expired += 1;
+            }
+        }
+
+        expired
+    }
+
+    fn get_claimed(&self, reviewer: Principal, id: u64) -> Result<KycSubmission, ApiError> {
+        let submission = self.get_submission(id)?;
+
+        if submission.status != KycStatus::UnderReview {
+            return Err(ApiError::InvalidState {
+                current_state: format!("{:?}", submission.status),
+                required_state: "UnderReview".to_string(),
+            });
+        }
+
+        if submission.reviewer != Some(reviewer) {
+            return Err(ApiError::Forbidden {
+                action: "decide on a submission claimed by another reviewer".to_string(),
+            });
+        }
+
+        Ok(submission)
+    }
+
+    /// Mirrors the submission's status onto the account, which audits it, and
+    /// tells the user.
+    fn transition(
+        &self,
+        submission: &KycSubmission,
+        actor: Principal,
+        verification_level: Option<VerificationLevel>,
+    ) -> Result<(), ApiError> {
+        USER_SERVICE.with(|s| {
+            s.borrow().update_kyc_status(submission.user, submission.status.clone(), verification_level, actor)
+        })?;
+
+        self.notify(submission);
+        Ok(())
+    }
+
+    fn notify(&self, submission: &KycSubmission) {
+        let (notification_type, title, message, priority) = match &submission.status {
+            KycStatus::Approved => (
+                NotificationType::AccountVerified,
+                "Verification approved",
+                format!("Your account is now verified to {:?}.", submission.requested_level),
+                NotificationPriority::High,
+            ),
+            KycStatus::Rejected { reason } => (
+                NotificationType::SecurityAlert,
+                "Verification rejected",
+                format!("Your KYC submission was rejected: {}", reason),
+                NotificationPriority::High,
+            ),
+            KycStatus::Expired => (
+                NotificationType::SecurityAlert,
+                "Verification expired",
+                "Your KYC approval has expired. Submit new documents to restore your verification level.".to_string(),
+                NotificationPriority::Urgent,
+            ),
+            KycStatus::UnderReview => (
+                NotificationType::SecurityAlert,
+                "Verification in review",
+                "A reviewer has started checking your KYC submission.".to_string(),
+                NotificationPriority::Normal,
+            ),
+            _ => (
+                NotificationType::SecurityAlert,
+                "Verification submitted",
+                "Your KYC submission was received and is waiting for review.".to_string(),
+                NotificationPriority::Normal,
+            ),
+        };
+
+        let _ = NOTIFICATION_SERVICE.with(|s| {
+            s.borrow().create(
+                submission.user,
+                notification_type,
+                title.to_string(),
+                message,
+                priority,
+                Some(RelatedResource::User(submission.user)),
+                vec![],
+            )
+        });
+    }
+}
+
+fn validate_documents(documents: Vec<KycDocument>) -> Result<Vec<KycDocument>, ApiError> {
+    if documents.is_empty() || documents.len() > MAX_KYC_DOCUMENTS {
+        return Err(ApiError::ValidationError {
+            field: "documents".to_string(),
+            message: format!("Between 1 and {} documents are required", MAX_KYC_DOCUMENTS),
+        });
+    }
+
+    documents
+        .into_iter()
+        .map(|document| {
+            let sha256 = document.sha256.trim().to_lowercase();
+            if sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(ApiError::ValidationError {
+                    field: "sha256".to_string(),
+                    message: "Expected a hex-encoded SHA-256 hash".to_string(),
+                });
+            }
+
+            let document_type = match document.document_type {
+                KycDocumentType::Other(name) => KycDocumentType::Other(
+                    validation::validate_text(&name, "document_type", 1, MAX_KYC_DOCUMENT_TYPE_LENGTH)?,
+                ),
+                document_type => document_type,
+            };
+
+            // ISO 3166-1 alpha-2, e.g. "DE".
+            let issuing_country = match document.issuing_country {
+                Some(country) => {
+                    let country = validation::validate_text(&country, "issuing_country", 2, 2)?.to_uppercase();
+                    if !country.chars().all(|c| c.is_ascii_alphabetic()) {
+                        return Err(ApiError::ValidationError {
+                            field: "issuing_country".to_string(),
+                            message: "Expected a two-letter country code".to_string(),
+                        });
+                    }
+                    Some(country)
+                }
+                None => None,
+            };
+
+            Ok(KycDocument {
+                document_type,
+                file_name: validation::validate_text(&document.file_name, "file_name", 1, MAX_KYC_FILE_NAME_LENGTH)?,
+                sha256,
+                issuing_country,
+                ..document
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::user::RegisterUserRequest;
+    use crate::utils::time::advance_mock_time;
+
+    fn user() -> Principal {
+        Principal::from_slice(&[1; 29])
+    }
+
+    fn reviewer() -> Principal {
+        Principal::from_slice(&[9; 29])
+    }
+
+    fn setup() -> KycService {
+        USER_SERVICE.with(|s| {
+            s.borrow().register(user(), RegisterUserRequest {
+                username: "kycuser".to_string(),
+                email: None,
+                display_name: None,
+                referral_code: None,
+            })
+        }).unwrap();
+        KycService::new()
+    }
+
+    fn request() -> SubmitKycRequest {
+        SubmitKycRequest {
+            requested_level: VerificationLevel::Standard,
+            documents: vec![KycDocument {
+                document_type: KycDocumentType::Passport,
+                file_name: "passport.pdf".to_string(),
+                sha256: "AB".repeat(32),
+                size: 2048,
+                issuing_country: Some("DE".to_string()),
+                expires_at: None,
+            }],
+        }
+    }
+
+    fn account() -> crate::types::user::UserAccount {
+        USER_SERVICE.with(|s| s.borrow().get_account(user())).unwrap()
+    }
+
+    #[test]
+    fn test_review_flow_and_expiry() {
+        let service = setup();
+
+        let submission = service.submit(user(), request()).unwrap();
+        assert_eq!(submission.documents[0].sha256, "ab".repeat(32));
+        assert_eq!(account().kyc_status, KycStatus::Pending);
+        assert!(service.submit(user(), request()).is_err());
+
+        assert!(matches!(service.claim(user(), submission.id), Err(ApiError::Forbidden { .. })));
+        service.claim(reviewer(), submission.id).unwrap();
+        assert_eq!(account().kyc_status, KycStatus::UnderReview);
+        assert!(service.approve(Principal::from_slice(&[10; 29]), submission.id).is_err());
+
+        service.approve(reviewer(), submission.id).unwrap();
+        let approved = account();
+        assert_eq!(approved.kyc_status, KycStatus::Approved);
+        assert_eq!(approved.verification_level, VerificationLevel::Standard);
+        assert!(approved.is_verified);
+        assert!(service.get_review_queue().is_empty());
+
+        advance_mock_time(KYC_APPROVAL_VALIDITY);
+        assert_eq!(service.expire_approvals(), 1);
+        let expired = account();
+        assert_eq!(expired.kyc_status, KycStatus::Expired);
+        assert_eq!(expired.verification_level, VerificationLevel::Basic);
+        assert!(!expired.is_verified);
+
+        let notifications = NOTIFICATION_SERVICE.with(|s| s.borrow().get_unread_count(user()));
+        assert_eq!(notifications, 4);
+    }
+
+    #[test]
+    fn test_rejection_allows_resubmission() {
+        let service = setup();
+
+        let mut invalid = request();
+        invalid.documents[0].sha256 = "not-a-hash".to_string();
+        assert!(service.submit(user(), invalid).is_err());
+
+        let mut invalid = request();
+        invalid.documents[0].issuing_country = Some("Germany".to_string());
+        assert!(service.submit(user(), invalid).is_err());
+        let mut invalid = request();
+        invalid.documents[0].document_type = KycDocumentType::Other("x".repeat(MAX_KYC_DOCUMENT_TYPE_LENGTH + 1));
+        assert!(service.submit(user(), invalid).is_err());
+
+        let submission = service.submit(user(), request()).unwrap();
+        assert!(service.reject(reviewer(), submission.id, "Blurry".to_string()).is_err());
+        service.claim(reviewer(), submission.id).unwrap();
+        service.reject(reviewer(), submission.id, "Blurry".to_string()).unwrap();
+        assert_eq!(account().kyc_status, KycStatus::Rejected { reason: "Blurry".to_string() });
+
+        let resubmitted = service.submit(user(), request()).unwrap();
+        assert_eq!(service.get_latest(user()).unwrap().id, resubmitted.id);
+        assert_eq!(service.get_review_queue().len(), 1);
+    }
+
+    #[test]
+    fn test_largest_submission_fits_storage_bound() {
+        use ic_stable_structures::Storable;
+
+        let document = KycDocument {
+            document_type: KycDocumentType::Other("t".repeat(MAX_KYC_DOCUMENT_TYPE_LENGTH)),
+            file_name: "f".repeat(MAX_KYC_FILE_NAME_LENGTH),
+            sha256: "ab".repeat(32),
+            size: u64::MAX,
+            issuing_country: Some("DE".to_string()),
+            expires_at: Some(u64::MAX),
+        };
+        let submission = KycSubmission {
+            id: u64::MAX,
+            user: user(),
+            requested_level: VerificationLevel::Enhanced,
+            documents: vec![document; MAX_KYC_DOCUMENTS],
+            status: KycStatus::Rejected { reason: "r".repeat(MAX_KYC_REJECTION_REASON_LENGTH) },
+            reviewer: Some(reviewer()),
+            submitted_at: u64::MAX,
+            claimed_at: Some(u64::MAX),
+            reviewed_at: Some(u64::MAX),
+            expires_at: Some(u64::MAX),
+        };
+
+        let size = submission.to_bytes().len() as u32;
+        assert!(size <= KycSubmission::BOUND.max_size(), "{} bytes", size);
+    }
+}
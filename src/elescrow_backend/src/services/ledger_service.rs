@@ -0,0 +1,621 @@
+use candid::{Nat, Principal};
+use ic_cdk::call::Call;
+
+use crate::types::{
+    errors::ApiError,
+    transaction::{Balance, Currency},
+    ledger::*,
+    common::AuditAction,
+    treasury::TreasuryBalance,
+};
+use crate::storage::{
+    stable_storage::StableStorage,
+    memory::MemoryRegion,
+};
+use crate::utils::time::now;
+use crate::{AUDIT_LOGGER, BALANCE_SERVICE, TREASURY_SERVICE};
+
+/// The subset of ICRC-1/ICRC-2 the canister needs. `IcrcLedger` talks to a
+/// real ledger canister; tests substitute an in-memory ledger.
+pub trait Ledger {
+    async fn transfer(&self, arg: TransferArg) -> Result<Result<Nat, TransferError>, String>;
+    async fn transfer_from(&self, args: TransferFromArgs) -> Result<Result<Nat, TransferFromError>, String>;
+    async fn balance_of(&self, account: Account) -> Result<Nat, String>;
+}
+
+pub struct IcrcLedger {
+    canister_id: Principal,
+}
+
+impl IcrcLedger {
+    pub fn new(canister_id: Principal) -> Self {
+        Self { canister_id }
+    }
+}
+
+impl Ledger for IcrcLedger {
+    async fn transfer(&self, arg: TransferArg) -> Result<Result<Nat, TransferError>, String> {
+        Call::unbounded_wait(self.canister_id, "icrc1_transfer")
+            .with_arg(arg)
+            .await
+            .map_err(|e| e.to_string())?
+            .candid()
+            .map_err(|e| e.to_string())
+    }
+
+    async fn transfer_from(&self, args: TransferFromArgs) -> Result<Result<Nat, TransferFromError>, String> {
+        Call::unbounded_wait(self.canister_id, "icrc2_transfer_from")
+            .with_arg(args)
+            .await
+            .map_err(|e| e.to_string())?
+            .candid()
+            .map_err(|e| e.to_string())
+    }
+
+    async fn balance_of(&self, account: Account) -> Result<Nat, String> {
+        Call::unbounded_wait(self.canister_id, "icrc1_balance_of")
+            .with_arg(account)
+            .await
+            .map_err(|e| e.to_string())?
+            .candid()
+            .map_err(|e| e.to_string())
+    }
+}
+
+pub struct LedgerService {
+    configs: StableStorage<String, LedgerConfig>,
+}
+
+impl LedgerService {
+    pub fn new() -> Self {
+        Self {
+            configs: StableStorage::new(MemoryRegion::Ledgers),
+        }
+    }
+
+    pub fn set_config(&self, config: LedgerConfig, admin_principal: Principal) -> Result<(), ApiError> {
+        crate::security::validation::validate_currency(&config.currency)?;
+
+        self.configs.insert(config.currency.code(), config.clone());
+
+        AUDIT_LOGGER.with(|l| l.borrow().log(
+            admin_principal,
+            AuditAction::ConfigurationChanged,
+            &format!("ledger_{}", config.currency.code()),
+            Some(format!("Ledger: {}, fee: {}", config.ledger_canister, config.transfer_fee)),
+        ));
+
+        Ok(())
+    }
+
+    pub fn get_config(&self, currency: &Currency) -> Result<LedgerConfig, ApiError> {
+        self.configs.get(&currency.code()).ok_or_else(|| ApiError::ServiceUnavailable {
+            service: format!("{} ledger", currency.code()),
+        })
+    }
+
+    pub fn list_configs(&self) -> Vec<LedgerConfig> {
+        self.configs.values()
+    }
+}
+
+/// Per-user deposit subaccount of the canister: the owner's principal bytes,
+/// length-prefixed and zero-padded to 32 bytes.
+pub fn deposit_subaccount(owner: Principal) -> Subaccount {
+    let bytes = owner.as_slice();
+    let mut subaccount = vec![0u8; 32];
+    subaccount[0] = bytes.len() as u8;
+    subaccount[1..1 + bytes.len()].copy_from_slice(bytes);
+    subaccount
+}
+
+pub fn deposit_account(canister: Principal, owner: Principal) -> Account {
+    Account {
+        owner: canister,
+        subaccount: Some(deposit_subaccount(owner)),
+    }
+}
+
+/// ICRC-2 deposit: pulls `amount` from the depositor's approved allowance
+/// into the canister's main account. The ledger fee is paid by the depositor.
+pub async fn deposit_from_allowance<L: Ledger>(
+    ledger: &L,
+    config: &LedgerConfig,
+    canister: Principal,
+    depositor: Principal,
+    amount: u64,
+) -> Result<Balance, ApiError> {
+    BALANCE_SERVICE.with(|s| s.borrow_mut().begin_deposit(depositor, amount, &config.currency))?;
+
+    let args = TransferFromArgs {
+        spender_subaccount: None,
+        from: Account::new(depositor),
+        to: Account::new(canister),
+        amount: Nat::from(amount),
+        fee: None,
+        memo: None,
+        created_at_time: Some(now()),
+    };
+
+    let outcome = match ledger.transfer_from(args).await {
+        Ok(Ok(block)) => Ok(block),
+        Ok(Err(e)) => Err(transfer_from_error(e, amount)),
+        Err(e) => Err(ledger_unavailable(&config.currency, e)),
+    };
+
+    settle_deposit(config, depositor, amount, outcome)
+}
+
+/// Sweeps whatever the depositor sent to their deposit subaccount into the
+/// canister's main account and credits it, minus the sweep fee.
+pub async fn claim_subaccount_deposit<L: Ledger>(
+    ledger: &L,
+    config: &LedgerConfig,
+    canister: Principal,
+    depositor: Principal,
+) -> Result<Balance, ApiError> {
+    let source = deposit_account(canister, depositor);
+
+    let held = ledger.balance_of(source.clone()).await
+        .map_err(|e| ledger_unavailable(&config.currency, e))?;
+    let held = nat_to_u64(&held)?;
+
+    if held <= config.transfer_fee {
+        return Err(ApiError::NotFound {
+            resource: "Deposit".to_string(),
+        });
+    }
+
+    let amount = held - config.transfer_fee;
+    BALANCE_SERVICE.with(|s| s.borrow_mut().begin_deposit(depositor, amount, &config.currency))?;
+
+    let arg = TransferArg {
+        from_subaccount: source.subaccount,
+        to: Account::new(canister),
+        amount: Nat::from(amount),
+        fee: Some(Nat::from(config.transfer_fee)),
+        memo: None,
+        created_at_time: Some(now()),
+    };
+
+    let outcome = match ledger.transfer(arg).await {
+        Ok(Ok(block)) => Ok(block),
+        Ok(Err(e)) => Err(transfer_error(e, held)),
+        Err(e) => Err(ledger_unavailable(&config.currency, e)),
+    };
+
+    settle_deposit(config, depositor, amount, outcome)
+}
+
+/// ICRC-1 withdrawal: reserves `amount`, sends `amount - fee` to `to` and
+/// either settles or releases the reservation depending on the ledger reply.
+pub async fn withdraw<L: Ledger>(
+    ledger: &L,
+    config: &LedgerConfig,
+    owner: Principal,
+    amount: u64,
+    to: Account,
+) -> Result<Balance, ApiError> {
+    if amount <= config.transfer_fee {
+        return Err(ApiError::ValidationError {
+            field: "amount".to_string(),
+            message: format!("Amount must exceed the ledger fee of {}", config.transfer_fee),
+        });
+    }
+
+    BALANCE_SERVICE.with(|s| s.borrow_mut().begin_withdrawal(owner, amount, &config.currency))?;
+
+    let arg = TransferArg {
+        from_subaccount: None,
+        to,
+        amount: Nat::from(amount - config.transfer_fee),
+        fee: Some(Nat::from(config.transfer_fee)),
+        memo: None,
+        created_at_time: Some(now()),
+    };
+
+    let outcome = match ledger.transfer(arg).await {
+        Ok(Ok(block)) => nat_to_u64(&block),
+        Ok(Err(e)) => Err(transfer_error(e, amount)),
+        Err(e) => Err(ledger_unavailable(&config.currency, e)),
+    };
+
+    match outcome {
+        Ok(block) => {
+            let balance = BALANCE_SERVICE.with(|s| {
+                s.borrow_mut().complete_withdrawal(owner, amount, &config.currency, block)
+            });
+            AUDIT_LOGGER.with(|l| l.borrow().log(
+                owner,
+                AuditAction::Withdrawal,
+                &owner.to_text(),
+                Some(format!("Amount: {} {}, block: {}", amount, config.currency.code(), block)),
+            ));
+            Ok(balance)
+        }
+        Err(e) => {
+            BALANCE_SERVICE.with(|s| {
+                s.borrow_mut().fail_withdrawal(owner, amount, &config.currency, &e.to_string())
+            })?;
+            Err(e)
+        }
+    }
+}
+
+/// Pays collected platform fees out of the canister's main account. Like
+/// `withdraw`, the treasury reserves `amount` and the recipient receives
+/// `amount - fee`. Both outcomes are audited against `admin`.
+pub async fn withdraw_treasury<L: Ledger>(
+    ledger: &L,
+    config: &LedgerConfig,
+    admin: Principal,
+    amount: u64,
+    to: Account,
+) -> Result<TreasuryBalance, ApiError> {
+    if amount <= config.transfer_fee {
+        return Err(ApiError::ValidationError {
+            field: "amount".to_string(),
+            message: format!("Amount must exceed the ledger fee of {}", config.transfer_fee),
+        });
+    }
+
+    TREASURY_SERVICE.with(|s| s.borrow().begin_withdrawal(&config.currency, amount))?;
+
+    let arg = TransferArg {
+        from_subaccount: None,
+        to: to.clone(),
+        amount: Nat::from(amount - config.transfer_fee),
+        fee: Some(Nat::from(config.transfer_fee)),
+        memo: None,
+        created_at_time: Some(now()),
+    };
+
+    let outcome = match ledger.transfer(arg).await {
+        Ok(Ok(block)) => nat_to_u64(&block),
+        Ok(Err(e)) => Err(transfer_error(e, amount)),
+        Err(e) => Err(ledger_unavailable(&config.currency, e)),
+    };
+
+    match outcome {
+        Ok(block) => {
+            let balance = TREASURY_SERVICE.with(|s| {
+                s.borrow().complete_withdrawal(admin, &config.currency, amount, to.clone(), block)
+            });
+            AUDIT_LOGGER.with(|l| l.borrow().log(
+                admin,
+                AuditAction::TreasuryWithdrawal,
+                &format!("treasury_{}", config.currency.code()),
+                Some(format!("Amount: {} to {}, block: {}", amount, to.owner, block)),
+            ));
+            Ok(balance)
+        }
+        Err(e) => {
+            TREASURY_SERVICE.with(|s| s.borrow().fail_withdrawal(&config.currency, amount));
+            AUDIT_LOGGER.with(|l| l.borrow().log(
+                admin,
+                AuditAction::TreasuryWithdrawal,
+                &format!("treasury_{}", config.currency.code()),
+                Some(format!("Failed: {} to {}: {}", amount, to.owner, e.to_string())),
+            ));
+            Err(e)
+        }
+    }
+}
+
+fn settle_deposit(
+    config: &LedgerConfig,
+    depositor: Principal,
+    amount: u64,
+    outcome: Result<Nat, ApiError>,
+) -> Result<Balance, ApiError> {
+    match outcome.and_then(|block| nat_to_u64(&block)) {
+        Ok(block) => {
+            let balance = BALANCE_SERVICE.with(|s| {
+                s.borrow_mut().complete_deposit(depositor, amount, &config.currency, block)
+            })?;
+            AUDIT_LOGGER.with(|l| l.borrow().log(
+                depositor,
+                AuditAction::Deposit,
+                &depositor.to_text(),
+                Some(format!("Amount: {} {}, block: {}", amount, config.currency.code(), block)),
+            ));
+            Ok(balance)
+        }
+        Err(e) => {
+            BALANCE_SERVICE.with(|s| {
+                s.borrow_mut().fail_deposit(depositor, amount, &config.currency, &e.to_string())
+            });
+            Err(e)
+        }
+    }
+}
+
+fn nat_to_u64(value: &Nat) -> Result<u64, ApiError> {
+    u64::try_from(value.0.clone()).map_err(|_| ApiError::InternalError {
+        details: format!("Ledger value {} does not fit in u64", value),
+    })
+}
+
+fn ledger_unavailable(currency: &Currency, reason: String) -> ApiError {
+    ic_cdk::println!("{} ledger call failed: {}", currency.code(), reason);
+    ApiError::ServiceUnavailable {
+        service: format!("{} ledger", currency.code()),
+    }
+}
+
+fn transfer_error(error: TransferError, required: u64) -> ApiError {
+    match error {
+        TransferError::InsufficientFunds { balance } => ApiError::InsufficientFunds {
+            available: nat_to_u64(&balance).unwrap_or(u64::MAX),
+            required,
+        },
+        TransferError::TemporarilyUnavailable => ApiError::ServiceUnavailable {
+            service: "Ledger".to_string(),
+        },
+        other => ApiError::BadRequest {
+            message: format!("Ledger rejected transfer: {:?}", other),
+        },
+    }
+}
+
+fn transfer_from_error(error: TransferFromError, required: u64) -> ApiError {
+    match error {
+        TransferFromError::InsufficientFunds { balance } => ApiError::InsufficientFunds {
+            available: nat_to_u64(&balance).unwrap_or(u64::MAX),
+            required,
+        },
+        TransferFromError::InsufficientAllowance { allowance } => ApiError::BadRequest {
+            message: format!("Insufficient ledger allowance: {}", allowance),
+        },
+        TransferFromError::TemporarilyUnavailable => ApiError::ServiceUnavailable {
+            service: "Ledger".to_string(),
+        },
+        other => ApiError::BadRequest {
+            message: format!("Ledger rejected transfer: {:?}", other),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+    use std::collections::HashMap;
+    use crate::types::{common::PaginationParams, transaction::BalanceEntryStatus, treasury::FeeSource};
+
+    const FEE: u64 = 10;
+
+    #[derive(Default)]
+    struct MockLedger {
+        balances: RefCell<HashMap<Account, u64>>,
+        allowances: RefCell<HashMap<Account, u64>>,
+        next_block: Cell<u64>,
+        unreachable: Cell<bool>,
+    }
+
+    impl MockLedger {
+        fn balance(&self, account: &Account) -> u64 {
+            self.balances.borrow().get(account).copied().unwrap_or(0)
+        }
+
+        fn mint(&self, account: Account, amount: u64) {
+            *self.balances.borrow_mut().entry(account).or_insert(0) += amount;
+        }
+
+        fn approve(&self, owner: Account, amount: u64) {
+            self.allowances.borrow_mut().insert(owner, amount);
+        }
+
+        fn move_funds(&self, from: &Account, to: Account, amount: u64) -> Result<Nat, u64> {
+            let held = self.balance(from);
+            if held < amount + FEE {
+                return Err(held);
+            }
+            self.balances.borrow_mut().insert(from.clone(), held - amount - FEE);
+            self.mint(to, amount);
+            self.next_block.set(self.next_block.get() + 1);
+            Ok(Nat::from(self.next_block.get()))
+        }
+    }
+
+    impl Ledger for MockLedger {
+        async fn transfer(&self, arg: TransferArg) -> Result<Result<Nat, TransferError>, String> {
+            if self.unreachable.get() {
+                return Err("ledger unreachable".to_string());
+            }
+            let from = Account { owner: canister(), subaccount: arg.from_subaccount };
+            let amount = nat_to_u64(&arg.amount).unwrap();
+            Ok(self.move_funds(&from, arg.to, amount)
+                .map_err(|held| TransferError::InsufficientFunds { balance: Nat::from(held) }))
+        }
+
+        async fn transfer_from(&self, args: TransferFromArgs) -> Result<Result<Nat, TransferFromError>, String> {
+            if self.unreachable.get() {
+                return Err("ledger unreachable".to_string());
+            }
+            let amount = nat_to_u64(&args.amount).unwrap();
+            let allowance = self.allowances.borrow().get(&args.from).copied().unwrap_or(0);
+            if allowance < amount + FEE {
+                return Ok(Err(TransferFromError::InsufficientAllowance { allowance: Nat::from(allowance) }));
+            }
+            self.allowances.borrow_mut().insert(args.from.clone(), allowance - amount - FEE);
+            Ok(self.move_funds(&args.from, args.to, amount)
+                .map_err(|held| TransferFromError::InsufficientFunds { balance: Nat::from(held) }))
+        }
+
+        async fn balance_of(&self, account: Account) -> Result<Nat, String> {
+            Ok(Nat::from(self.balance(&account)))
+        }
+    }
+
+    fn canister() -> Principal {
+        Principal::from_slice(&[9; 29])
+    }
+
+    fn user() -> Principal {
+        Principal::from_slice(&[1; 29])
+    }
+
+    fn config() -> LedgerConfig {
+        LedgerConfig {
+            currency: Currency::ICP,
+            ledger_canister: Principal::from_slice(&[7; 29]),
+            transfer_fee: FEE,
+        }
+    }
+
+    fn balance_of(owner: Principal) -> Balance {
+        BALANCE_SERVICE.with(|s| s.borrow_mut().get_balance(owner, &Currency::ICP)).unwrap()
+    }
+
+    fn last_status(owner: Principal) -> Option<BalanceEntryStatus> {
+        // The mock clock stands still, so entries are spread over the
+        // following nanoseconds; look past `now()`.
+        let history = BALANCE_SERVICE.with(|s| {
+            s.borrow().get_balance_history(owner, None, None, Some(u64::MAX), PaginationParams { offset: 0, limit: 100 })
+        }).unwrap();
+        history.last().and_then(|entry| entry.status.clone())
+    }
+
+    #[tokio::test]
+    async fn test_deposit_from_allowance_credits_balance() {
+        let ledger = MockLedger::default();
+        ledger.mint(Account::new(user()), 1_000);
+        ledger.approve(Account::new(user()), 1_000);
+
+        let balance = deposit_from_allowance(&ledger, &config(), canister(), user(), 500).await.unwrap();
+
+        assert_eq!(balance.available, 500);
+        assert_eq!(balance.pending_incoming, 0);
+        assert_eq!(ledger.balance(&Account::new(canister())), 500);
+        assert_eq!(ledger.balance(&Account::new(user())), 490);
+        assert_eq!(last_status(user()), Some(BalanceEntryStatus::Completed { ledger_block: Some(1) }));
+    }
+
+    #[tokio::test]
+    async fn test_deposit_without_allowance_is_not_credited() {
+        let ledger = MockLedger::default();
+        ledger.mint(Account::new(user()), 1_000);
+
+        let result = deposit_from_allowance(&ledger, &config(), canister(), user(), 500).await;
+
+        assert!(matches!(result, Err(ApiError::BadRequest { .. })));
+        let balance = balance_of(user());
+        assert_eq!(balance.available, 0);
+        assert_eq!(balance.pending_incoming, 0);
+        assert!(matches!(last_status(user()), Some(BalanceEntryStatus::Failed { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_claim_subaccount_deposit_sweeps_once() {
+        let ledger = MockLedger::default();
+        ledger.mint(deposit_account(canister(), user()), 300);
+
+        let balance = claim_subaccount_deposit(&ledger, &config(), canister(), user()).await.unwrap();
+        assert_eq!(balance.available, 290);
+        assert_eq!(ledger.balance(&Account::new(canister())), 290);
+
+        let again = claim_subaccount_deposit(&ledger, &config(), canister(), user()).await;
+        assert!(matches!(again, Err(ApiError::NotFound { .. })));
+        assert_eq!(balance_of(user()).available, 290);
+    }
+
+    #[tokio::test]
+    async fn test_withdraw_settles_after_ledger_transfer() {
+        let ledger = MockLedger::default();
+        ledger.mint(Account::new(user()), 1_000);
+        ledger.approve(Account::new(user()), 1_000);
+        deposit_from_allowance(&ledger, &config(), canister(), user(), 500).await.unwrap();
+
+        let recipient = Account::new(Principal::from_slice(&[2; 29]));
+        let balance = withdraw(&ledger, &config(), user(), 200, recipient.clone()).await.unwrap();
+
+        assert_eq!(balance.available, 300);
+        assert_eq!(balance.pending_outgoing, 0);
+        assert_eq!(ledger.balance(&recipient), 190);
+        assert_eq!(ledger.balance(&Account::new(canister())), 300);
+        assert_eq!(last_status(user()), Some(BalanceEntryStatus::Completed { ledger_block: Some(2) }));
+    }
+
+    #[tokio::test]
+    async fn test_failed_withdrawal_is_refunded() {
+        let ledger = MockLedger::default();
+        ledger.mint(Account::new(user()), 1_000);
+        ledger.approve(Account::new(user()), 1_000);
+        deposit_from_allowance(&ledger, &config(), canister(), user(), 500).await.unwrap();
+
+        ledger.unreachable.set(true);
+        let result = withdraw(&ledger, &config(), user(), 200, Account::new(user())).await;
+
+        assert!(matches!(result, Err(ApiError::ServiceUnavailable { .. })));
+        let balance = balance_of(user());
+        assert_eq!(balance.available, 500);
+        assert_eq!(balance.pending_outgoing, 0);
+        assert!(matches!(last_status(user()), Some(BalanceEntryStatus::Failed { .. })));
+    }
+
+    fn collect_fee(fee: u64) {
+        let transaction = crate::types::transaction::Transaction {
+            id: 1,
+            transaction_type: crate::types::transaction::TransactionType::DirectPayment,
+            from: user(),
+            to: Principal::from_slice(&[2; 29]),
+            amount: fee * 100,
+            fee,
+            currency: Currency::ICP,
+            description: String::new(),
+            status: crate::types::transaction::TransactionStatus::Completed,
+            escrow_agent: None,
+            created_at: 0,
+            updated_at: 0,
+            completed_at: Some(0),
+            deadline: None,
+            metadata: Default::default(),
+            agent_fee_bps: None,
+            agent_accepted_at: None,
+        };
+        TREASURY_SERVICE.with(|s| s.borrow().record_fee(&transaction.into(), FeeSource::Completion));
+    }
+
+    #[tokio::test]
+    async fn test_treasury_withdrawal_pays_out_collected_fees() {
+        let ledger = MockLedger::default();
+        ledger.mint(Account::new(canister()), 1_000);
+        collect_fee(500);
+
+        let admin = Account::new(Principal::from_slice(&[3; 29]));
+        let too_much = withdraw_treasury(&ledger, &config(), admin.owner, 501, admin.clone()).await;
+        assert!(matches!(too_much, Err(ApiError::InsufficientFunds { available: 500, .. })));
+
+        let balance = withdraw_treasury(&ledger, &config(), admin.owner, 300, admin.clone()).await.unwrap();
+        assert_eq!(balance.available, 200);
+        assert_eq!(balance.pending_withdrawal, 0);
+        assert_eq!(balance.total_withdrawn, 300);
+        assert_eq!(ledger.balance(&admin), 290);
+
+        let logs = AUDIT_LOGGER.with(|l| l.borrow().get_logs(PaginationParams { offset: 0, limit: 100 })).unwrap();
+        assert!(logs.iter().any(|log| matches!(log.action, AuditAction::TreasuryWithdrawal) && log.principal == admin.owner));
+    }
+
+    #[tokio::test]
+    async fn test_failed_treasury_withdrawal_is_restored() {
+        let ledger = MockLedger::default();
+        collect_fee(500);
+        ledger.unreachable.set(true);
+
+        let result = withdraw_treasury(&ledger, &config(), user(), 300, Account::new(user())).await;
+
+        assert!(matches!(result, Err(ApiError::ServiceUnavailable { .. })));
+        let balance = TREASURY_SERVICE.with(|s| s.borrow().get_balance(&Currency::ICP));
+        assert_eq!(balance.available, 500);
+        assert_eq!(balance.pending_withdrawal, 0);
+        assert_eq!(balance.total_withdrawn, 0);
+    }
+
+    #[tokio::test]
+    async fn test_withdraw_must_cover_ledger_fee() {
+        let ledger = MockLedger::default();
+        let result = withdraw(&ledger, &config(), user(), FEE, Account::new(user())).await;
+        assert!(matches!(result, Err(ApiError::ValidationError { .. })));
+    }
+}
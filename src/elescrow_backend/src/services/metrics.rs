@@ -0,0 +1,122 @@
+use std::fmt::Write;
+
+use crate::storage::memory::{utils::{memory_usage, region_size}, MemoryRegion};
+use crate::utils::time::now;
+use crate::{AUDIT_LOGGER, BALANCE_SERVICE, NOTIFICATION_SERVICE, TRANSACTION_SERVICE, TREASURY_SERVICE, USER_SERVICE};
+
+/// Renders the canister's metrics in the Prometheus text exposition format.
+/// Everything is computed on request; nothing is cached between scrapes.
+pub fn render() -> String {
+    let mut out = MetricsWriter::default();
+
+    let transactions = TRANSACTION_SERVICE.with(|s| s.borrow().get_statistics());
+    out.family("elescrow_transactions", "gauge", "Transactions by current status.");
+    for (status, count) in &transactions.transactions_by_status {
+        out.sample("elescrow_transactions", &[("status", status.label())], *count);
+    }
+    out.family("elescrow_transactions_by_type", "gauge", "Transactions by type.");
+    for (transaction_type, count) in &transactions.transactions_by_type {
+        out.sample("elescrow_transactions_by_type", &[("type", transaction_type.label())], *count);
+    }
+
+    let balances = BALANCE_SERVICE.with(|s| s.borrow().get_currency_statistics());
+    out.family("elescrow_balance_available", "gauge", "Available user funds per currency, in base units.");
+    for (currency, stats) in &balances {
+        out.sample("elescrow_balance_available", &[("currency", &currency.code())], stats.total_available);
+    }
+    out.family("elescrow_balance_locked", "gauge", "Funds locked in escrow per currency, in base units.");
+    for (currency, stats) in &balances {
+        out.sample("elescrow_balance_locked", &[("currency", &currency.code())], stats.total_locked);
+    }
+
+    let fees = TREASURY_SERVICE.with(|s| s.borrow().get_collected_fees());
+    out.family("elescrow_fees_collected", "counter", "Platform fees collected into the treasury per currency, in base units.");
+    for (currency, total) in &fees {
+        out.sample("elescrow_fees_collected", &[("currency", &currency.code())], *total);
+    }
+
+    let users = USER_SERVICE.with(|s| s.borrow().get_user_statistics());
+    out.family("elescrow_users", "gauge", "Registered users.");
+    out.sample("elescrow_users", &[], users.total_users);
+    out.family("elescrow_users_active_24h", "gauge", "Users active in the last 24 hours.");
+    out.sample("elescrow_users_active_24h", &[], users.active_users_24h);
+    out.family("elescrow_users_frozen", "gauge", "Frozen accounts.");
+    out.sample("elescrow_users_frozen", &[], users.frozen_accounts);
+
+    out.family("elescrow_notifications_unread", "gauge", "Unread, unarchived notifications across all users.");
+    out.sample("elescrow_notifications_unread", &[], NOTIFICATION_SERVICE.with(|s| s.borrow().get_backlog_count()));
+
+    out.family("elescrow_messages", "gauge", "Stored chat messages.");
+    out.sample("elescrow_messages", &[], crate::messaging::get_message_count());
+    out.family("elescrow_websocket_clients", "gauge", "Users with an open websocket connection.");
+    out.sample("elescrow_websocket_clients", &[], crate::services::events::online_user_count());
+
+    let audit = AUDIT_LOGGER.with(|l| l.borrow().get_statistics());
+    out.family("elescrow_audit_log_entries", "gauge", "Stored audit log entries.");
+    out.sample("elescrow_audit_log_entries", &[], audit.total_entries);
+
+    out.family("elescrow_stable_memory_bytes", "gauge", "Stable memory used by each region.");
+    for region in MemoryRegion::ALL {
+        out.sample("elescrow_stable_memory_bytes", &[("region", &format!("{:?}", region))], region_size(region));
+    }
+    let memory = memory_usage();
+    out.family("elescrow_heap_memory_bytes", "gauge", "Wasm heap size.");
+    out.sample("elescrow_heap_memory_bytes", &[], memory.heap_size);
+
+    out.family("elescrow_uptime_seconds", "gauge", "Seconds since the last install or upgrade.");
+    out.sample("elescrow_uptime_seconds", &[], now().saturating_sub(crate::init_timestamp()) / 1_000_000_000);
+
+    out.0
+}
+
+#[derive(Default)]
+struct MetricsWriter(String);
+
+impl MetricsWriter {
+    fn family(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.0, "# HELP {} {}", name, help);
+        let _ = writeln!(self.0, "# TYPE {} {}", name, kind);
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: u64) {
+        if labels.is_empty() {
+            let _ = writeln!(self.0, "{} {}", name, value);
+            return;
+        }
+
+        let labels: Vec<String> = labels
+            .iter()
+            .map(|(key, value)| format!("{}=\"{}\"", key, escape_label(value)))
+            .collect();
+        let _ = writeln!(self.0, "{}{{{}}} {}", name, labels.join(","), value);
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::transaction::Currency;
+
+    #[test]
+    fn test_render_exposition_format() {
+        BALANCE_SERVICE.with(|s| {
+            s.borrow_mut().credit_funds(candid::Principal::from_slice(&[1; 29]), 5_000, &Currency::ICP, 0, "Test funds")
+        }).unwrap();
+
+        let text = render();
+        assert!(text.contains("# TYPE elescrow_balance_available gauge\n"));
+        assert!(text.contains("elescrow_balance_available{currency=\"ICP\"} 5000\n"));
+        assert!(text.contains("elescrow_stable_memory_bytes{region=\"Transactions\"} "));
+        assert!(text.contains("elescrow_websocket_clients 0\n"));
+        assert!(text.lines().all(|line| line.starts_with('#') || line.split(' ').count() == 2));
+    }
+
+    #[test]
+    fn test_label_escaping() {
+        assert_eq!(escape_label("CUSTOM:\"X\"\n"), "CUSTOM:\\\"X\\\"\\n");
+    }
+}
@@ -1,4 +1,15 @@
 pub mod user_service;
 pub mod transaction_service;
 pub mod notification_service;
-pub mod balance_service;
\ No newline at end of file
+pub mod balance_service;
+pub mod ledger_service;
+pub mod scheduler;
+pub mod role_service;
+pub mod evidence_service;
+pub mod metrics;
+pub mod kyc_service;
+pub mod referral_service;
+pub mod treasury_service;
+pub mod fee_service;
+pub mod contact_service;
+pub mod events;
\ No newline at end of file
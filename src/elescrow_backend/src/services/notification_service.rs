@@ -1,24 +1,25 @@
 use candid::Principal;
-use ic_cdk::api::time;
-use std::cell::RefCell;
+use crate::utils::time::now;
 
 use crate::types::{
     errors::ApiError,
     notification::*,
     common::{PaginationParams, ListResponse},
+    messaging::WsEvent,
 };
 
 use crate::models::notification::NotificationModel;
+use crate::services::events::send_ws_event;
 use crate::storage::{
-    stable_storage::{StableStorage, IndexedStorage},
+    stable_storage::StableStorage,
     memory::MemoryRegion,
+    config::{StableCounter, NOTIFICATION_ID_COUNTER},
 };
 
 pub struct NotificationService {
     notifications: StableStorage<u64, NotificationModel>,
-    user_notifications: IndexedStorage<u64, NotificationModel, Principal>,
     
-    next_id: RefCell<u64>,
+    next_id: StableCounter,
     
     max_notifications_per_user: usize,
     default_expiry: u64,
@@ -28,11 +29,7 @@ impl NotificationService {
     pub fn new() -> Self {
         Self {
             notifications: StableStorage::new(MemoryRegion::Notifications),
-            user_notifications: IndexedStorage::new(
-                MemoryRegion::Notifications,
-                MemoryRegion::NotificationIndex,
-            ),
-            next_id: RefCell::new(1),
+            next_id: StableCounter::new(NOTIFICATION_ID_COUNTER),
             max_notifications_per_user: 1000,
             default_expiry: 30 * 24 * 60 * 60 * 1_000_000_000, 
         }
@@ -54,7 +51,7 @@ impl NotificationService {
         }
         
         let id = self.get_next_id();
-        let now = time();
+        let now = now();
         
         let notification_model = NotificationModel {
             id,
@@ -75,9 +72,11 @@ impl NotificationService {
         };
         
         self.notifications.insert(id, notification_model.clone());
-        self.user_notifications.insert_indexed(id, notification_model.clone(), recipient);
-        
-        Ok(notification_model.into())
+
+        let notification: Notification = notification_model.into();
+        send_ws_event(recipient, WsEvent::NewNotification(notification.clone()));
+
+        Ok(notification)
     }
     
     pub fn create_transaction_notification(
@@ -107,6 +106,33 @@ impl NotificationService {
         )
     }
     
+    pub fn create_escrow_expiring_notification(
+        &self,
+        recipient: Principal,
+        transaction_id: u64,
+        message: &str,
+    ) -> Result<Notification, ApiError> {
+        self.create(
+            recipient,
+            NotificationType::EscrowExpiring,
+            "Escrow Expiring".to_string(),
+            message.to_string(),
+            NotificationPriority::Urgent,
+            Some(RelatedResource::Transaction(transaction_id)),
+            vec![
+                NotificationAction {
+                    id: "view".to_string(),
+                    label: "View Transaction".to_string(),
+                    action_type: ActionType::Navigate {
+                        url: format!("/transactions/{}", transaction_id),
+                    },
+                    style: ActionStyle::Primary,
+                    confirmation_required: false,
+                },
+            ],
+        )
+    }
+    
     pub fn get_user_notifications(
         &self,
         user: Principal,
@@ -142,7 +168,7 @@ impl NotificationService {
                 }
                 
                 if let Some(expires_at) = n.expires_at {
-                    if time() > expires_at {
+                    if now() > expires_at {
                         return false;
                     }
                 }
@@ -190,9 +216,9 @@ impl NotificationService {
         }
         
         notification.is_read = true;
-        notification.read_at = Some(time());
+        notification.read_at = Some(now());
         notification.delivery_status.in_app = DeliveryState::Delivered {
-            delivered_at: time(),
+            delivered_at: now(),
         };
         
         self.notifications.insert(id, notification.clone());
@@ -207,7 +233,7 @@ impl NotificationService {
             .collect();
         
         let count = unread_notifications.len() as u64;
-        let now = time();
+        let now = now();
         
         for (id, mut notification) in unread_notifications {
             notification.is_read = true;
@@ -243,7 +269,6 @@ impl NotificationService {
         }
         
         self.notifications.remove(&id);
-        self.user_notifications.remove_by_index(&user);
         
         Ok(())
     }
@@ -254,8 +279,15 @@ impl NotificationService {
             .len() as u64
     }
     
+    /// Unread notifications across all users.
+    pub fn get_backlog_count(&self) -> u64 {
+        self.notifications
+            .filter(|_, n| !n.is_read && !n.is_archived)
+            .len() as u64
+    }
+    
     pub fn cleanup_expired(&self) -> u64 {
-        let now = time();
+        let now = now();
         let expired: Vec<u64> = self.notifications
             .filter(|_, n| {
                 if let Some(expires_at) = n.expires_at {
@@ -277,10 +309,11 @@ impl NotificationService {
     }
     
     fn get_next_id(&self) -> u64 {
-        let mut id = self.next_id.borrow_mut();
-        let current = *id;
-        *id += 1;
-        current
+        self.next_id.next()
+    }
+
+    pub fn restore_next_id(&self) {
+        self.next_id.ensure_after(self.notifications.last_key());
     }
     
     pub fn get_notification_model(&self, id: u64) -> Result<NotificationModel, ApiError> {
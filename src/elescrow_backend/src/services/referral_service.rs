@@ -0,0 +1,339 @@
+use candid::Principal;
+use sha2::{Digest, Sha256};
+
+use crate::types::{
+    errors::ApiError,
+    common::AuditAction,
+    referral::*,
+    transaction::Currency,
+};
+use crate::models::transaction::TransactionModel;
+use crate::storage::{
+    stable_storage::StableStorage,
+    memory::MemoryRegion,
+    config::{self, REFERRAL_FEE_SHARE_BPS, REFERRAL_REWARDED_TRANSACTIONS},
+};
+use crate::utils::constants::{
+    DEFAULT_REFERRAL_FEE_SHARE_BPS, DEFAULT_REFERRAL_REWARDED_TRANSACTIONS, MAX_REFERRAL_CODES_PER_USER,
+    REFERRAL_CODE_LENGTH,
+};
+use crate::services::fee_service::apply_bps;
+use crate::utils::time::now;
+use crate::{AUDIT_LOGGER, BALANCE_SERVICE, NOTIFICATION_SERVICE, TREASURY_SERVICE};
+
+/// Referral codes and the rewards they earn. A referrer receives a share of
+/// the platform fee on each of the referee's first few completed
+/// transactions, paid out of the treasury to their available balance.
+pub struct ReferralService {
+    codes: StableStorage<String, ReferralCode>,
+    referrals: StableStorage<Principal, Referral>,
+}
+
+impl ReferralService {
+    pub fn new() -> Self {
+        Self {
+            codes: StableStorage::new(MemoryRegion::ReferralCodes),
+            referrals: StableStorage::new(MemoryRegion::Referrals),
+        }
+    }
+
+    pub fn generate_code(&self, owner: Principal) -> Result<ReferralCode, ApiError> {
+        if self.codes_of(owner).len() >= MAX_REFERRAL_CODES_PER_USER {
+            return Err(ApiError::ValidationError {
+                field: "referral_code".to_string(),
+                message: format!("A user can have at most {} referral codes", MAX_REFERRAL_CODES_PER_USER),
+            });
+        }
+
+        let created_at = now();
+        let mut nonce = self.codes.len();
+        let code = loop {
+            let code = derive_code(owner, created_at, nonce);
+            if !self.codes.contains_key(&code) {
+                break code;
+            }
+            nonce += 1;
+        };
+
+        let referral_code = ReferralCode {
+            code: code.clone(),
+            owner,
+            created_at,
+            uses: 0,
+        };
+        self.codes.insert(code, referral_code.clone());
+
+        Ok(referral_code)
+    }
+
+    /// Looks up the owner of `code` for a new registration by `referee`.
+    pub fn resolve_code(&self, code: &str, referee: Principal) -> Result<ReferralCode, ApiError> {
+        let referral_code = self.codes
+            .get(&code.trim().to_uppercase())
+            .ok_or_else(|| ApiError::ValidationError {
+                field: "referral_code".to_string(),
+                message: "Unknown referral code".to_string(),
+            })?;
+
+        if referral_code.owner == referee {
+            return Err(ApiError::ValidationError {
+                field: "referral_code".to_string(),
+                message: "Cannot use your own referral code".to_string(),
+            });
+        }
+
+        Ok(referral_code)
+    }
+
+    /// Records that `referee` registered with `code`, which must have been
+    /// checked with `resolve_code`.
+    pub fn record_referral(&self, referee: Principal, mut code: ReferralCode) {
+        code.uses += 1;
+        self.codes.insert(code.code.clone(), code.clone());
+
+        self.referrals.insert(referee, Referral {
+            referee,
+            referrer: code.owner,
+            code: code.code,
+            registered_at: now(),
+            rewarded_transactions: 0,
+            rewards: vec![],
+        });
+    }
+
+    /// Pays the referrer of `transaction.from` their share of its fee, while
+    /// the referee is still within their rewarded transactions. Returns the
+    /// amount credited.
+    pub fn reward_completed_transaction(&self, transaction: &TransactionModel) -> u64 {
+        let Some(mut referral) = self.referrals.get(&transaction.from) else {
+            return 0;
+        };
+
+        let config = self.get_config();
+        if referral.rewarded_transactions >= config.rewarded_transactions {
+            return 0;
+        }
+
+        let reward = apply_bps(transaction.fee, config.fee_share_bps);
+        if reward == 0 {
+            return 0;
+        }
+
+        // The fee is already in the treasury; the reward comes out of it.
+        let treasury = TREASURY_SERVICE.with(|s| s.borrow().get_balance(&transaction.currency));
+        if treasury.available < reward {
+            return 0;
+        }
+
+        let credited = BALANCE_SERVICE.with(|s| {
+            s.borrow_mut().credit_funds(
+                referral.referrer,
+                reward,
+                &transaction.currency,
+                transaction.id,
+                "Referral reward",
+            )
+        });
+        if credited.is_err() {
+            return 0;
+        }
+        let _ = TREASURY_SERVICE.with(|s| {
+            s.borrow().record_referral_reward(transaction, referral.referrer, reward)
+        });
+
+        referral.rewarded_transactions += 1;
+        add_to(&mut referral.rewards, &transaction.currency, reward);
+        self.referrals.insert(transaction.from, referral.clone());
+
+        AUDIT_LOGGER.with(|l| l.borrow().log(
+            referral.referrer,
+            AuditAction::ReferralRewarded,
+            &format!("transaction_{}", transaction.id),
+            Some(format!("Referral reward: {} {}", reward, transaction.currency.code())),
+        ));
+
+        let _ = NOTIFICATION_SERVICE.with(|s| {
+            s.borrow().create_transaction_notification(
+                referral.referrer,
+                transaction.id,
+                &format!("Referral reward received: {} {}", reward, transaction.currency.code()),
+            )
+        });
+
+        reward
+    }
+
+    pub fn get_stats(&self, user: Principal) -> ReferralStats {
+        let referred: Vec<Referral> = self.referrals
+            .filter(|_, r| r.referrer == user)
+            .into_iter()
+            .map(|(_, r)| r)
+            .collect();
+
+        let mut earnings = vec![];
+        for referral in &referred {
+            for (currency, amount) in &referral.rewards {
+                add_to(&mut earnings, currency, *amount);
+            }
+        }
+
+        ReferralStats {
+            codes: self.codes_of(user),
+            referred_users: referred.len() as u64,
+            rewarded_transactions: referred.iter().map(|r| r.rewarded_transactions as u64).sum(),
+            earnings,
+            referred_by: self.referrals.get(&user),
+        }
+    }
+
+    pub fn get_config(&self) -> ReferralConfig {
+        ReferralConfig {
+            fee_share_bps: config::get_or(REFERRAL_FEE_SHARE_BPS, DEFAULT_REFERRAL_FEE_SHARE_BPS),
+            rewarded_transactions: config::get_or(
+                REFERRAL_REWARDED_TRANSACTIONS,
+                DEFAULT_REFERRAL_REWARDED_TRANSACTIONS as u64,
+            ) as u32,
+        }
+    }
+
+    pub fn set_config(&self, admin: Principal, referral_config: ReferralConfig) -> Result<(), ApiError> {
+        if referral_config.fee_share_bps > 10_000 {
+            return Err(ApiError::ValidationError {
+                field: "fee_share_bps".to_string(),
+                message: "Fee share cannot exceed 10000 bps (100%)".to_string(),
+            });
+        }
+
+        config::set(REFERRAL_FEE_SHARE_BPS, referral_config.fee_share_bps);
+        config::set(REFERRAL_REWARDED_TRANSACTIONS, referral_config.rewarded_transactions as u64);
+
+        AUDIT_LOGGER.with(|l| l.borrow().log(
+            admin,
+            AuditAction::ConfigurationChanged,
+            "referral_program",
+            Some(format!("{:?}", referral_config)),
+        ));
+
+        Ok(())
+    }
+
+    fn codes_of(&self, owner: Principal) -> Vec<ReferralCode> {
+        self.codes
+            .filter(|_, c| c.owner == owner)
+            .into_iter()
+            .map(|(_, c)| c)
+            .collect()
+    }
+}
+
+fn derive_code(owner: Principal, created_at: u64, nonce: u64) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(owner.as_slice());
+    hasher.update(created_at.to_be_bytes());
+    hasher.update(nonce.to_be_bytes());
+
+    hex::encode(hasher.finalize())[..REFERRAL_CODE_LENGTH].to_uppercase()
+}
+
+fn add_to(totals: &mut Vec<(Currency, u64)>, currency: &Currency, amount: u64) {
+    match totals.iter_mut().find(|(c, _)| c == currency) {
+        Some((_, total)) => *total = total.saturating_add(amount),
+        None => totals.push((currency.clone(), amount)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::transaction::{Transaction, TransactionMetadata, TransactionStatus, TransactionType};
+    use crate::types::treasury::FeeSource;
+    use crate::types::user::RegisterUserRequest;
+    use crate::USER_SERVICE;
+
+    fn referrer() -> Principal {
+        Principal::from_slice(&[1; 29])
+    }
+
+    fn referee() -> Principal {
+        Principal::from_slice(&[2; 29])
+    }
+
+    fn register(principal: Principal, username: &str, referral_code: Option<String>) -> Result<(), ApiError> {
+        USER_SERVICE.with(|s| {
+            s.borrow().register(principal, RegisterUserRequest {
+                username: username.to_string(),
+                email: None,
+                display_name: None,
+                referral_code,
+            })
+        }).map(|_| ())
+    }
+
+    fn completed(id: u64, fee: u64) -> TransactionModel {
+        Transaction {
+            id,
+            transaction_type: TransactionType::DirectPayment,
+            from: referee(),
+            to: referrer(),
+            amount: fee * 100,
+            fee,
+            currency: Currency::ICP,
+            description: String::new(),
+            status: TransactionStatus::Completed,
+            escrow_agent: None,
+            created_at: 0,
+            updated_at: 0,
+            completed_at: Some(0),
+            deadline: None,
+            metadata: TransactionMetadata::default(),
+            agent_fee_bps: None,
+            agent_accepted_at: None,
+        }.into()
+    }
+
+    #[test]
+    fn test_registration_records_referrer() {
+        register(referrer(), "referrer", None).unwrap();
+        let code = crate::REFERRAL_SERVICE.with(|s| s.borrow().generate_code(referrer())).unwrap();
+        assert_eq!(code.code.len(), REFERRAL_CODE_LENGTH);
+
+        assert!(register(referee(), "referee", Some("NOPE1234".to_string())).is_err());
+        assert!(USER_SERVICE.with(|s| s.borrow().get_account(referee())).is_none());
+
+        register(referee(), "referee", Some(code.code.to_lowercase())).unwrap();
+        let stats = crate::REFERRAL_SERVICE.with(|s| s.borrow().get_stats(referrer()));
+        assert_eq!(stats.referred_users, 1);
+        assert_eq!(stats.codes[0].uses, 1);
+
+        let referred_by = crate::REFERRAL_SERVICE.with(|s| s.borrow().get_stats(referee())).referred_by.unwrap();
+        assert_eq!(referred_by.referrer, referrer());
+    }
+
+    #[test]
+    fn test_rewards_stop_after_configured_transactions() {
+        let service = ReferralService::new();
+        let code = service.generate_code(referrer()).unwrap();
+        assert!(matches!(service.resolve_code(&code.code, referrer()), Err(ApiError::ValidationError { .. })));
+        service.record_referral(referee(), service.resolve_code(&code.code, referee()).unwrap());
+        service.set_config(referrer(), ReferralConfig { fee_share_bps: 2_500, rewarded_transactions: 2 }).unwrap();
+
+        let settle = |id, fee| {
+            let transaction = completed(id, fee);
+            TREASURY_SERVICE.with(|s| s.borrow().record_fee(&transaction, FeeSource::Completion));
+            service.reward_completed_transaction(&transaction)
+        };
+        assert_eq!(settle(1, 1_000), 250);
+        assert_eq!(settle(2, 400), 100);
+        assert_eq!(settle(3, 1_000), 0);
+
+        let balance = BALANCE_SERVICE.with(|s| s.borrow_mut().get_balance(referrer(), &Currency::ICP)).unwrap();
+        assert_eq!(balance.available, 350);
+
+        let stats = service.get_stats(referrer());
+        assert_eq!(stats.rewarded_transactions, 2);
+        assert_eq!(stats.earnings, vec![(Currency::ICP, 350)]);
+
+        let treasury = TREASURY_SERVICE.with(|s| s.borrow().get_balance(&Currency::ICP));
+        assert_eq!(treasury.available, 2_400 - 350);
+    }
+}
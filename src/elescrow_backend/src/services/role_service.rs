@@ -0,0 +1,204 @@
+use candid::Principal;
+
+use crate::types::{
+    errors::ApiError,
+    role::*,
+    common::AuditAction,
+};
+use crate::storage::{
+    stable_storage::StableStorage,
+    memory::MemoryRegion,
+};
+use crate::utils::time::now;
+use crate::AUDIT_LOGGER;
+
+pub struct RoleService {
+    assignments: StableStorage<Principal, RoleAssignment>,
+}
+
+impl RoleService {
+    pub fn new() -> Self {
+        Self {
+            assignments: StableStorage::new(MemoryRegion::Roles),
+        }
+    }
+
+    /// Makes `principal` a SuperAdmin. Called from `init`/`post_upgrade`;
+    /// without an explicit principal it only acts when no SuperAdmin exists,
+    /// so an upgrade never takes the role away from anyone.
+    pub fn bootstrap_super_admin(&self, principal: Option<Principal>, installer: Principal) {
+        let target = match principal {
+            Some(principal) => principal,
+            None if self.has_super_admin() || installer == Principal::anonymous() => return,
+            None => installer,
+        };
+
+        if !self.get_roles(target).contains(&Role::SuperAdmin) {
+            self.store_role(target, Role::SuperAdmin, installer);
+            AUDIT_LOGGER.with(|l| l.borrow().log(
+                installer,
+                AuditAction::RoleGranted,
+                &target.to_text(),
+                Some("SuperAdmin (bootstrap)".to_string()),
+            ));
+        }
+    }
+
+    pub fn grant_role(&self, granter: Principal, principal: Principal, role: Role) -> Result<RoleAssignment, ApiError> {
+        self.ensure_permission(granter, Permission::ManageRoles)?;
+        crate::security::validation::validate_principal(&principal)?;
+
+        if self.get_roles(principal).contains(&role) {
+            return Err(ApiError::AlreadyExists {
+                resource: format!("Role {:?} for {}", role, principal.to_text()),
+            });
+        }
+
+        let assignment = self.store_role(principal, role, granter);
+
+        AUDIT_LOGGER.with(|l| l.borrow().log(
+            granter,
+            AuditAction::RoleGranted,
+            &principal.to_text(),
+            Some(format!("{:?}", role)),
+        ));
+
+        Ok(assignment)
+    }
+
+    pub fn revoke_role(&self, revoker: Principal, principal: Principal, role: Role) -> Result<(), ApiError> {
+        self.ensure_permission(revoker, Permission::ManageRoles)?;
+
+        let mut assignment = self.assignments.get(&principal)
+            .filter(|a| a.roles.contains(&role))
+            .ok_or_else(|| ApiError::NotFound {
+                resource: format!("Role {:?} for {}", role, principal.to_text()),
+            })?;
+
+        if role == Role::SuperAdmin && self.count_with_role(Role::SuperAdmin) <= 1 {
+            return Err(ApiError::BadRequest {
+                message: "Cannot revoke the last SuperAdmin".to_string(),
+            });
+        }
+
+        assignment.roles.retain(|r| *r != role);
+        if assignment.roles.is_empty() {
+            self.assignments.remove(&principal);
+        } else {
+            assignment.updated_by = revoker;
+            assignment.updated_at = now();
+            self.assignments.insert(principal, assignment);
+        }
+
+        AUDIT_LOGGER.with(|l| l.borrow().log(
+            revoker,
+            AuditAction::RoleRevoked,
+            &principal.to_text(),
+            Some(format!("{:?}", role)),
+        ));
+
+        Ok(())
+    }
+
+    pub fn get_roles(&self, principal: Principal) -> Vec<Role> {
+        self.assignments.get(&principal).map(|a| a.roles).unwrap_or_default()
+    }
+
+    pub fn list_assignments(&self) -> Vec<RoleAssignment> {
+        self.assignments.values()
+    }
+
+    pub fn has_permission(&self, principal: Principal, permission: Permission) -> bool {
+        self.get_roles(principal).iter().any(|role| role.has_permission(permission))
+    }
+
+    pub fn ensure_permission(&self, principal: Principal, permission: Permission) -> Result<(), ApiError> {
+        if !self.has_permission(principal, permission) {
+            return Err(ApiError::Unauthorized {
+                reason: format!("Permission {:?} required", permission),
+            });
+        }
+
+        Ok(())
+    }
+
+    fn has_super_admin(&self) -> bool {
+        self.count_with_role(Role::SuperAdmin) > 0
+    }
+
+    fn count_with_role(&self, role: Role) -> usize {
+        self.assignments.values().iter().filter(|a| a.roles.contains(&role)).count()
+    }
+
+    fn store_role(&self, principal: Principal, role: Role, granted_by: Principal) -> RoleAssignment {
+        let mut assignment = self.assignments.get(&principal).unwrap_or(RoleAssignment {
+            principal,
+            roles: vec![],
+            updated_by: granted_by,
+            updated_at: 0,
+        });
+
+        assignment.roles.push(role);
+        assignment.updated_by = granted_by;
+        assignment.updated_at = now();
+        self.assignments.insert(principal, assignment.clone());
+
+        assignment
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn principal(n: u8) -> Principal {
+        Principal::from_slice(&[n; 29])
+    }
+
+    #[test]
+    fn test_bootstrap_only_without_super_admin() {
+        let service = RoleService::new();
+        service.bootstrap_super_admin(None, Principal::anonymous());
+        assert!(service.list_assignments().is_empty());
+
+        service.bootstrap_super_admin(None, principal(1));
+        service.bootstrap_super_admin(None, principal(2));
+        assert_eq!(service.get_roles(principal(1)), vec![Role::SuperAdmin]);
+        assert!(service.get_roles(principal(2)).is_empty());
+
+        service.bootstrap_super_admin(Some(principal(3)), principal(2));
+        assert_eq!(service.get_roles(principal(3)), vec![Role::SuperAdmin]);
+    }
+
+    #[test]
+    fn test_grant_and_revoke_require_manage_roles() {
+        let service = RoleService::new();
+        service.bootstrap_super_admin(Some(principal(1)), principal(1));
+
+        service.grant_role(principal(1), principal(2), Role::Admin).unwrap();
+        assert!(service.has_permission(principal(2), Permission::ResolveDisputes));
+        assert!(!service.has_permission(principal(2), Permission::ManageRoles));
+
+        let denied = service.grant_role(principal(2), principal(3), Role::Support);
+        assert!(matches!(denied, Err(ApiError::Unauthorized { .. })));
+
+        service.grant_role(principal(1), principal(2), Role::Auditor).unwrap();
+        service.revoke_role(principal(1), principal(2), Role::Admin).unwrap();
+        assert_eq!(service.get_roles(principal(2)), vec![Role::Auditor]);
+        assert!(!service.has_permission(principal(2), Permission::ResolveDisputes));
+        assert!(service.has_permission(principal(2), Permission::ViewAuditLogs));
+    }
+
+    #[test]
+    fn test_last_super_admin_cannot_be_revoked() {
+        let service = RoleService::new();
+        service.bootstrap_super_admin(Some(principal(1)), principal(1));
+
+        let result = service.revoke_role(principal(1), principal(1), Role::SuperAdmin);
+        assert!(matches!(result, Err(ApiError::BadRequest { .. })));
+
+        service.grant_role(principal(1), principal(2), Role::SuperAdmin).unwrap();
+        service.revoke_role(principal(2), principal(1), Role::SuperAdmin).unwrap();
+        assert!(service.get_roles(principal(1)).is_empty());
+    }
+}
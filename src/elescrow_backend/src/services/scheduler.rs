@@ -0,0 +1,95 @@
+use std::time::Duration;
+
+use crate::utils::constants::{
+    ESCROW_SWEEP_INTERVAL, ESCROW_EXPIRY_WARNING, SCHEDULED_PAYMENT_SWEEP_INTERVAL,
+    RATE_LIMIT_CLEANUP_INTERVAL, KYC_EXPIRY_SWEEP_INTERVAL, EVIDENCE_CLEANUP_INTERVAL,
+};
+use crate::{EVIDENCE_SERVICE, KYC_SERVICE, RATE_LIMITER, SYSTEM_STATE, TRANSACTION_SERVICE};
+
+/// Arms the canister's periodic jobs. Timers live on the heap and do not
+/// survive an upgrade, so this must run from both `init` and `post_upgrade`.
+pub fn start() {
+    ic_cdk_timers::set_timer_interval(Duration::from_nanos(ESCROW_SWEEP_INTERVAL), run_escrow_sweep);
+    run_backfill(backfill_escrow_deadlines);
+    run_backfill(backfill_payment_schedules);
+    run_backfill(crate::messaging::backfill_user_conversations);
+    ic_cdk_timers::set_timer_interval(
+        Duration::from_nanos(SCHEDULED_PAYMENT_SWEEP_INTERVAL),
+        run_scheduled_payments,
+    );
+    ic_cdk_timers::set_timer_interval(Duration::from_nanos(RATE_LIMIT_CLEANUP_INTERVAL), run_rate_limit_cleanup);
+    ic_cdk_timers::set_timer_interval(Duration::from_nanos(KYC_EXPIRY_SWEEP_INTERVAL), run_kyc_expiry);
+    ic_cdk_timers::set_timer_interval(Duration::from_nanos(EVIDENCE_CLEANUP_INTERVAL), run_evidence_cleanup);
+}
+
+/// Runs `step` in its own message, again and again, until it reports the
+/// backfill is complete.
+fn run_backfill(step: fn() -> bool) {
+    ic_cdk_timers::set_timer(Duration::ZERO, move || {
+        if !step() {
+            run_backfill(step);
+        }
+    });
+}
+
+fn backfill_escrow_deadlines() -> bool {
+    TRANSACTION_SERVICE.with(|s| s.borrow().backfill_escrow_deadlines())
+}
+
+fn backfill_payment_schedules() -> bool {
+    TRANSACTION_SERVICE.with(|s| s.borrow().backfill_payment_schedules())
+}
+
+fn run_escrow_sweep() {
+    if SYSTEM_STATE.with(|s| s.borrow().is_paused) {
+        return;
+    }
+
+    let report = TRANSACTION_SERVICE.with(|s| s.borrow().process_escrow_timeouts(ESCROW_EXPIRY_WARNING));
+
+    if report.released + report.expired + report.warned + report.failed > 0 {
+        ic_cdk::println!(
+            "Escrow sweep: {} released, {} expired, {} warned, {} failed",
+            report.released, report.expired, report.warned, report.failed,
+        );
+    }
+}
+
+fn run_scheduled_payments() {
+    if SYSTEM_STATE.with(|s| s.borrow().is_paused) {
+        return;
+    }
+
+    let report = TRANSACTION_SERVICE.with(|s| s.borrow().process_scheduled_payments());
+
+    if report.executed + report.failed + report.finished > 0 {
+        ic_cdk::println!(
+            "Scheduled payments: {} executed, {} failed, {} finished",
+            report.executed, report.failed, report.finished,
+        );
+    }
+}
+
+fn run_rate_limit_cleanup() {
+    let removed = RATE_LIMITER.with(|l| l.borrow().cleanup_stale());
+
+    if removed > 0 {
+        ic_cdk::println!("Rate limiter: {} stale entries removed", removed);
+    }
+}
+
+fn run_kyc_expiry() {
+    let expired = KYC_SERVICE.with(|s| s.borrow().expire_approvals());
+
+    if expired > 0 {
+        ic_cdk::println!("KYC: {} approvals expired", expired);
+    }
+}
+
+fn run_evidence_cleanup() {
+    let expired = EVIDENCE_SERVICE.with(|s| s.borrow().expire_stale_uploads());
+
+    if expired > 0 {
+        ic_cdk::println!("Evidence: {} abandoned uploads removed", expired);
+    }
+}
@@ -1,40 +1,41 @@
 use candid::Principal;
-use ic_cdk::api::time;
-use std::cell::RefCell;
+use crate::utils::time::now;
 use crate::types::{
     errors::ApiError,
     transaction::*,
     common::{PaginationParams, AuditAction},
+    treasury::FeeSource,
+    fees::FeeTransactionType,
+    messaging::WsEvent,
 };
 use crate::models::transaction::TransactionModel;
 use crate::storage::stable_storage::StorageManager;
-use crate::security::{
-    validation,
-    audit::AuditLogger,
+use crate::storage::config::{
+    self, StableCounter, TRANSACTION_ID_COUNTER, FEE_PERCENTAGE_BPS, MAX_AGENT_FEE_BPS, ESCROW_DEADLINE_BACKFILL_CURSOR,
+    PAYMENT_SCHEDULE_BACKFILL_CURSOR,
 };
-use crate::services::notification_service::NotificationService;
-use crate::BALANCE_SERVICE;
+use crate::utils::constants::{
+    DEFAULT_TRANSACTION_FEE_BPS, DEFAULT_MAX_AGENT_FEE_BPS, NANOS_PER_HOUR, INDEX_BACKFILL_BATCH_SIZE, ESCROW_SWEEP_BATCH_SIZE,
+    MAX_MILESTONES, MAX_MILESTONE_DESCRIPTION_LENGTH, MAX_MILESTONE_DISPUTE_REASON_LENGTH,
+    MAX_SCHEDULED_PAYMENT_RETRIES, SCHEDULED_PAYMENT_RETRY_DELAY, SCHEDULED_PAYMENT_BATCH_SIZE, MAX_REFUNDS_PER_TRANSACTION,
+    LIMIT_DAILY_WINDOW, LIMIT_MONTHLY_WINDOW,
+};
+use crate::utils::time::get_next_occurrence;
+use crate::security::validation;
+use crate::services::events::send_ws_event;
+use crate::services::fee_service::apply_bps;
+use crate::{AUDIT_LOGGER, BALANCE_SERVICE, NOTIFICATION_SERVICE, USER_SERVICE};
 
 pub struct TransactionService {
-    next_id: RefCell<u64>,
-    fee_percentage: u64,
+    next_id: StableCounter,
     min_transaction_amount: u64,
-    max_transaction_amount: u64,
-    
-    // Dependencies
-    audit_logger: RefCell<AuditLogger>,
-    notification_service: RefCell<NotificationService>,
 }
 
 impl TransactionService {
     pub fn new() -> Self {
         Self {
-            next_id: RefCell::new(1),
-            fee_percentage: 100, // 1% fee
+            next_id: StableCounter::new(TRANSACTION_ID_COUNTER),
             min_transaction_amount: 1000,
-            max_transaction_amount: 1_000_000_000,
-            audit_logger: RefCell::new(AuditLogger::with_defaults()),
-            notification_service: RefCell::new(NotificationService::new()),
         }
     }
     
@@ -53,12 +54,10 @@ impl TransactionService {
             });
         }
 
-        validation::validate_amount(
-            request.amount,
-            Some(self.min_transaction_amount),
-            Some(self.max_transaction_amount),
-        )?;
+        validation::validate_amount(request.amount, Some(self.min_transaction_amount), None)?;
         let description = validation::validate_text(&request.description, "description", 1, 500)?;
+        validation::validate_currency(&request.currency)?;
+        self.check_tier_limits(from, request.amount, &request.currency)?;
 
         if from == request.to {
             return Err(ApiError::ValidationError {
@@ -66,9 +65,35 @@ impl TransactionService {
                 message: "Cannot send to yourself".to_string(),
             });
         }
+        // Admin reversals send funds back to the original sender regardless.
+        if request.transaction_type != TransactionType::Reversal {
+            crate::CONTACT_SERVICE.with(|s| s.borrow().ensure_not_blocked(from, request.to))?;
+        }
+
+        if let Some(agent) = request.escrow_agent {
+            validation::validate_principal(&agent)?;
+            if agent == from || agent == request.to {
+                return Err(ApiError::ValidationError {
+                    field: "escrow_agent".to_string(),
+                    message: "Escrow agent must be a third party".to_string(),
+                });
+            }
+        }
+
+        if let Some(agent_fee_bps) = request.agent_fee_bps {
+            let max_agent_fee_bps = config::get_or(MAX_AGENT_FEE_BPS, DEFAULT_MAX_AGENT_FEE_BPS);
+            if request.escrow_agent.is_none() || agent_fee_bps > max_agent_fee_bps {
+                return Err(ApiError::ValidationError {
+                    field: "agent_fee_bps".to_string(),
+                    message: format!("Agent fee requires an escrow agent and cannot exceed {} bps", max_agent_fee_bps),
+                });
+            }
+        }
 
-        let balance = BALANCE_SERVICE.with(|s| s.borrow_mut().get_balance(from))?;
-        let fee = self.calculate_fee(request.amount);
+        let transaction_type = prepare_milestones(request.transaction_type.clone(), request.amount)?;
+
+        let balance = BALANCE_SERVICE.with(|s| s.borrow_mut().get_balance(from, &request.currency))?;
+        let fee = self.calculate_fee(FeeTransactionType::of(&transaction_type), request.amount, &request.currency);
         let total_amount = request.amount + fee;
         
         if balance.available < total_amount {
@@ -82,12 +107,12 @@ impl TransactionService {
 
         BALANCE_SERVICE.with(|s| {
             s.borrow_mut()
-                .lock_funds(from, total_amount, id)
+                .lock_funds(from, total_amount, &request.currency, id)
         })?;
 
         if let Some(deadline) = request.deadline {
             validation::validate_timestamp(deadline, "deadline")?;
-            if deadline <= time() {
+            if deadline <= now() {
                 return Err(ApiError::ValidationError {
                     field: "deadline".to_string(),
                     message: "Deadline must be in the future".to_string(),
@@ -95,16 +120,16 @@ impl TransactionService {
             }
         }
         
-        let now = time();
+        let now = now();
         
         let transaction_model = TransactionModel {
             id,
-            transaction_type: request.transaction_type.clone(),
+            transaction_type,
             from,
             to: request.to,
             amount: request.amount,
             fee,
-            currency: request.currency,
+            currency: request.currency.clone(),
             description,
             status: TransactionStatus::Pending,
             escrow_agent: request.escrow_agent,
@@ -117,29 +142,112 @@ impl TransactionService {
                 tags: request.tags,
                 ..Default::default()
             },
+            expiry_notice_for: None,
+            agent_fee_bps: request.escrow_agent.and(request.agent_fee_bps),
+            agent_accepted_at: None,
         };
 
-        let storage = self.storage();
-        storage.transactions().insert(id, transaction_model.clone());
-        storage.user_transactions().insert_indexed(id, transaction_model.clone(), from);
-        storage.user_transactions().insert_indexed(id, transaction_model.clone(), request.to);
+        self.store_new_transaction(&transaction_model);
 
-        self.notification_service.borrow().create_transaction_notification(
+        let _ = NOTIFICATION_SERVICE.with(|s| s.borrow().create_transaction_notification(
             request.to,
             id,
             &format!("New transaction from {}", from.to_text()),
-        );
+        ));
 
-        self.audit_logger.borrow().log(
+        AUDIT_LOGGER.with(|l| l.borrow().log(
             from,
             AuditAction::TransactionCreated,
             &format!("transaction_{}", id),
             Some(format!("Amount: {}, To: {}", request.amount, request.to)),
-        );
+        ));
         
         Ok(transaction_model.into())
     }
 
+    /// Registers a standing order. Nothing is locked up front: each payment is
+    /// debited from the sender's available balance when it falls due.
+    pub fn create_scheduled_payment(
+        &self,
+        from: Principal,
+        to: Principal,
+        mut schedule: PaymentSchedule,
+        description: String,
+        currency: Currency,
+    ) -> Result<Transaction, ApiError> {
+        if crate::SYSTEM_STATE.with(|s| s.borrow().is_paused) {
+            return Err(ApiError::SystemPaused {
+                reason: crate::SYSTEM_STATE.with(|s| s.borrow().reason.clone().unwrap_or_default()),
+            });
+        }
+
+        validation::validate_principal(&to)?;
+        validation::validate_amount(schedule.amount_per_payment, Some(self.min_transaction_amount), None)?;
+        // Only the per-payment cap applies up front; volume is counted as
+        // each payment executes.
+        crate::TIER_LIMITER.with(|l| l.borrow().check_transaction(from, schedule.amount_per_payment, &currency, 0, 0))?;
+        validation::validate_payment_schedule(&schedule)?;
+        validation::validate_currency(&currency)?;
+        let description = validation::validate_text(&description, "description", 1, 500)?;
+
+        if from == to {
+            return Err(ApiError::ValidationError {
+                field: "to".to_string(),
+                message: "Cannot send to yourself".to_string(),
+            });
+        }
+        crate::CONTACT_SERVICE.with(|s| s.borrow().ensure_not_blocked(from, to))?;
+
+        schedule.payments_completed = 0;
+        schedule.next_payment_date = schedule.start_date;
+        schedule.failed_attempts = None;
+        schedule.retry_at = None;
+
+        let id = self.get_next_id();
+        let now = now();
+
+        let transaction_model = TransactionModel {
+            id,
+            transaction_type: TransactionType::ScheduledPayment { schedule: schedule.clone() },
+            from,
+            to,
+            amount: schedule.amount_per_payment,
+            fee: 0,
+            currency,
+            description,
+            status: TransactionStatus::Pending,
+            escrow_agent: None,
+            created_at: now,
+            updated_at: now,
+            completed_at: None,
+            deadline: None,
+            metadata: TransactionMetadata {
+                tags: vec!["scheduled".to_string()],
+                ..Default::default()
+            },
+            expiry_notice_for: None,
+            agent_fee_bps: None,
+            agent_accepted_at: None,
+        };
+
+        self.store_new_transaction(&transaction_model);
+
+        let _ = NOTIFICATION_SERVICE.with(|s| s.borrow().create_transaction_notification(
+            to,
+            id,
+            &format!("{} set up a scheduled payment to you", from.to_text()),
+        ));
+
+        AUDIT_LOGGER.with(|l| l.borrow().log(
+            from,
+            AuditAction::TransactionCreated,
+            &format!("transaction_{}", id),
+            Some(format!("Scheduled: {} per payment, {:?}", schedule.amount_per_payment, schedule.frequency)),
+        ));
+
+        Ok(transaction_model.into())
+    }
+
     pub fn approve_transaction(
         &mut self,
         transaction_id: u64,
@@ -166,22 +274,22 @@ impl TransactionService {
         }
 
         transaction.status = TransactionStatus::Approved;
-        transaction.updated_at = time();
+        transaction.updated_at = now();
         
-        self.storage().transactions().insert(transaction_id, transaction.clone());
+        self.save_transaction(&transaction);
 
-        self.notification_service.borrow().create_transaction_notification(
+        let _ = NOTIFICATION_SERVICE.with(|s| s.borrow().create_transaction_notification(
             transaction.from,
             transaction_id,
             "Transaction approved",
-        );
+        ));
 
-        self.audit_logger.borrow().log(
+        AUDIT_LOGGER.with(|l| l.borrow().log(
             approver,
             AuditAction::TransactionApproved,
             &format!("transaction_{}", transaction_id),
             None,
-        );
+        ));
         
         Ok(transaction.into())
     }
@@ -207,15 +315,15 @@ impl TransactionService {
         }
 
         transaction.status = TransactionStatus::InEscrow;
-        transaction.updated_at = time();
+        transaction.updated_at = now();
         
-        self.storage().transactions().insert(transaction_id, transaction.clone());
+        self.save_transaction(&transaction);
 
-        let _ = self.notification_service.borrow().create_transaction_notification(
+        let _ = NOTIFICATION_SERVICE.with(|s| s.borrow().create_transaction_notification(
             transaction.from,
             transaction_id,
             "Escrow terms have been accepted.",
-        );
+        ));
 
         Ok(transaction.into())
     }
@@ -226,6 +334,7 @@ impl TransactionService {
         submitter: Principal,
     ) -> Result<Transaction, ApiError> {
         let mut transaction = self.get_transaction_model(transaction_id)?;
+        ensure_not_milestone_escrow(&transaction)?;
 
         if transaction.to != submitter {
             return Err(ApiError::Unauthorized {
@@ -241,17 +350,17 @@ impl TransactionService {
         }
 
         transaction.status = TransactionStatus::SubmittedForReview {
-            submitted_at: time(),
+            submitted_at: now(),
         };
-        transaction.updated_at = time();
+        transaction.updated_at = now();
         
-        self.storage().transactions().insert(transaction_id, transaction.clone());
+        self.save_transaction(&transaction);
 
-        let _ = self.notification_service.borrow().create_transaction_notification(
+        let _ = NOTIFICATION_SERVICE.with(|s| s.borrow().create_transaction_notification(
             transaction.from,
             transaction_id,
             "Work has been submitted for your review.",
-        );
+        ));
 
         Ok(transaction.into())
     }
@@ -267,14 +376,13 @@ impl TransactionService {
             });
         }
         let mut transaction = self.get_transaction_model(transaction_id)?;
+        ensure_not_milestone_escrow(&transaction)?;
 
-        let authorized = transaction.from == completer ||
-                        transaction.to == completer ||
-                        transaction.escrow_agent == Some(completer);
+        let acting_agent = transaction.escrow_agent == Some(completer) && transaction.agent_accepted_at.is_some();
         
-        if transaction.from != completer {
+        if transaction.from != completer && !acting_agent {
             return Err(ApiError::Unauthorized {
-                reason: "Only the sender (User A) can release the funds for this escrow.".to_string(),
+                reason: "Only the sender (User A) or the escrow agent can release the funds for this escrow.".to_string(),
             });
         }
 
@@ -285,46 +393,537 @@ impl TransactionService {
             });
         }
 
-        let total_amount = transaction.amount + transaction.fee;
+        self.release_escrow(&mut transaction, completer)?;
+        
+        Ok(transaction.into())
+    }
+
+    /// Pays the escrowed amount out to the recipient, moves the fee to the
+    /// treasury and marks the transaction completed. `actor` is recorded in
+    /// the audit log.
+    fn release_escrow(&self, transaction: &mut TransactionModel, actor: Principal) -> Result<(), ApiError> {
+        let transaction_id = transaction.id;
         BALANCE_SERVICE.with(|s| {
             s.borrow_mut().transfer_locked_funds(
                 transaction.from,
                 transaction.to,
                 transaction.amount,
+                &transaction.currency,
                 transaction_id,
                 "Transaction completed",
             )
         })?;
+        self.collect_fee(transaction, FeeSource::Completion)?;
         
         transaction.status = TransactionStatus::Completed;
-        transaction.completed_at = Some(time());
-        transaction.updated_at = time();
+        transaction.completed_at = Some(now());
+        transaction.updated_at = now();
         
-        self.storage().transactions().insert(transaction_id, transaction.clone());
+        self.save_transaction(transaction);
+        crate::REFERRAL_SERVICE.with(|s| s.borrow().reward_completed_transaction(transaction));
         
         // self.update_balance_statistics(&transaction);
         
-        self.notification_service.borrow().create_transaction_notification(
+        let _ = NOTIFICATION_SERVICE.with(|s| s.borrow().create_transaction_notification(
             transaction.from,
             transaction_id,
             "Transaction completed",
-        );
-        self.notification_service.borrow().create_transaction_notification(
+        ));
+        let _ = NOTIFICATION_SERVICE.with(|s| s.borrow().create_transaction_notification(
             transaction.to,
             transaction_id,
             &format!("Payment received: {} {:?}", transaction.amount, transaction.currency),
-        );
+        ));
         
-        self.audit_logger.borrow().log(
-            completer,
+        AUDIT_LOGGER.with(|l| l.borrow().log(
+            actor,
             AuditAction::TransactionCompleted,
             &format!("transaction_{}", transaction_id),
             None,
-        );
+        ));
         
-        Ok(transaction.into())
+        Ok(())
+    }
+
+    /// Acts on escrows whose clock has run out: work left in review past
+    /// `auto_release_after` is paid out, and escrows nobody accepted before
+    /// their `deadline` are refunded. Parties get an `EscrowExpiring` notice
+    /// `warning_window` nanoseconds ahead of either. At most
+    /// `ESCROW_SWEEP_BATCH_SIZE` escrows are handled per call.
+    pub fn process_escrow_timeouts(&self, warning_window: u64) -> EscrowSweepReport {
+        let current_time = now();
+        let mut report = EscrowSweepReport::default();
+        let deadlines = self.storage().escrow_deadlines();
+
+        let horizon = current_time.saturating_add(warning_window);
+        let due = deadlines.range_while_take((0, 0), ESCROW_SWEEP_BATCH_SIZE, |(due_at, _), _| *due_at <= horizon);
+
+        for ((due_at, id), _) in due {
+            // Entries go stale once the escrow moves on or its clock restarts.
+            let transaction = self.storage().transactions().get(&id)
+                .filter(|tx| escrow_due(tx).is_some_and(|(at, _)| at == due_at));
+            let Some(mut transaction) = transaction else {
+                deadlines.remove(&(due_at, id));
+                continue;
+            };
+
+            let result = match escrow_timeout(&transaction, current_time, warning_window) {
+                Some(EscrowTimeout::AutoRelease) => self
+                    .release_escrow(&mut transaction, Principal::management_canister())
+                    .map(|_| report.released += 1),
+                Some(EscrowTimeout::Expire) => self
+                    .expire_escrow(&mut transaction)
+                    .map(|_| report.expired += 1),
+                Some(EscrowTimeout::Warn { due_at }) => {
+                    self.warn_escrow_expiring(&mut transaction, due_at, current_time);
+                    report.warned += 1;
+                    Ok(())
+                }
+                None => Ok(()),
+            };
+
+            match result {
+                Ok(()) if escrow_due(&transaction).is_none() => deadlines.remove(&(due_at, id)),
+                Ok(()) => None,
+                Err(e) => {
+                    ic_cdk::println!("Escrow timeout for transaction {} failed: {}", id, e.to_string());
+                    report.failed += 1;
+                    None
+                }
+            };
+        }
+
+        report
+    }
+
+    fn expire_escrow(&self, transaction: &mut TransactionModel) -> Result<(), ApiError> {
+        let transaction_id = transaction.id;
+        let total_amount = transaction.amount + transaction.fee;
+        BALANCE_SERVICE.with(|s| {
+            s.borrow_mut()
+                .unlock_funds(transaction.from, total_amount, &transaction.currency, transaction_id)
+        })?;
+
+        let reason = "Escrow deadline passed before the terms were accepted".to_string();
+        transaction.status = TransactionStatus::Failed {
+            reason: reason.clone(),
+            failed_at: now(),
+        };
+        transaction.updated_at = now();
+
+        self.save_transaction(transaction);
+
+        let _ = NOTIFICATION_SERVICE.with(|s| s.borrow().create_transaction_notification(
+            transaction.from,
+            transaction_id,
+            "Escrow expired: your funds have been returned.",
+        ));
+        let _ = NOTIFICATION_SERVICE.with(|s| s.borrow().create_transaction_notification(
+            transaction.to,
+            transaction_id,
+            "Escrow expired before you accepted the terms.",
+        ));
+
+        AUDIT_LOGGER.with(|l| l.borrow().log(
+            Principal::management_canister(),
+            AuditAction::TransactionFailed,
+            &format!("transaction_{}", transaction_id),
+            Some(reason),
+        ));
+
+        Ok(())
+    }
+
+    fn warn_escrow_expiring(&self, transaction: &mut TransactionModel, due_at: u64, current_time: u64) {
+        let hours_left = due_at.saturating_sub(current_time).div_ceil(NANOS_PER_HOUR);
+        NOTIFICATION_SERVICE.with(|s| {
+            let notifications = s.borrow();
+            if matches!(transaction.status, TransactionStatus::SubmittedForReview { .. }) {
+                let _ = notifications.create_escrow_expiring_notification(
+                    transaction.from,
+                    transaction.id,
+                    &format!(
+                        "Funds will be released automatically in {} hour(s) unless you raise a dispute.",
+                        hours_left,
+                    ),
+                );
+            } else {
+                for party in [transaction.from, transaction.to] {
+                    let _ = notifications.create_escrow_expiring_notification(
+                        party,
+                        transaction.id,
+                        &format!("Escrow expires in {} hour(s) unless the terms are accepted.", hours_left),
+                    );
+                }
+            }
+        });
+
+        transaction.expiry_notice_for = Some(due_at);
+        self.storage().transactions().insert(transaction.id, transaction.clone());
     }
     
+    /// Runs every standing order whose payment (or retry) is due. A payment
+    /// that cannot be funded is recorded as a failed child transaction and
+    /// retried; once retries run out that occurrence is skipped. At most
+    /// `SCHEDULED_PAYMENT_BATCH_SIZE` schedules are handled per call.
+    pub fn process_scheduled_payments(&self) -> ScheduledPaymentReport {
+        let current_time = now();
+        let mut report = ScheduledPaymentReport::default();
+
+        let schedules = self.storage().payment_schedules();
+        let due = schedules.range_while_take((0, 0), SCHEDULED_PAYMENT_BATCH_SIZE, |(due_at, _), _| *due_at <= current_time);
+
+        for ((due_at, id), _) in due {
+            // Every save re-indexes the schedule, so this entry is done with.
+            schedules.remove(&(due_at, id));
+
+            let parent = self.storage().transactions().get(&id)
+                .filter(|tx| payment_due(tx) == Some(due_at));
+            let Some(mut parent) = parent else {
+                continue;
+            };
+            let mut schedule = match &parent.transaction_type {
+                TransactionType::ScheduledPayment { schedule } => schedule.clone(),
+                _ => continue,
+            };
+
+            if schedule_exhausted(&schedule, schedule.next_payment_date) {
+                self.finish_schedule(&mut parent, schedule);
+                report.finished += 1;
+                continue;
+            }
+
+            match self.execute_scheduled_payment(&parent, &schedule) {
+                Ok(_) => {
+                    report.executed += 1;
+                    schedule.payments_completed += 1;
+                    schedule.failed_attempts = None;
+                    schedule.retry_at = None;
+                }
+                Err(e) => {
+                    report.failed += 1;
+                    let attempts = schedule.failed_attempts.unwrap_or(0) + 1;
+                    let retrying = attempts <= MAX_SCHEDULED_PAYMENT_RETRIES;
+                    self.record_failed_scheduled_payment(&parent, &schedule, &e, retrying);
+
+                    if retrying {
+                        schedule.failed_attempts = Some(attempts);
+                        schedule.retry_at = Some(current_time + SCHEDULED_PAYMENT_RETRY_DELAY);
+                        self.save_schedule(&mut parent, schedule);
+                        continue;
+                    }
+
+                    schedule.failed_attempts = None;
+                    schedule.retry_at = None;
+                }
+            }
+
+            match next_payment_date(&schedule) {
+                Some(next) => {
+                    schedule.next_payment_date = next;
+                    self.save_schedule(&mut parent, schedule);
+                }
+                None => {
+                    self.finish_schedule(&mut parent, schedule);
+                    report.finished += 1;
+                }
+            }
+        }
+
+        report
+    }
+
+    /// Pays one installment as a completed `DirectPayment` linked to its
+    /// schedule through the `scheduled_payment_id` custom field.
+    fn execute_scheduled_payment(
+        &self,
+        parent: &TransactionModel,
+        schedule: &PaymentSchedule,
+    ) -> Result<Transaction, ApiError> {
+        let amount = schedule.amount_per_payment;
+        // The order was authorized when it was created; the payer may have
+        // been frozen or deactivated since.
+        if let Some(account) = USER_SERVICE.with(|s| s.borrow().get_account(parent.from)) {
+            if !account.is_active {
+                return Err(ApiError::Unauthorized {
+                    reason: "Account is deactivated".to_string(),
+                });
+            }
+            if account.is_frozen {
+                return Err(ApiError::AccountFrozen {
+                    reason: account.freeze_reason.unwrap_or_default(),
+                });
+            }
+        }
+        crate::CONTACT_SERVICE.with(|s| s.borrow().ensure_not_blocked(parent.from, parent.to))?;
+        self.check_tier_limits(parent.from, amount, &parent.currency)?;
+
+        let fee = self.calculate_fee(FeeTransactionType::ScheduledPayment, amount, &parent.currency);
+        let total_amount = amount + fee;
+
+        let balance = BALANCE_SERVICE.with(|s| s.borrow_mut().get_balance(parent.from, &parent.currency))?;
+        if balance.available < total_amount {
+            return Err(ApiError::InsufficientFunds {
+                available: balance.available,
+                required: total_amount,
+            });
+        }
+
+        let mut child = self.scheduled_payment_child(parent, schedule, fee);
+        let child_id = child.id;
+
+        BALANCE_SERVICE.with(|s| {
+            s.borrow_mut().lock_funds(parent.from, total_amount, &parent.currency, child_id)
+        })?;
+        BALANCE_SERVICE.with(|s| {
+            s.borrow_mut().transfer_locked_funds(
+                parent.from,
+                parent.to,
+                amount,
+                &parent.currency,
+                child_id,
+                "Scheduled payment",
+            )
+        })?;
+        self.collect_fee(&child, FeeSource::ScheduledPayment)?;
+
+        child.status = TransactionStatus::Completed;
+        child.completed_at = Some(now());
+
+        self.store_new_transaction(&child);
+        crate::REFERRAL_SERVICE.with(|s| s.borrow().reward_completed_transaction(&child));
+
+        let _ = NOTIFICATION_SERVICE.with(|s| s.borrow().create_transaction_notification(
+            parent.to,
+            child_id,
+            &format!("Scheduled payment received: {} {:?}", amount, parent.currency),
+        ));
+
+        AUDIT_LOGGER.with(|l| l.borrow().log(
+            parent.from,
+            AuditAction::TransactionCompleted,
+            &format!("transaction_{}", child_id),
+            Some(format!("Scheduled payment {} of transaction {}", schedule.payments_completed + 1, parent.id)),
+        ));
+
+        Ok(child.into())
+    }
+
+    fn record_failed_scheduled_payment(
+        &self,
+        parent: &TransactionModel,
+        schedule: &PaymentSchedule,
+        error: &ApiError,
+        retrying: bool,
+    ) {
+        let mut child = self.scheduled_payment_child(parent, schedule, 0);
+        child.status = TransactionStatus::Failed {
+            reason: error.to_string(),
+            failed_at: now(),
+        };
+
+        self.store_new_transaction(&child);
+
+        let outcome = if retrying {
+            format!("It will be retried in {} hour(s).", SCHEDULED_PAYMENT_RETRY_DELAY / NANOS_PER_HOUR)
+        } else {
+            "This payment has been skipped.".to_string()
+        };
+
+        NOTIFICATION_SERVICE.with(|s| {
+            let notifications = s.borrow();
+            let _ = notifications.create_transaction_notification(
+                parent.from,
+                parent.id,
+                &format!("Scheduled payment failed: {}. {}", error.to_string(), outcome),
+            );
+            let _ = notifications.create_transaction_notification(
+                parent.to,
+                parent.id,
+                &format!("A scheduled payment from {} could not be processed. {}", parent.from.to_text(), outcome),
+            );
+        });
+
+        AUDIT_LOGGER.with(|l| l.borrow().log(
+            parent.from,
+            AuditAction::TransactionFailed,
+            &format!("transaction_{}", child.id),
+            Some(format!("Scheduled payment of transaction {}: {}", parent.id, error.to_string())),
+        ));
+    }
+
+    fn scheduled_payment_child(&self, parent: &TransactionModel, schedule: &PaymentSchedule, fee: u64) -> TransactionModel {
+        let now = now();
+
+        TransactionModel {
+            id: self.get_next_id(),
+            transaction_type: TransactionType::DirectPayment,
+            from: parent.from,
+            to: parent.to,
+            amount: schedule.amount_per_payment,
+            fee,
+            currency: parent.currency.clone(),
+            description: format!("{} (payment {})", parent.description, schedule.payments_completed + 1),
+            status: TransactionStatus::Processing,
+            escrow_agent: None,
+            created_at: now,
+            updated_at: now,
+            completed_at: None,
+            deadline: None,
+            metadata: TransactionMetadata {
+                category: parent.metadata.category.clone(),
+                tags: vec!["scheduled".to_string()],
+                custom_fields: vec![("scheduled_payment_id".to_string(), parent.id.to_string())],
+                ..Default::default()
+            },
+            expiry_notice_for: None,
+            agent_fee_bps: None,
+            agent_accepted_at: None,
+        }
+    }
+
+    fn save_schedule(&self, parent: &mut TransactionModel, schedule: PaymentSchedule) {
+        parent.transaction_type = TransactionType::ScheduledPayment { schedule };
+        parent.updated_at = now();
+        self.save_transaction(parent);
+    }
+
+    fn finish_schedule(&self, parent: &mut TransactionModel, schedule: PaymentSchedule) {
+        let payments_completed = schedule.payments_completed;
+        parent.status = TransactionStatus::Completed;
+        parent.completed_at = Some(now());
+        self.save_schedule(parent, schedule);
+
+        for party in [parent.from, parent.to] {
+            let _ = NOTIFICATION_SERVICE.with(|s| s.borrow().create_transaction_notification(
+                party,
+                parent.id,
+                &format!("Scheduled payment finished after {} payment(s)", payments_completed),
+            ));
+        }
+    }
+
+    /// Lets the recipient of a completed payment return part or all of it to
+    /// the sender. Refunds are capped, cumulatively, at the original amount.
+    pub fn refund_transaction(
+        &self,
+        transaction_id: u64,
+        amount: u64,
+        reason: String,
+        refunder: Principal,
+    ) -> Result<Transaction, ApiError> {
+        let mut original = self.get_transaction_model(transaction_id)?;
+
+        if original.to != refunder {
+            return Err(ApiError::Unauthorized {
+                reason: "Only the recipient of a payment can refund it.".to_string(),
+            });
+        }
+
+        if matches!(original.transaction_type, TransactionType::Refund { .. } | TransactionType::ScheduledPayment { .. }) {
+            return Err(ApiError::InvalidState {
+                current_state: format!("{:?}", original.transaction_type),
+                required_state: "Payment or escrow".to_string(),
+            });
+        }
+
+        let (already_refunded, mut refund_ids) = match &original.status {
+            TransactionStatus::Completed => (0, vec![]),
+            TransactionStatus::PartiallyRefunded { refunded_amount, refund_transaction_ids } => {
+                (*refunded_amount, refund_transaction_ids.clone())
+            }
+            other => {
+                return Err(ApiError::InvalidState {
+                    current_state: format!("{:?}", other),
+                    required_state: "Completed or PartiallyRefunded".to_string(),
+                });
+            }
+        };
+
+        let remaining = original.amount - already_refunded;
+        validation::validate_amount(amount, Some(1), Some(remaining))?;
+        if refund_ids.len() + 1 >= MAX_REFUNDS_PER_TRANSACTION && amount != remaining {
+            return Err(ApiError::ValidationError {
+                field: "amount".to_string(),
+                message: format!(
+                    "A payment can be refunded at most {} times; this refund must return the remaining {}",
+                    MAX_REFUNDS_PER_TRANSACTION, remaining,
+                ),
+            });
+        }
+        let reason = validation::validate_text(&reason, "reason", 1, 500)?;
+
+        let now = now();
+        let refund = TransactionModel {
+            id: self.get_next_id(),
+            transaction_type: TransactionType::Refund { original_transaction_id: transaction_id },
+            from: original.to,
+            to: original.from,
+            amount,
+            fee: 0,
+            currency: original.currency.clone(),
+            description: reason,
+            status: TransactionStatus::Completed,
+            escrow_agent: None,
+            created_at: now,
+            updated_at: now,
+            completed_at: Some(now),
+            deadline: None,
+            metadata: TransactionMetadata {
+                category: Some(TransactionCategory::Refund),
+                custom_fields: vec![("original_transaction_id".to_string(), transaction_id.to_string())],
+                ..Default::default()
+            },
+            expiry_notice_for: None,
+            agent_fee_bps: None,
+            agent_accepted_at: None,
+        };
+
+        BALANCE_SERVICE.with(|s| {
+            s.borrow_mut().transfer_refund(
+                refund.from,
+                refund.to,
+                amount,
+                &refund.currency,
+                refund.id,
+                transaction_id,
+            )
+        })?;
+
+        self.store_new_transaction(&refund);
+
+        refund_ids.push(refund.id);
+        original.status = if already_refunded + amount == original.amount {
+            TransactionStatus::Refunded {
+                refund_transaction_id: refund.id,
+                refund_transaction_ids: refund_ids,
+                refunded_at: now,
+            }
+        } else {
+            TransactionStatus::PartiallyRefunded {
+                refunded_amount: already_refunded + amount,
+                refund_transaction_ids: refund_ids,
+            }
+        };
+        original.updated_at = now;
+        self.save_transaction(&original);
+
+        let _ = NOTIFICATION_SERVICE.with(|s| s.borrow().create_transaction_notification(
+            original.from,
+            refund.id,
+            &format!("Refund received: {} {:?} for transaction {}", amount, refund.currency, transaction_id),
+        ));
+
+        AUDIT_LOGGER.with(|l| l.borrow().log(
+            refunder,
+            AuditAction::TransactionRefunded,
+            &format!("transaction_{}", transaction_id),
+            Some(format!("Refund {}: {}", refund.id, amount)),
+        ));
+
+        Ok(refund.into())
+    }
+
     pub fn cancel_transaction(
         &self,
         transaction_id: u64,
@@ -351,33 +950,36 @@ impl TransactionService {
             });
         }
 
-        let total_amount = transaction.amount + transaction.fee;
-        BALANCE_SERVICE.with(|s| {
-            s.borrow_mut()
-                .unlock_funds(transaction.from, total_amount, transaction_id)
-        })?;
+        // Standing orders hold no funds; each payment is debited when it runs.
+        if !matches!(transaction.transaction_type, TransactionType::ScheduledPayment { .. }) {
+            let total_amount = transaction.amount + transaction.fee;
+            BALANCE_SERVICE.with(|s| {
+                s.borrow_mut()
+                    .unlock_funds(transaction.from, total_amount, &transaction.currency, transaction_id)
+            })?;
+        }
 
         transaction.status = TransactionStatus::Cancelled {
             reason: reason.clone(),
             cancelled_by: canceller,
-            cancelled_at: time(),
+            cancelled_at: now(),
         };
-        transaction.updated_at = time();
+        transaction.updated_at = now();
         
-        self.storage().transactions().insert(transaction_id, transaction.clone());
+        self.save_transaction(&transaction);
 
-        self.notification_service.borrow().create_transaction_notification(
+        let _ = NOTIFICATION_SERVICE.with(|s| s.borrow().create_transaction_notification(
             transaction.to,
             transaction_id,
             &format!("Transaction cancelled: {}", reason),
-        );
+        ));
 
-        self.audit_logger.borrow().log(
+        AUDIT_LOGGER.with(|l| l.borrow().log(
             canceller,
             AuditAction::TransactionCancelled,
             &format!("transaction_{}", transaction_id),
             Some(reason),
-        );
+        ));
         
         Ok(transaction.into())
     }
@@ -396,19 +998,92 @@ impl TransactionService {
         Ok(transaction.into())
     }
 
-    pub fn get_user_transactions(
-        &self,
-        user: Principal,
-        filter: Option<TransactionFilter>,
-        pagination: PaginationParams,
-    ) -> Result<Vec<Transaction>, ApiError> {
-        pagination.validate()?;
-        
-        let transactions: Vec<Transaction> = self.storage().transactions()
-            .filter(|_, tx| {
-                if tx.from != user && tx.to != user {
-                    return false;
-                }
+    /// Reads a transaction without the party check; callers must have
+    /// already authorized the requester.
+    pub fn get_transaction_unchecked(&self, transaction_id: u64) -> Result<Transaction, ApiError> {
+        Ok(self.get_transaction_model(transaction_id)?.into())
+    }
+
+    pub fn get_statistics(&self) -> TransactionStatistics {
+        let mut stats = TransactionStatistics {
+            total_transactions: 0,
+            total_volume: 0,
+            total_fees: 0,
+            completed_count: 0,
+            pending_count: 0,
+            failed_count: 0,
+            average_transaction_size: 0,
+            transactions_by_type: vec![],
+            transactions_by_status: vec![],
+        };
+
+        for (_, tx) in self.storage().transactions().entries() {
+            stats.total_transactions += 1;
+            stats.total_volume = stats.total_volume.saturating_add(tx.amount);
+            stats.total_fees = stats.total_fees.saturating_add(tx.fee);
+
+            match tx.status {
+                TransactionStatus::Completed => stats.completed_count += 1,
+                TransactionStatus::Failed { .. } => stats.failed_count += 1,
+                TransactionStatus::Pending | TransactionStatus::Approved | TransactionStatus::Processing => {
+                    stats.pending_count += 1
+                }
+                _ => {}
+            }
+
+            match stats.transactions_by_type.iter_mut().find(|(t, _)| t.label() == tx.transaction_type.label()) {
+                Some((_, count)) => *count += 1,
+                None => stats.transactions_by_type.push((tx.transaction_type.clone(), 1)),
+            }
+            match stats.transactions_by_status.iter_mut().find(|(s, _)| s.label() == tx.status.label()) {
+                Some((_, count)) => *count += 1,
+                None => stats.transactions_by_status.push((tx.status.clone(), 1)),
+            }
+        }
+
+        stats.average_transaction_size = stats.total_volume.checked_div(stats.total_transactions).unwrap_or(0);
+
+        stats
+    }
+
+    /// Sum of what `user` sent in `currency` since `since`, leaving out
+    /// cancelled and failed transactions and scheduled-payment templates,
+    /// whose executions are counted instead.
+    pub fn outgoing_volume(&self, user: Principal, currency: &Currency, since: u64) -> u64 {
+        let storage = self.storage();
+        storage.outgoing_transactions()
+            .range_while((user, since, 0), |(from, _, _), _| *from == user)
+            .into_iter()
+            .filter_map(|((_, _, id), _)| storage.transactions().get(&id))
+            .filter(|tx| {
+                tx.currency == *currency
+                    && !matches!(tx.status, TransactionStatus::Cancelled { .. } | TransactionStatus::Failed { .. })
+                    && !matches!(tx.transaction_type, TransactionType::ScheduledPayment { .. })
+            })
+            .fold(0u64, |total, tx| total.saturating_add(tx.amount))
+    }
+
+    fn check_tier_limits(&self, from: Principal, amount: u64, currency: &Currency) -> Result<(), ApiError> {
+        let now = now();
+        let daily = self.outgoing_volume(from, currency, now.saturating_sub(LIMIT_DAILY_WINDOW));
+        let monthly = self.outgoing_volume(from, currency, now.saturating_sub(LIMIT_MONTHLY_WINDOW));
+
+        crate::TIER_LIMITER.with(|l| l.borrow().check_transaction(from, amount, currency, daily, monthly))
+    }
+
+    pub fn get_user_transactions(
+        &self,
+        user: Principal,
+        filter: Option<TransactionFilter>,
+        pagination: PaginationParams,
+    ) -> Result<Vec<Transaction>, ApiError> {
+        pagination.validate()?;
+        
+        let transactions: Vec<Transaction> = self.storage().transactions()
+            .filter(|_, tx| {
+                if tx.from != user && tx.to != user {
+                    return false;
+                }
                 
                 if let Some(ref f) = filter {
                     if let Some(ref statuses) = f.status {
@@ -448,6 +1123,7 @@ impl TransactionService {
         reason: String,
     ) -> Result<Transaction, ApiError> {
         let mut transaction = self.get_transaction_model(transaction_id)?;
+        ensure_not_milestone_escrow(&transaction)?;
     
         if transaction.from != disputer && transaction.to != disputer {
             return Err(ApiError::Unauthorized {
@@ -465,18 +1141,25 @@ impl TransactionService {
         transaction.status = TransactionStatus::Disputed {
             reason,
             disputed_by: disputer,
-            disputed_at: time(),
+            disputed_at: now(),
         };
-        transaction.updated_at = time();
+        transaction.updated_at = now();
         
-        self.storage().transactions().insert(transaction_id, transaction.clone());
+        self.save_transaction(&transaction);
     
         let other_party = if transaction.from == disputer { transaction.to } else { transaction.from };
-        let _ = self.notification_service.borrow().create_transaction_notification(
+        let _ = NOTIFICATION_SERVICE.with(|s| s.borrow().create_transaction_notification(
             other_party,
             transaction_id,
             "A dispute has been raised on your transaction.",
-        );
+        ));
+        if let (Some(agent), Some(_)) = (transaction.escrow_agent, transaction.agent_accepted_at) {
+            let _ = NOTIFICATION_SERVICE.with(|s| s.borrow().create_transaction_notification(
+                agent,
+                transaction_id,
+                "A dispute needs your review as escrow agent.",
+            ));
+        }
         
     
         Ok(transaction.into())
@@ -490,73 +1173,503 @@ impl TransactionService {
     ) -> Result<Transaction, ApiError> {
         let mut transaction = self.get_transaction_model(transaction_id)?;
     
+        if !matches!(transaction.status, TransactionStatus::Disputed { .. } | TransactionStatus::UnderReview { .. }) {
+            return Err(ApiError::InvalidState {
+                current_state: format!("{:?}", transaction.status),
+                required_state: "Disputed or UnderReview".to_string(),
+            });
+        }
+
+        self.settle_dispute(&mut transaction, resolution, admin_principal, 0)?;
+    
+        Ok(transaction.into())
+    }
+
+    pub fn accept_agent_role(&self, transaction_id: u64, agent: Principal) -> Result<Transaction, ApiError> {
+        let mut transaction = self.get_transaction_model(transaction_id)?;
+
+        if transaction.escrow_agent != Some(agent) {
+            return Err(ApiError::Unauthorized {
+                reason: "Only the designated escrow agent can accept this role.".to_string(),
+            });
+        }
+
+        if transaction.agent_accepted_at.is_some() {
+            return Err(ApiError::AlreadyExists {
+                resource: format!("Agent acceptance for transaction {}", transaction_id),
+            });
+        }
+
+        if !matches!(
+            transaction.status,
+            TransactionStatus::Pending
+                | TransactionStatus::Approved
+                | TransactionStatus::InEscrow
+                | TransactionStatus::SubmittedForReview { .. }
+                | TransactionStatus::Disputed { .. }
+        ) {
+            return Err(ApiError::InvalidState {
+                current_state: format!("{:?}", transaction.status),
+                required_state: "An open escrow".to_string(),
+            });
+        }
+
+        transaction.agent_accepted_at = Some(now());
+        transaction.updated_at = now();
+
+        self.save_transaction(&transaction);
+
+        for party in [transaction.from, transaction.to] {
+            let _ = NOTIFICATION_SERVICE.with(|s| s.borrow().create_transaction_notification(
+                party,
+                transaction_id,
+                "The escrow agent has accepted the role.",
+            ));
+        }
+
+        Ok(transaction.into())
+    }
+
+    pub fn start_dispute_review(&self, transaction_id: u64, agent: Principal) -> Result<Transaction, ApiError> {
+        let mut transaction = self.get_transaction_model(transaction_id)?;
+        self.ensure_acting_agent(&transaction, agent)?;
+
         if !matches!(transaction.status, TransactionStatus::Disputed { .. }) {
             return Err(ApiError::InvalidState {
                 current_state: format!("{:?}", transaction.status),
                 required_state: "Disputed".to_string(),
             });
         }
-    
-        match resolution {
-            DisputeResolution::ReleaseToRecipient => {
-                BALANCE_SERVICE.with(|s| {
-                    s.borrow_mut().transfer_locked_funds(
-                        transaction.from,
-                        transaction.to,
-                        transaction.amount,
-                        transaction_id,
-                        "Dispute resolved: Funds released to recipient.",
-                    )
-                })?;
-
-                BALANCE_SERVICE.with(|s| {
-                    s.borrow_mut().unlock_funds(transaction.from, transaction.fee, transaction_id)
-                })?;
-            },
-            DisputeResolution::RefundToSender => {
-                let total_amount = transaction.amount + transaction.fee;
-                BALANCE_SERVICE.with(|s| {
-                    s.borrow_mut().unlock_funds(transaction.from, total_amount, transaction_id)
-                })?;
-            },
-            DisputeResolution::SplitBetweenParties { sender_percentage } => {
-                let recipient_percentage = 100 - sender_percentage;
-                let recipient_amount = transaction.amount * recipient_percentage as u64 / 100;
-                let sender_amount = transaction.amount - recipient_amount;
-    
-                BALANCE_SERVICE.with(|s| {
-                    s.borrow_mut().transfer_locked_funds(
-                        transaction.from,
-                        transaction.to,
-                        recipient_amount,
-                        transaction_id,
-                        "Dispute resolved: Funds split.",
-                    )
-                })?;
-                
-                BALANCE_SERVICE.with(|s| {
-                    s.borrow_mut().unlock_funds(transaction.from, sender_amount + transaction.fee, transaction_id)
-                })?;
+
+        transaction.status = TransactionStatus::UnderReview {
+            reviewer: agent,
+            review_started_at: now(),
+        };
+        transaction.updated_at = now();
+
+        self.save_transaction(&transaction);
+
+        for party in [transaction.from, transaction.to] {
+            let _ = NOTIFICATION_SERVICE.with(|s| s.borrow().create_transaction_notification(
+                party,
+                transaction_id,
+                "The escrow agent is reviewing the dispute.",
+            ));
+        }
+
+        Ok(transaction.into())
+    }
+
+    /// Resolution by the transaction's own escrow agent, who is paid
+    /// `agent_fee_bps` of the escrowed amount for it.
+    pub fn agent_resolve_dispute(
+        &self,
+        transaction_id: u64,
+        resolution: DisputeResolution,
+        agent: Principal,
+    ) -> Result<Transaction, ApiError> {
+        let mut transaction = self.get_transaction_model(transaction_id)?;
+        self.ensure_acting_agent(&transaction, agent)?;
+
+        match transaction.status {
+            TransactionStatus::UnderReview { reviewer, .. } if reviewer == agent => {}
+            _ => {
+                return Err(ApiError::InvalidState {
+                    current_state: format!("{:?}", transaction.status),
+                    required_state: "UnderReview by this agent".to_string(),
+                });
             }
         }
+
+        let agent_fee = apply_bps(transaction.amount, transaction.agent_fee_bps.unwrap_or(0));
+        self.settle_dispute(&mut transaction, resolution, agent, agent_fee)?;
+
+        Ok(transaction.into())
+    }
+
+    fn ensure_acting_agent(&self, transaction: &TransactionModel, agent: Principal) -> Result<(), ApiError> {
+        if transaction.escrow_agent != Some(agent) || transaction.agent_accepted_at.is_none() {
+            return Err(ApiError::Unauthorized {
+                reason: "Only an escrow agent who accepted the role can arbitrate.".to_string(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Pays out a disputed escrow. `agent_fee` comes off the escrowed amount
+    /// before the resolution splits what remains; the platform fee goes to
+    /// the treasury whatever the outcome.
+    fn settle_dispute(
+        &self,
+        transaction: &mut TransactionModel,
+        resolution: DisputeResolution,
+        resolver: Principal,
+        agent_fee: u64,
+    ) -> Result<(), ApiError> {
+        let transaction_id = transaction.id;
+        let description = self.distribute_disputed_funds(
+            transaction,
+            transaction.amount,
+            &resolution,
+            agent_fee,
+        )?;
+        self.collect_fee(transaction, FeeSource::DisputeResolution)?;
     
         transaction.status = TransactionStatus::Resolved {
             resolution: resolution.clone(),
-            resolved_by: admin_principal,
-            resolved_at: time()
+            resolved_by: resolver,
+            resolved_at: now()
         };
-        transaction.updated_at = time();
+        transaction.updated_at = now();
     
-        self.storage().transactions().insert(transaction_id, transaction.clone());
+        self.save_transaction(transaction);
+
+        for party in [transaction.from, transaction.to] {
+            let _ = NOTIFICATION_SERVICE.with(|s| s.borrow().create_transaction_notification(
+                party,
+                transaction_id,
+                description,
+            ));
+        }
+
+        AUDIT_LOGGER.with(|l| l.borrow().log(
+            resolver,
+            AuditAction::TransactionDisputed,
+            &format!("transaction_{}", transaction_id),
+            Some(format!("Resolved: {:?}, agent fee: {}", resolution, agent_fee)),
+        ));
+
+        Ok(())
+    }
     
+    /// Splits `amount` of the sender's locked funds per `resolution`, after
+    /// paying `agent_fee` to the escrow agent. Returns a description of the
+    /// outcome.
+    fn distribute_disputed_funds(
+        &self,
+        transaction: &TransactionModel,
+        amount: u64,
+        resolution: &DisputeResolution,
+        agent_fee: u64,
+    ) -> Result<&'static str, ApiError> {
+        let distributable = amount.checked_sub(agent_fee).ok_or_else(|| ApiError::InternalError {
+            details: "Agent fee exceeds escrowed amount".to_string(),
+        })?;
+
+        let (recipient_amount, sender_amount) = match resolution {
+            DisputeResolution::ReleaseToRecipient => (distributable, 0),
+            DisputeResolution::RefundToSender => (0, distributable),
+            DisputeResolution::SplitBetweenParties { sender_percentage } => {
+                if *sender_percentage > 100 {
+                    return Err(ApiError::ValidationError {
+                        field: "sender_percentage".to_string(),
+                        message: "Percentage cannot exceed 100".to_string(),
+                    });
+                }
+                let recipient_amount = distributable * (100 - *sender_percentage as u64) / 100;
+                (recipient_amount, distributable - recipient_amount)
+            }
+        };
+
+        let description = match resolution {
+            DisputeResolution::ReleaseToRecipient => "Dispute resolved: Funds released to recipient.",
+            DisputeResolution::RefundToSender => "Dispute resolved: Funds refunded to sender.",
+            DisputeResolution::SplitBetweenParties { .. } => "Dispute resolved: Funds split.",
+        };
+
+        BALANCE_SERVICE.with(|s| {
+            let mut balances = s.borrow_mut();
+            if recipient_amount > 0 {
+                balances.transfer_locked_funds(
+                    transaction.from,
+                    transaction.to,
+                    recipient_amount,
+                    &transaction.currency,
+                    transaction.id,
+                    description,
+                )?;
+            }
+            if let (Some(agent), true) = (transaction.escrow_agent, agent_fee > 0) {
+                balances.transfer_locked_funds(
+                    transaction.from,
+                    agent,
+                    agent_fee,
+                    &transaction.currency,
+                    transaction.id,
+                    "Escrow agent fee",
+                )?;
+            }
+            if sender_amount > 0 {
+                balances.unlock_funds(transaction.from, sender_amount, &transaction.currency, transaction.id)?;
+            }
+            Ok::<(), ApiError>(())
+        })?;
+
+        Ok(description)
+    }
+
+    pub fn submit_milestone_work(
+        &self,
+        transaction_id: u64,
+        milestone_id: u32,
+        submitter: Principal,
+    ) -> Result<Transaction, ApiError> {
+        let mut transaction = self.get_transaction_model(transaction_id)?;
+
+        if transaction.to != submitter {
+            return Err(ApiError::Unauthorized {
+                reason: "Only the recipient (User B) can submit work for this escrow.".to_string(),
+            });
+        }
+
+        if !matches!(transaction.status, TransactionStatus::InEscrow) {
+            return Err(ApiError::InvalidState {
+                current_state: format!("{:?}", transaction.status),
+                required_state: "InEscrow".to_string(),
+            });
+        }
+
+        let milestone = milestone_mut(&mut transaction, milestone_id)?;
+        if !matches!(milestone.status, MilestoneStatus::Pending) {
+            return Err(ApiError::InvalidState {
+                current_state: format!("{:?}", milestone.status),
+                required_state: "Pending".to_string(),
+            });
+        }
+        milestone.status = MilestoneStatus::Submitted { submitted_at: now() };
+        let description = milestone.description.clone();
+
+        transaction.updated_at = now();
+        self.save_transaction(&transaction);
+
+        let _ = NOTIFICATION_SERVICE.with(|s| s.borrow().create_transaction_notification(
+            transaction.from,
+            transaction_id,
+            &format!("Milestone \"{}\" has been submitted for your review.", description),
+        ));
+
+        Ok(transaction.into())
+    }
+
+    pub fn approve_milestone(
+        &self,
+        transaction_id: u64,
+        milestone_id: u32,
+        approver: Principal,
+    ) -> Result<Transaction, ApiError> {
+        let mut transaction = self.get_transaction_model(transaction_id)?;
+
+        if transaction.from != approver && self.ensure_acting_agent(&transaction, approver).is_err() {
+            return Err(ApiError::Unauthorized {
+                reason: "Only the sender (User A) or the escrow agent can approve a milestone.".to_string(),
+            });
+        }
+
+        let milestone = milestone_mut(&mut transaction, milestone_id)?;
+        if !matches!(milestone.status, MilestoneStatus::Submitted { .. }) {
+            return Err(ApiError::InvalidState {
+                current_state: format!("{:?}", milestone.status),
+                required_state: "Submitted".to_string(),
+            });
+        }
+        milestone.status = MilestoneStatus::Approved { approved_at: now() };
+        let (amount, description) = (milestone.amount, milestone.description.clone());
+
+        BALANCE_SERVICE.with(|s| {
+            s.borrow_mut().transfer_locked_funds(
+                transaction.from,
+                transaction.to,
+                amount,
+                &transaction.currency,
+                transaction_id,
+                &format!("Milestone approved: {}", description),
+            )
+        })?;
+
+        transaction.updated_at = now();
+        self.save_transaction(&transaction);
+
+        let _ = NOTIFICATION_SERVICE.with(|s| s.borrow().create_transaction_notification(
+            transaction.to,
+            transaction_id,
+            &format!("Milestone \"{}\" approved: {} {:?} released", description, amount, transaction.currency),
+        ));
+
+        AUDIT_LOGGER.with(|l| l.borrow().log(
+            approver,
+            AuditAction::TransactionReleased,
+            &format!("transaction_{}", transaction_id),
+            Some(format!("Milestone {}: {}", milestone_id, amount)),
+        ));
+
+        self.complete_if_milestones_settled(&mut transaction)?;
+
+        Ok(transaction.into())
+    }
+
+    pub fn dispute_milestone(
+        &self,
+        transaction_id: u64,
+        milestone_id: u32,
+        disputer: Principal,
+        reason: String,
+    ) -> Result<Transaction, ApiError> {
+        let mut transaction = self.get_transaction_model(transaction_id)?;
+
+        if transaction.from != disputer && transaction.to != disputer {
+            return Err(ApiError::Unauthorized {
+                reason: "Only the sender or recipient can raise a dispute.".to_string(),
+            });
+        }
+        let reason = validation::validate_text(&reason, "reason", 1, MAX_MILESTONE_DISPUTE_REASON_LENGTH)?;
+
+        let milestone = milestone_mut(&mut transaction, milestone_id)?;
+        if !matches!(milestone.status, MilestoneStatus::Submitted { .. }) {
+            return Err(ApiError::InvalidState {
+                current_state: format!("{:?}", milestone.status),
+                required_state: "Submitted".to_string(),
+            });
+        }
+        milestone.status = MilestoneStatus::Disputed {
+            reason,
+            disputed_by: disputer,
+            disputed_at: now(),
+        };
+        let description = milestone.description.clone();
+
+        transaction.updated_at = now();
+        self.save_transaction(&transaction);
+
+        let other_party = if transaction.from == disputer { transaction.to } else { transaction.from };
+        let mut recipients = vec![other_party];
+        if let (Some(agent), Some(_)) = (transaction.escrow_agent, transaction.agent_accepted_at) {
+            recipients.push(agent);
+        }
+        for recipient in recipients {
+            let _ = NOTIFICATION_SERVICE.with(|s| s.borrow().create_transaction_notification(
+                recipient,
+                transaction_id,
+                &format!("A dispute has been raised on milestone \"{}\".", description),
+            ));
+        }
+
+        Ok(transaction.into())
+    }
+
+    /// Settles a disputed milestone. Open to the escrow agent, who earns
+    /// `agent_fee_bps` of the milestone amount, and to dispute arbiters.
+    pub fn resolve_milestone_dispute(
+        &self,
+        transaction_id: u64,
+        milestone_id: u32,
+        resolution: DisputeResolution,
+        resolver: Principal,
+    ) -> Result<Transaction, ApiError> {
+        let mut transaction = self.get_transaction_model(transaction_id)?;
+
+        let is_agent = self.ensure_acting_agent(&transaction, resolver).is_ok();
+        let is_arbiter = crate::ROLE_SERVICE.with(|r| {
+            r.borrow().has_permission(resolver, crate::types::role::Permission::ResolveDisputes)
+        });
+        if !is_agent && !is_arbiter {
+            return Err(ApiError::Unauthorized {
+                reason: "Only the escrow agent or a dispute arbiter can resolve this dispute.".to_string(),
+            });
+        }
+
+        let milestone = milestone_mut(&mut transaction, milestone_id)?.clone();
+        if !matches!(milestone.status, MilestoneStatus::Disputed { .. }) {
+            return Err(ApiError::InvalidState {
+                current_state: format!("{:?}", milestone.status),
+                required_state: "Disputed".to_string(),
+            });
+        }
+
+        let agent_fee = if is_agent {
+            apply_bps(milestone.amount, transaction.agent_fee_bps.unwrap_or(0))
+        } else {
+            0
+        };
+        let outcome = self.distribute_disputed_funds(&transaction, milestone.amount, &resolution, agent_fee)?;
+
+        milestone_mut(&mut transaction, milestone_id)?.status = MilestoneStatus::Resolved {
+            resolution: resolution.clone(),
+            resolved_by: resolver,
+            resolved_at: now(),
+        };
+        transaction.updated_at = now();
+        self.save_transaction(&transaction);
+
+        for party in [transaction.from, transaction.to] {
+            let _ = NOTIFICATION_SERVICE.with(|s| s.borrow().create_transaction_notification(
+                party,
+                transaction_id,
+                &format!("Milestone \"{}\": {}", milestone.description, outcome),
+            ));
+        }
+
+        AUDIT_LOGGER.with(|l| l.borrow().log(
+            resolver,
+            AuditAction::TransactionDisputed,
+            &format!("transaction_{}", transaction_id),
+            Some(format!("Milestone {} resolved: {:?}, agent fee: {}", milestone_id, resolution, agent_fee)),
+        ));
+
+        self.complete_if_milestones_settled(&mut transaction)?;
+
         Ok(transaction.into())
     }
-    
+
+    fn complete_if_milestones_settled(&self, transaction: &mut TransactionModel) -> Result<(), ApiError> {
+        let all_settled = match &transaction.transaction_type {
+            TransactionType::Escrow { milestones: Some(milestones), .. } => {
+                milestones.iter().all(|m| m.status.is_settled())
+            }
+            _ => false,
+        };
+        if !all_settled {
+            return Ok(());
+        }
+
+        self.collect_fee(transaction, FeeSource::Completion)?;
+
+        transaction.status = TransactionStatus::Completed;
+        transaction.completed_at = Some(now());
+        transaction.updated_at = now();
+        self.save_transaction(transaction);
+        crate::REFERRAL_SERVICE.with(|s| s.borrow().reward_completed_transaction(transaction));
+
+        for party in [transaction.from, transaction.to] {
+            let _ = NOTIFICATION_SERVICE.with(|s| s.borrow().create_transaction_notification(
+                party,
+                transaction.id,
+                "All milestones settled. Transaction completed",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Moves the fee locked alongside `transaction` into the treasury.
+    fn collect_fee(&self, transaction: &TransactionModel, source: FeeSource) -> Result<(), ApiError> {
+        if transaction.fee == 0 {
+            return Ok(());
+        }
+
+        BALANCE_SERVICE.with(|s| {
+            s.borrow_mut().collect_locked_fee(transaction.from, transaction.fee, &transaction.currency, transaction.id)
+        })?;
+        crate::TREASURY_SERVICE.with(|s| s.borrow().record_fee(transaction, source));
+
+        Ok(())
+    }
+
     // fn credit_funds(&self, principal: Principal, amount: u64) -> Result<(), ApiError> {
     //     let mut balance = self.get_or_create_balance(principal);
         
     //     balance.available += amount;
-    //     balance.updated_at = time();
+    //     balance.updated_at = now();
         
     //     self.storage().balances().insert(principal, balance);
     //     Ok(())
@@ -586,7 +1699,7 @@ impl TransactionService {
     //     let mut balance = self.get_or_create_balance(principal);
     //     balance.available += amount;
     //     balance.total_received += amount;
-    //     balance.updated_at = time();
+    //     balance.updated_at = now();
         
     //     self.storage().balances().insert(principal, balance.clone());
         
@@ -613,7 +1726,7 @@ impl TransactionService {
     //     }
         
     //     balance.available -= amount;
-    //     balance.updated_at = time();
+    //     balance.updated_at = now();
         
     //     self.storage().balances().insert(principal, balance.clone());
         
@@ -657,21 +1770,22 @@ impl TransactionService {
             deadline: None,
             category: None,
             tags: vec!["reversal".to_string()],
+            agent_fee_bps: None,
         };
 
         let reversal_transaction = self.create_transaction(original_transaction.to, reversal_request)?;
 
-        self.audit_logger.borrow().log(
+        AUDIT_LOGGER.with(|l| l.borrow().log(
             admin_principal,
             AuditAction::TransactionReversed,
             &transaction_id.to_string(),
             Some(reason),
-        );
+        ));
 
         Ok(reversal_transaction)
     }
 
-    pub fn update_fee_percentage(&mut self, new_fee_bps: u64, admin_principal: Principal) -> Result<(), ApiError> {
+    pub fn update_fee_percentage(&self, new_fee_bps: u64, admin_principal: Principal) -> Result<(), ApiError> {
         if new_fee_bps > 10000 { // Max 100%
             return Err(ApiError::ValidationError {
                 field: "new_fee_bps".to_string(),
@@ -679,30 +1793,148 @@ impl TransactionService {
             });
         }
 
-        let old_fee = self.fee_percentage;
-        self.fee_percentage = new_fee_bps;
+        let old_fee = config::get_or(FEE_PERCENTAGE_BPS, DEFAULT_TRANSACTION_FEE_BPS);
+        config::set(FEE_PERCENTAGE_BPS, new_fee_bps);
 
-        self.audit_logger.borrow().log(
+        AUDIT_LOGGER.with(|l| l.borrow().log(
             admin_principal,
             AuditAction::ConfigurationChanged,
             "fee_percentage",
             Some(format!("Changed from {} to {} bps", old_fee, new_fee_bps)),
-        );
+        ));
+
+        Ok(())
+    }
+    
+    pub fn update_max_agent_fee(&self, max_agent_fee_bps: u64, admin_principal: Principal) -> Result<(), ApiError> {
+        if max_agent_fee_bps > 10000 {
+            return Err(ApiError::ValidationError {
+                field: "max_agent_fee_bps".to_string(),
+                message: "Agent fee cannot exceed 10000 bps (100%)".to_string(),
+            });
+        }
+
+        let old_fee = config::get_or(MAX_AGENT_FEE_BPS, DEFAULT_MAX_AGENT_FEE_BPS);
+        config::set(MAX_AGENT_FEE_BPS, max_agent_fee_bps);
+
+        AUDIT_LOGGER.with(|l| l.borrow().log(
+            admin_principal,
+            AuditAction::ConfigurationChanged,
+            "max_agent_fee",
+            Some(format!("Changed from {} to {} bps", old_fee, max_agent_fee_bps)),
+        ));
 
         Ok(())
     }
     
     fn get_next_id(&self) -> u64 {
-        let mut id = self.next_id.borrow_mut();
-        let current = *id;
-        *id += 1;
-        current
+        self.next_id.next()
+    }
+
+    pub fn restore_next_id(&self) {
+        self.next_id.ensure_after(self.storage().transactions().last_key());
     }
     
-    fn calculate_fee(&self, amount: u64) -> u64 {
-        (amount * self.fee_percentage) / 10_000
+    fn calculate_fee(&self, transaction_type: FeeTransactionType, amount: u64, currency: &Currency) -> u64 {
+        crate::FEE_SERVICE.with(|s| s.borrow().quote(transaction_type, amount, currency).fee)
     }
     
+    /// Stores a transaction created by this service and indexes it for both
+    /// parties.
+    fn store_new_transaction(&self, transaction: &TransactionModel) {
+        let storage = self.storage();
+        storage.transactions().insert(transaction.id, transaction.clone());
+        storage.user_transactions().insert_indexed(transaction.id, transaction.clone(), transaction.from);
+        storage.user_transactions().insert_indexed(transaction.id, transaction.clone(), transaction.to);
+        storage.outgoing_transactions().insert((transaction.from, transaction.created_at, transaction.id), ());
+        self.index_deadlines(transaction);
+        self.publish_transaction(transaction);
+    }
+
+    /// Adds the transaction's current escrow deadline to the sweep index.
+    /// Superseded entries are dropped by the sweep when it reaches them.
+    fn index_deadlines(&self, transaction: &TransactionModel) {
+        self.index_escrow_deadline(transaction);
+        self.index_payment_due(transaction);
+    }
+
+    fn index_escrow_deadline(&self, transaction: &TransactionModel) {
+        if let Some((due_at, _)) = escrow_due(transaction) {
+            self.storage().escrow_deadlines().insert((due_at, transaction.id), ());
+        }
+    }
+
+    fn index_payment_due(&self, transaction: &TransactionModel) {
+        if let Some(due_at) = payment_due(transaction) {
+            self.storage().payment_schedules().insert((due_at, transaction.id), ());
+        }
+    }
+
+    /// Indexes the next batch of transactions stored before the deadline
+    /// index existed. Returns true once all of them have been visited.
+    pub fn backfill_escrow_deadlines(&self) -> bool {
+        self.backfill_index(ESCROW_DEADLINE_BACKFILL_CURSOR, Self::index_escrow_deadline)
+    }
+
+    /// Same as `backfill_escrow_deadlines`, for the scheduled payment index.
+    pub fn backfill_payment_schedules(&self) -> bool {
+        self.backfill_index(PAYMENT_SCHEDULE_BACKFILL_CURSOR, Self::index_payment_due)
+    }
+
+    fn backfill_index(&self, cursor: &str, index: fn(&Self, &TransactionModel)) -> bool {
+        let start = config::get_or(cursor, 0);
+        if start == u64::MAX {
+            return true;
+        }
+
+        let end = start.saturating_add(INDEX_BACKFILL_BATCH_SIZE);
+        for (_, transaction) in self.storage().transactions().range_while(start, |id, _| *id < end) {
+            index(self, &transaction);
+        }
+
+        let done = self.storage().transactions().last_key().is_none_or(|last| last < end);
+        config::set(cursor, if done { u64::MAX } else { end });
+        done
+    }
+
+    /// Indexes the transactions of the last limit window by sender, for
+    /// canisters upgraded from builds without the index. Returns how many
+    /// were indexed.
+    pub fn index_recent_transactions(&self) -> u64 {
+        let storage = self.storage();
+        let Some(last_id) = storage.transactions().last_key() else {
+            return 0;
+        };
+        if !storage.outgoing_transactions().is_empty() {
+            return 0;
+        }
+
+        let since = now().saturating_sub(LIMIT_MONTHLY_WINDOW);
+        let recent = storage.transactions().range_rev_while(last_id, |_, tx| tx.created_at >= since);
+        for (id, tx) in &recent {
+            storage.outgoing_transactions().insert((tx.from, tx.created_at, *id), ());
+        }
+
+        recent.len() as u64
+    }
+
+    pub(crate) fn save_transaction(&self, transaction: &TransactionModel) {
+        self.storage().transactions().insert(transaction.id, transaction.clone());
+        self.index_deadlines(transaction);
+        self.publish_transaction(transaction);
+    }
+
+    /// Pushes the transaction's new state to every party that is online.
+    fn publish_transaction(&self, transaction: &TransactionModel) {
+        let event = WsEvent::TransactionUpdated(transaction.clone().into());
+        for party in std::iter::once(transaction.from)
+            .chain(std::iter::once(transaction.to))
+            .chain(transaction.escrow_agent)
+        {
+            send_ws_event(party, event.clone());
+        }
+    }
+
     fn get_transaction_model(&self, id: u64) -> Result<TransactionModel, ApiError> {
         self.storage().transactions().get_or_error(&id, &format!("Transaction {}", id))
     }
@@ -718,7 +1950,7 @@ impl TransactionService {
     //         total_received: 0,
     //         total_sent: 0,
     //         last_transaction_id: None,
-    //         updated_at: time(),
+    //         updated_at: now(),
     //     })
     // }
     
@@ -734,7 +1966,7 @@ impl TransactionService {
         
     //     balance.available -= amount;
     //     balance.locked += amount;
-    //     balance.updated_at = time();
+    //     balance.updated_at = now();
         
     //     self.storage().balances().insert(principal, balance);
     //     Ok(())
@@ -751,7 +1983,7 @@ impl TransactionService {
         
     //     balance.locked -= amount;
     //     balance.available += amount;
-    //     balance.updated_at = time();
+    //     balance.updated_at = now();
         
     //     self.storage().balances().insert(principal, balance);
     //     Ok(())
@@ -768,9 +2000,840 @@ impl TransactionService {
     //     }
         
     //     balance.available -= amount;
-    //     balance.updated_at = time();
+    //     balance.updated_at = now();
         
     //     self.storage().balances().insert(principal, balance);
     //     Ok(())
     // }
-}
\ No newline at end of file
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum EscrowTimeout {
+    AutoRelease,
+    Expire,
+    Warn { due_at: u64 },
+}
+
+/// When the escrow's clock runs out and what happens then.
+fn escrow_due(transaction: &TransactionModel) -> Option<(u64, EscrowTimeout)> {
+    let auto_release_after = match &transaction.transaction_type {
+        TransactionType::Escrow { auto_release_after, .. } => *auto_release_after,
+        _ => return None,
+    };
+
+    match transaction.status {
+        TransactionStatus::SubmittedForReview { submitted_at } => {
+            Some((submitted_at.saturating_add(auto_release_after?), EscrowTimeout::AutoRelease))
+        }
+        TransactionStatus::Pending | TransactionStatus::Approved => {
+            Some((transaction.deadline?, EscrowTimeout::Expire))
+        }
+        _ => None,
+    }
+}
+
+/// When a pending standing order next needs to run, counting retries.
+fn payment_due(transaction: &TransactionModel) -> Option<u64> {
+    match (&transaction.status, &transaction.transaction_type) {
+        (TransactionStatus::Pending, TransactionType::ScheduledPayment { schedule }) => {
+            Some(schedule.retry_at.unwrap_or(schedule.next_payment_date))
+        }
+        _ => None,
+    }
+}
+
+fn escrow_timeout(transaction: &TransactionModel, now: u64, warning_window: u64) -> Option<EscrowTimeout> {
+    let (due_at, on_due) = escrow_due(transaction)?;
+
+    if now >= due_at {
+        Some(on_due)
+    } else if now.saturating_add(warning_window) >= due_at && transaction.expiry_notice_for != Some(due_at) {
+        Some(EscrowTimeout::Warn { due_at })
+    } else {
+        None
+    }
+}
+
+/// Numbers the requested milestones and resets their status. Amounts must add
+/// up to the escrowed amount.
+fn prepare_milestones(transaction_type: TransactionType, amount: u64) -> Result<TransactionType, ApiError> {
+    let (release_conditions, auto_release_after, milestones) = match transaction_type {
+        TransactionType::Escrow { release_conditions, auto_release_after, milestones: Some(milestones) } => {
+            (release_conditions, auto_release_after, milestones)
+        }
+        other => return Ok(other),
+    };
+
+    if milestones.is_empty() || milestones.len() > MAX_MILESTONES {
+        return Err(ApiError::ValidationError {
+            field: "milestones".to_string(),
+            message: format!("An escrow must have between 1 and {} milestones", MAX_MILESTONES),
+        });
+    }
+
+    let mut total: u64 = 0;
+    let mut prepared = Vec::with_capacity(milestones.len());
+    for (index, milestone) in milestones.into_iter().enumerate() {
+        validation::validate_amount(milestone.amount, Some(1), None)?;
+        if let Some(due_date) = milestone.due_date {
+            validation::validate_timestamp(due_date, "due_date")?;
+        }
+        total = total.saturating_add(milestone.amount);
+
+        prepared.push(Milestone {
+            id: index as u32 + 1,
+            description: validation::validate_text(
+                &milestone.description,
+                "milestone description",
+                1,
+                MAX_MILESTONE_DESCRIPTION_LENGTH,
+            )?,
+            amount: milestone.amount,
+            due_date: milestone.due_date,
+            status: MilestoneStatus::Pending,
+        });
+    }
+
+    if total != amount {
+        return Err(ApiError::ValidationError {
+            field: "milestones".to_string(),
+            message: format!("Milestone amounts add up to {} but the escrow is for {}", total, amount),
+        });
+    }
+
+    Ok(TransactionType::Escrow {
+        release_conditions,
+        auto_release_after,
+        milestones: Some(prepared),
+    })
+}
+
+fn milestone_mut(transaction: &mut TransactionModel, milestone_id: u32) -> Result<&mut Milestone, ApiError> {
+    let transaction_id = transaction.id;
+    match &mut transaction.transaction_type {
+        TransactionType::Escrow { milestones: Some(milestones), .. } => milestones
+            .iter_mut()
+            .find(|m| m.id == milestone_id)
+            .ok_or_else(|| ApiError::NotFound {
+                resource: format!("Milestone {} of transaction {}", milestone_id, transaction_id),
+            }),
+        _ => Err(ApiError::InvalidState {
+            current_state: "Escrow without milestones".to_string(),
+            required_state: "Milestone escrow".to_string(),
+        }),
+    }
+}
+
+fn ensure_not_milestone_escrow(transaction: &TransactionModel) -> Result<(), ApiError> {
+    if matches!(transaction.transaction_type, TransactionType::Escrow { milestones: Some(_), .. }) {
+        return Err(ApiError::InvalidState {
+            current_state: "Milestone escrow".to_string(),
+            required_state: "Escrow without milestones; use the milestone calls".to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+fn schedule_exhausted(schedule: &PaymentSchedule, payment_date: u64) -> bool {
+    schedule.total_payments.is_some_and(|total| schedule.payments_completed >= total)
+        || schedule.end_date.is_some_and(|end| payment_date > end)
+}
+
+/// The occurrence after `schedule.next_payment_date`, or `None` once the
+/// schedule has nothing left to pay.
+fn next_payment_date(schedule: &PaymentSchedule) -> Option<u64> {
+    let interval = schedule.frequency.interval()?;
+    let next = get_next_occurrence(schedule.next_payment_date, interval);
+
+    if schedule_exhausted(schedule, next) {
+        None
+    } else {
+        Some(next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::security::limits::TierLimiter;
+    use crate::types::limits::TierLimits;
+    use crate::test_utils::*;
+    use crate::types::user::VerificationLevel;
+    use crate::utils::{
+        constants::{MAX_EVIDENCE_FILES_PER_TRANSACTION, NANOS_PER_DAY, NANOS_PER_WEEK},
+        time::advance_mock_time,
+    };
+
+    const WARNING: u64 = NANOS_PER_DAY;
+
+    fn model(status: TransactionStatus, auto_release_after: Option<u64>, deadline: Option<u64>) -> TransactionModel {
+        Transaction {
+            id: 1,
+            transaction_type: TransactionType::Escrow { release_conditions: vec![], auto_release_after, milestones: None },
+            from: sender(),
+            to: recipient(),
+            amount: 10_000,
+            fee: 100,
+            currency: Currency::ICP,
+            description: String::new(),
+            status,
+            escrow_agent: None,
+            created_at: 0,
+            updated_at: 0,
+            completed_at: None,
+            deadline,
+            metadata: TransactionMetadata::default(),
+            agent_fee_bps: None,
+            agent_accepted_at: None,
+        }.into()
+    }
+
+    #[test]
+    fn test_escrow_timeout_classification() {
+        let review = TransactionStatus::SubmittedForReview { submitted_at: 1_000 };
+
+        assert_eq!(escrow_timeout(&model(review.clone(), Some(500), None), 1_499, 0), None);
+        assert_eq!(escrow_timeout(&model(review.clone(), Some(500), None), 1_500, 0), Some(EscrowTimeout::AutoRelease));
+        assert_eq!(escrow_timeout(&model(review.clone(), None, Some(1)), 9_999, 0), None);
+        assert_eq!(
+            escrow_timeout(&model(review, Some(500), None), 1_200, 300),
+            Some(EscrowTimeout::Warn { due_at: 1_500 }),
+        );
+
+        assert_eq!(escrow_timeout(&model(TransactionStatus::Pending, None, Some(2_000)), 2_000, 0), Some(EscrowTimeout::Expire));
+        assert_eq!(escrow_timeout(&model(TransactionStatus::Approved, None, Some(2_000)), 2_001, 0), Some(EscrowTimeout::Expire));
+        assert_eq!(escrow_timeout(&model(TransactionStatus::InEscrow, None, Some(2_000)), 2_001, 0), None);
+
+        let mut warned = model(TransactionStatus::Pending, None, Some(2_000));
+        warned.expiry_notice_for = Some(2_000);
+        assert_eq!(escrow_timeout(&warned, 1_900, 500), None);
+        assert_eq!(escrow_timeout(&warned, 2_000, 500), Some(EscrowTimeout::Expire));
+    }
+
+    #[test]
+    fn test_submitted_escrow_auto_releases() {
+        let mut service = funded_service();
+        let tx = service.create_transaction(sender(), escrow_request(Some(3 * NANOS_PER_DAY), None)).unwrap();
+        service.accept_escrow_terms(tx.id, recipient()).unwrap();
+        service.submit_escrow_work(tx.id, recipient()).unwrap();
+
+        advance_mock_time(2 * NANOS_PER_DAY + 1);
+        let report = service.process_escrow_timeouts(WARNING);
+        assert_eq!(report, EscrowSweepReport { warned: 1, ..Default::default() });
+        assert_eq!(service.process_escrow_timeouts(WARNING), EscrowSweepReport::default());
+
+        advance_mock_time(NANOS_PER_DAY);
+        let report = service.process_escrow_timeouts(WARNING);
+        assert_eq!(report, EscrowSweepReport { released: 1, ..Default::default() });
+
+        let tx = service.get_transaction(tx.id, sender()).unwrap();
+        assert_eq!(tx.status, TransactionStatus::Completed);
+        assert_eq!(available(recipient()), 10_000);
+    }
+
+    #[test]
+    fn test_completion_moves_fee_to_treasury() {
+        let mut service = funded_service();
+        let tx = completed_payment(&mut service);
+
+        let balance = BALANCE_SERVICE.with(|s| s.borrow_mut().get_balance(sender(), &Currency::ICP)).unwrap();
+        assert_eq!(balance.locked, 0);
+        assert_eq!(balance.available, 1_000_000 - tx.amount - tx.fee);
+        assert_eq!(treasury_available(), tx.fee);
+        assert_eq!(crate::TREASURY_SERVICE.with(|s| s.borrow().get_collected_fees()), vec![(Currency::ICP, tx.fee)]);
+    }
+
+    #[test]
+    fn test_referral_reward_is_paid_from_fee() {
+        let referrer = Principal::from_slice(&[3; 29]);
+        crate::REFERRAL_SERVICE.with(|s| {
+            let service = s.borrow();
+            let code = service.generate_code(referrer).unwrap();
+            service.record_referral(sender(), service.resolve_code(&code.code, sender()).unwrap());
+        });
+
+        let mut service = funded_service();
+        let tx = completed_payment(&mut service);
+
+        assert!(available(referrer) > 0);
+        assert_eq!(treasury_available() + available(referrer), tx.fee);
+    }
+
+    #[test]
+    fn test_blocked_sender_cannot_create_transaction() {
+        let service = funded_service();
+        crate::CONTACT_SERVICE.with(|s| s.borrow().block(recipient(), sender())).unwrap();
+
+        assert!(matches!(
+            service.create_transaction(sender(), escrow_request(None, None)),
+            Err(ApiError::Unauthorized { .. })
+        ));
+        assert_eq!(available(sender()), 1_000_000);
+    }
+
+    #[test]
+    fn test_unaccepted_escrow_expires_at_deadline() {
+        let service = funded_service();
+        let deadline = now() + 2 * NANOS_PER_DAY;
+        let tx = service.create_transaction(sender(), escrow_request(None, Some(deadline))).unwrap();
+        assert_eq!(available(sender()), 1_000_000 - tx.amount - tx.fee);
+
+        advance_mock_time(2 * NANOS_PER_DAY);
+        let report = service.process_escrow_timeouts(WARNING);
+        assert_eq!(report, EscrowSweepReport { expired: 1, ..Default::default() });
+
+        let tx = service.get_transaction(tx.id, sender()).unwrap();
+        assert!(matches!(tx.status, TransactionStatus::Failed { .. }));
+        assert_eq!(available(sender()), 1_000_000);
+    }
+
+    #[test]
+    fn test_escrow_deadline_index_drops_settled_entries() {
+        let mut service = funded_service();
+        let deadline = now() + 2 * NANOS_PER_DAY;
+        let accepted = service.create_transaction(sender(), escrow_request(None, Some(deadline))).unwrap();
+        let expiring = service.create_transaction(sender(), escrow_request(None, Some(deadline))).unwrap();
+        service.accept_escrow_terms(accepted.id, recipient()).unwrap();
+        assert_eq!(service.storage().escrow_deadlines().len(), 2);
+
+        advance_mock_time(2 * NANOS_PER_DAY);
+        let report = service.process_escrow_timeouts(WARNING);
+        assert_eq!(report, EscrowSweepReport { expired: 1, ..Default::default() });
+        assert!(matches!(service.get_transaction(expiring.id, sender()).unwrap().status, TransactionStatus::Failed { .. }));
+        assert_eq!(service.get_transaction(accepted.id, sender()).unwrap().status, TransactionStatus::InEscrow);
+        assert!(service.storage().escrow_deadlines().is_empty());
+    }
+
+    #[test]
+    fn test_escrow_sweep_is_batched() {
+        let service = funded_service();
+        let deadline = now() + NANOS_PER_DAY;
+        for _ in 0..=ESCROW_SWEEP_BATCH_SIZE {
+            service.create_transaction(sender(), escrow_request(None, Some(deadline))).unwrap();
+        }
+
+        advance_mock_time(NANOS_PER_DAY);
+        let report = service.process_escrow_timeouts(WARNING);
+        assert_eq!(report, EscrowSweepReport { expired: ESCROW_SWEEP_BATCH_SIZE as u32, ..Default::default() });
+        let report = service.process_escrow_timeouts(WARNING);
+        assert_eq!(report, EscrowSweepReport { expired: 1, ..Default::default() });
+        assert_eq!(available(sender()), 1_000_000);
+    }
+
+    #[test]
+    fn test_escrow_deadline_backfill_indexes_existing_escrows() {
+        let service = funded_service();
+        let deadline = now() + 2 * NANOS_PER_DAY;
+        let tx = service.create_transaction(sender(), escrow_request(None, Some(deadline))).unwrap();
+        service.storage().escrow_deadlines().clear();
+
+        assert!(service.backfill_escrow_deadlines());
+        assert!(service.storage().escrow_deadlines().contains_key(&(deadline, tx.id)));
+        assert!(service.backfill_escrow_deadlines());
+    }
+
+    fn weekly_schedule(total_payments: Option<u32>, end_date: Option<u64>) -> PaymentSchedule {
+        PaymentSchedule {
+            frequency: PaymentFrequency::Weekly,
+            start_date: now(),
+            end_date,
+            amount_per_payment: 5_000,
+            total_payments,
+            payments_completed: 0,
+            next_payment_date: 0,
+            failed_attempts: None,
+            retry_at: None,
+        }
+    }
+
+    fn schedule_of(service: &TransactionService, id: u64) -> (TransactionStatus, PaymentSchedule) {
+        let tx = service.get_transaction(id, sender()).unwrap();
+        match tx.transaction_type {
+            TransactionType::ScheduledPayment { schedule } => (tx.status, schedule),
+            other => panic!("not a scheduled payment: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_next_payment_date_stops_at_end_date() {
+        let start = now();
+        let schedule = PaymentSchedule { next_payment_date: start, ..weekly_schedule(None, Some(start + NANOS_PER_WEEK)) };
+        assert_eq!(next_payment_date(&schedule), Some(start + NANOS_PER_WEEK));
+
+        let last = PaymentSchedule { next_payment_date: start + NANOS_PER_WEEK, ..schedule.clone() };
+        assert_eq!(next_payment_date(&last), None);
+
+        let one_off = PaymentSchedule { frequency: PaymentFrequency::OneTime, ..schedule };
+        assert_eq!(next_payment_date(&one_off), None);
+    }
+
+    #[test]
+    fn test_scheduled_payment_runs_until_total_payments() {
+        let service = funded_service();
+        let parent = service.create_scheduled_payment(
+            sender(), recipient(), weekly_schedule(Some(2), None), "Rent".to_string(), Currency::ICP,
+        ).unwrap();
+        assert_eq!(available(sender()), 1_000_000);
+
+        let report = service.process_scheduled_payments();
+        assert_eq!(report, ScheduledPaymentReport { executed: 1, ..Default::default() });
+        assert_eq!(service.process_scheduled_payments(), ScheduledPaymentReport::default());
+
+        let (status, schedule) = schedule_of(&service, parent.id);
+        assert_eq!(status, TransactionStatus::Pending);
+        assert_eq!(schedule.payments_completed, 1);
+        assert_eq!(schedule.next_payment_date, parent.created_at + NANOS_PER_WEEK);
+
+        advance_mock_time(NANOS_PER_WEEK);
+        let report = service.process_scheduled_payments();
+        assert_eq!(report, ScheduledPaymentReport { executed: 1, finished: 1, ..Default::default() });
+
+        let (status, schedule) = schedule_of(&service, parent.id);
+        assert_eq!(status, TransactionStatus::Completed);
+        assert_eq!(schedule.payments_completed, 2);
+        assert_eq!(available(recipient()), 10_000);
+    }
+
+    #[test]
+    fn test_unfunded_scheduled_payment_retries_then_skips() {
+        let service = TransactionService::new();
+        let parent = service.create_scheduled_payment(
+            sender(), recipient(), weekly_schedule(None, None), "Allowance".to_string(), Currency::ICP,
+        ).unwrap();
+        let first_due = parent.created_at;
+
+        for attempt in 1..=MAX_SCHEDULED_PAYMENT_RETRIES {
+            let report = service.process_scheduled_payments();
+            assert_eq!(report, ScheduledPaymentReport { failed: 1, ..Default::default() });
+
+            let (_, schedule) = schedule_of(&service, parent.id);
+            assert_eq!(schedule.failed_attempts, Some(attempt));
+            assert_eq!(schedule.retry_at, Some(now() + SCHEDULED_PAYMENT_RETRY_DELAY));
+            assert_eq!(schedule.next_payment_date, first_due);
+
+            assert_eq!(service.process_scheduled_payments(), ScheduledPaymentReport::default());
+            advance_mock_time(SCHEDULED_PAYMENT_RETRY_DELAY);
+        }
+
+        service.process_scheduled_payments();
+        let (status, schedule) = schedule_of(&service, parent.id);
+        assert_eq!(status, TransactionStatus::Pending);
+        assert_eq!(schedule.failed_attempts, None);
+        assert_eq!(schedule.payments_completed, 0);
+        assert_eq!(schedule.next_payment_date, first_due + NANOS_PER_WEEK);
+
+        let failures = service.get_user_transactions(recipient(), None, PaginationParams { offset: 0, limit: 100 })
+            .unwrap()
+            .into_iter()
+            .filter(|tx| matches!(tx.status, TransactionStatus::Failed { .. }))
+            .count();
+        assert_eq!(failures, MAX_SCHEDULED_PAYMENT_RETRIES as usize + 1);
+    }
+
+    #[test]
+    fn test_scheduled_payment_sweep_is_batched() {
+        let service = funded_service();
+        for _ in 0..=SCHEDULED_PAYMENT_BATCH_SIZE {
+            service.create_scheduled_payment(
+                sender(), recipient(), weekly_schedule(Some(1), None), "Rent".to_string(), Currency::ICP,
+            ).unwrap();
+        }
+
+        let batch = SCHEDULED_PAYMENT_BATCH_SIZE as u32;
+        let report = service.process_scheduled_payments();
+        assert_eq!(report, ScheduledPaymentReport { executed: batch, finished: batch, ..Default::default() });
+        let report = service.process_scheduled_payments();
+        assert_eq!(report, ScheduledPaymentReport { executed: 1, finished: 1, ..Default::default() });
+        assert_eq!(available(recipient()), 5_000 * (SCHEDULED_PAYMENT_BATCH_SIZE as u64 + 1));
+    }
+
+    #[test]
+    fn test_scheduled_payments_stop_at_daily_limit() {
+        TierLimiter::new().set_limits(admin(), VerificationLevel::Basic, Currency::ICP, TierLimits {
+            max_per_transaction: 5_000,
+            daily_volume: 12_000,
+            monthly_volume: 100_000,
+            daily_withdrawal: 1_000,
+            monthly_withdrawal: 1_000,
+        }).unwrap();
+        let service = funded_service();
+        let parents: Vec<Transaction> = (0..3)
+            .map(|_| service.create_scheduled_payment(
+                sender(), recipient(), weekly_schedule(None, None), "Rent".to_string(), Currency::ICP,
+            ).unwrap())
+            .collect();
+
+        let report = service.process_scheduled_payments();
+        assert_eq!(report, ScheduledPaymentReport { executed: 2, failed: 1, ..Default::default() });
+        assert_eq!(available(recipient()), 10_000);
+
+        let (_, schedule) = schedule_of(&service, parents[2].id);
+        assert_eq!(schedule.failed_attempts, Some(1));
+        assert_eq!(schedule.payments_completed, 0);
+    }
+
+    #[test]
+    fn test_frozen_payer_is_not_debited() {
+        register(sender());
+        let service = funded_service();
+        let parent = service.create_scheduled_payment(
+            sender(), recipient(), weekly_schedule(None, None), "Rent".to_string(), Currency::ICP,
+        ).unwrap();
+        USER_SERVICE.with(|s| s.borrow().freeze_account(sender(), "Chargeback".to_string(), admin())).unwrap();
+
+        let report = service.process_scheduled_payments();
+        assert_eq!(report, ScheduledPaymentReport { failed: 1, ..Default::default() });
+        assert_eq!(available(sender()), 1_000_000);
+        assert_eq!(available(recipient()), 0);
+
+        let (_, schedule) = schedule_of(&service, parent.id);
+        assert_eq!(schedule.failed_attempts, Some(1));
+    }
+
+    #[test]
+    fn test_blocked_recipient_stops_scheduled_payment() {
+        let service = funded_service();
+        let parent = service.create_scheduled_payment(
+            sender(), recipient(), weekly_schedule(None, None), "Rent".to_string(), Currency::ICP,
+        ).unwrap();
+        crate::CONTACT_SERVICE.with(|s| s.borrow().block(recipient(), sender())).unwrap();
+
+        let report = service.process_scheduled_payments();
+        assert_eq!(report, ScheduledPaymentReport { failed: 1, ..Default::default() });
+        assert_eq!(available(sender()), 1_000_000);
+        assert_eq!(available(recipient()), 0);
+
+        let (_, schedule) = schedule_of(&service, parent.id);
+        assert_eq!(schedule.failed_attempts, Some(1));
+    }
+
+    #[test]
+    fn test_cancel_scheduled_payment_releases_nothing() {
+        let service = funded_service();
+        let parent = service.create_scheduled_payment(
+            sender(), recipient(), weekly_schedule(None, None), "Gym".to_string(), Currency::ICP,
+        ).unwrap();
+
+        service.cancel_transaction(parent.id, sender(), "No longer needed".to_string()).unwrap();
+        assert_eq!(service.process_scheduled_payments(), ScheduledPaymentReport::default());
+        assert_eq!(available(sender()), 1_000_000);
+        assert!(service.storage().payment_schedules().is_empty());
+    }
+
+    #[test]
+    fn test_payment_schedule_backfill_indexes_pending_schedules() {
+        let service = funded_service();
+        let parent = service.create_scheduled_payment(
+            sender(), recipient(), weekly_schedule(None, None), "Rent".to_string(), Currency::ICP,
+        ).unwrap();
+        service.storage().payment_schedules().clear();
+        assert_eq!(service.process_scheduled_payments(), ScheduledPaymentReport::default());
+
+        assert!(service.backfill_payment_schedules());
+        assert!(service.storage().payment_schedules().contains_key(&(parent.created_at, parent.id)));
+        assert_eq!(service.process_scheduled_payments(), ScheduledPaymentReport { executed: 1, ..Default::default() });
+    }
+
+    #[test]
+    fn test_agent_fee_is_capped_and_needs_agent() {
+        let service = funded_service();
+
+        let without_agent = CreateTransactionRequest { agent_fee_bps: Some(100), ..escrow_request(None, None) };
+        assert!(matches!(
+            service.create_transaction(sender(), without_agent),
+            Err(ApiError::ValidationError { .. })
+        ));
+
+        let too_high = CreateTransactionRequest {
+            escrow_agent: Some(agent()),
+            agent_fee_bps: Some(DEFAULT_MAX_AGENT_FEE_BPS + 1),
+            ..escrow_request(None, None)
+        };
+        assert!(matches!(
+            service.create_transaction(sender(), too_high),
+            Err(ApiError::ValidationError { .. })
+        ));
+    }
+
+    #[test]
+    fn test_agent_arbitration_splits_funds_after_fee() {
+        let mut service = funded_service();
+        let tx = disputed_escrow(&mut service, agent_escrow_request());
+
+        assert!(matches!(
+            service.start_dispute_review(tx.id, agent()),
+            Err(ApiError::Unauthorized { .. })
+        ));
+
+        service.accept_agent_role(tx.id, agent()).unwrap();
+        let reviewed = service.start_dispute_review(tx.id, agent()).unwrap();
+        assert!(matches!(reviewed.status, TransactionStatus::UnderReview { reviewer, .. } if reviewer == agent()));
+
+        let resolution = DisputeResolution::SplitBetweenParties { sender_percentage: 50 };
+        let resolved = service.agent_resolve_dispute(tx.id, resolution, agent()).unwrap();
+        assert!(matches!(resolved.status, TransactionStatus::Resolved { resolved_by, .. } if resolved_by == agent()));
+
+        assert_eq!(available(agent()), 200);
+        assert_eq!(available(recipient()), 4_900);
+        assert_eq!(available(sender()), 1_000_000 - 10_000 - tx.fee + 4_900);
+        assert_eq!(treasury_available(), tx.fee);
+    }
+
+    #[test]
+    fn test_accepted_agent_can_release_escrow() {
+        let mut service = funded_service();
+        let request = CreateTransactionRequest { escrow_agent: Some(agent()), ..escrow_request(None, None) };
+        let tx = service.create_transaction(sender(), request).unwrap();
+        service.accept_escrow_terms(tx.id, recipient()).unwrap();
+        service.submit_escrow_work(tx.id, recipient()).unwrap();
+
+        assert!(matches!(
+            service.complete_transaction(tx.id, agent()),
+            Err(ApiError::Unauthorized { .. })
+        ));
+        assert!(matches!(
+            service.complete_transaction(tx.id, recipient()),
+            Err(ApiError::Unauthorized { .. })
+        ));
+
+        service.accept_agent_role(tx.id, agent()).unwrap();
+        let completed = service.complete_transaction(tx.id, agent()).unwrap();
+        assert_eq!(completed.status, TransactionStatus::Completed);
+        assert_eq!(available(recipient()), 10_000);
+    }
+
+    fn milestone(description: &str, amount: u64) -> Milestone {
+        Milestone {
+            id: 0,
+            description: description.to_string(),
+            amount,
+            due_date: None,
+            status: MilestoneStatus::Pending,
+        }
+    }
+
+    fn milestone_request(milestones: Vec<Milestone>) -> CreateTransactionRequest {
+        CreateTransactionRequest {
+            transaction_type: TransactionType::Escrow {
+                release_conditions: vec![],
+                auto_release_after: None,
+                milestones: Some(milestones),
+            },
+            escrow_agent: Some(agent()),
+            agent_fee_bps: Some(100),
+            ..escrow_request(None, None)
+        }
+    }
+
+    fn milestones_of(tx: &Transaction) -> Vec<Milestone> {
+        match &tx.transaction_type {
+            TransactionType::Escrow { milestones: Some(milestones), .. } => milestones.clone(),
+            other => panic!("not a milestone escrow: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_milestone_amounts_must_match_total() {
+        let service = funded_service();
+
+        let short = milestone_request(vec![milestone("Sketches", 4_000), milestone("Final", 5_000)]);
+        assert!(matches!(
+            service.create_transaction(sender(), short),
+            Err(ApiError::ValidationError { .. })
+        ));
+
+        let tx = service
+            .create_transaction(sender(), milestone_request(vec![milestone("Sketches", 4_000), milestone("Final", 6_000)]))
+            .unwrap();
+        let ids: Vec<u32> = milestones_of(&tx).iter().map(|m| m.id).collect();
+        assert_eq!(ids, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_milestones_release_separately_then_complete() {
+        let mut service = funded_service();
+        let tx = service
+            .create_transaction(sender(), milestone_request(vec![milestone("Sketches", 4_000), milestone("Final", 6_000)]))
+            .unwrap();
+        service.accept_escrow_terms(tx.id, recipient()).unwrap();
+
+        assert!(matches!(
+            service.submit_escrow_work(tx.id, recipient()),
+            Err(ApiError::InvalidState { .. })
+        ));
+        assert!(matches!(
+            service.approve_milestone(tx.id, 1, sender()),
+            Err(ApiError::InvalidState { .. })
+        ));
+
+        service.submit_milestone_work(tx.id, 1, recipient()).unwrap();
+        let tx = service.approve_milestone(tx.id, 1, sender()).unwrap();
+        assert_eq!(available(recipient()), 4_000);
+        assert_eq!(tx.status, TransactionStatus::InEscrow);
+
+        service.submit_milestone_work(tx.id, 2, recipient()).unwrap();
+        let tx = service.approve_milestone(tx.id, 2, sender()).unwrap();
+        assert_eq!(available(recipient()), 10_000);
+        assert_eq!(tx.status, TransactionStatus::Completed);
+        assert!(milestones_of(&tx).iter().all(|m| m.status.is_settled()));
+    }
+
+    #[test]
+    fn test_fully_disputed_milestone_escrow_fits_storage_bound() {
+        use ic_stable_structures::Storable;
+
+        let milestones = (1..=MAX_MILESTONES as u32)
+            .map(|id| Milestone {
+                id,
+                description: "d".repeat(MAX_MILESTONE_DESCRIPTION_LENGTH),
+                amount: u64::MAX,
+                due_date: Some(u64::MAX),
+                status: MilestoneStatus::Disputed {
+                    reason: "r".repeat(MAX_MILESTONE_DISPUTE_REASON_LENGTH),
+                    disputed_by: sender(),
+                    disputed_at: u64::MAX,
+                },
+            })
+            .collect();
+        let mut transaction = model(TransactionStatus::Pending, Some(u64::MAX), Some(u64::MAX));
+        transaction.transaction_type = TransactionType::Escrow {
+            release_conditions: vec![],
+            auto_release_after: Some(u64::MAX),
+            milestones: Some(milestones),
+        };
+        transaction.description = "t".repeat(500);
+        transaction.escrow_agent = Some(recipient());
+        transaction.agent_fee_bps = Some(u64::MAX);
+        transaction.agent_accepted_at = Some(u64::MAX);
+        transaction.metadata.attachments = (0..MAX_EVIDENCE_FILES_PER_TRANSACTION as u64)
+            .map(|id| Attachment {
+                id: u64::MAX.to_string(),
+                name: "n".repeat(255),
+                mime_type: "application/pdf".to_string(),
+                size: u64::MAX,
+                url: format!("evidence:{}", u64::MAX - id),
+                uploaded_by: sender(),
+                uploaded_at: u64::MAX,
+            })
+            .collect();
+
+        let size = transaction.to_bytes().len() as u32;
+        assert!(size <= TransactionModel::BOUND.max_size(), "{} bytes", size);
+    }
+
+    #[test]
+    fn test_disputed_milestone_resolved_by_agent() {
+        let mut service = funded_service();
+        let tx = service
+            .create_transaction(sender(), milestone_request(vec![milestone("Sketches", 4_000), milestone("Final", 6_000)]))
+            .unwrap();
+        service.accept_escrow_terms(tx.id, recipient()).unwrap();
+        service.accept_agent_role(tx.id, agent()).unwrap();
+
+        service.submit_milestone_work(tx.id, 2, recipient()).unwrap();
+        service.dispute_milestone(tx.id, 2, sender(), "Wrong colours".to_string()).unwrap();
+
+        assert!(matches!(
+            service.resolve_milestone_dispute(tx.id, 2, DisputeResolution::ReleaseToRecipient, recipient()),
+            Err(ApiError::Unauthorized { .. })
+        ));
+
+        let tx = service
+            .resolve_milestone_dispute(
+                tx.id,
+                2,
+                DisputeResolution::SplitBetweenParties { sender_percentage: 50 },
+                agent(),
+            )
+            .unwrap();
+
+        // 1% of the 6_000 milestone goes to the agent, the rest is split evenly.
+        assert_eq!(available(agent()), 60);
+        assert_eq!(available(recipient()), 2_970);
+        assert_eq!(tx.status, TransactionStatus::InEscrow);
+        assert!(matches!(milestones_of(&tx)[1].status, MilestoneStatus::Resolved { .. }));
+    }
+
+    #[test]
+    fn test_partial_then_full_refund() {
+        let mut service = funded_service();
+        let tx = completed_payment(&mut service);
+        let sender_before = available(sender());
+
+        assert!(matches!(
+            service.refund_transaction(tx.id, 1_000, "Late".to_string(), sender()),
+            Err(ApiError::Unauthorized { .. })
+        ));
+
+        let first = service.refund_transaction(tx.id, 4_000, "Late delivery".to_string(), recipient()).unwrap();
+        assert_eq!(first.transaction_type, TransactionType::Refund { original_transaction_id: tx.id });
+        assert_eq!((first.from, first.to), (recipient(), sender()));
+        assert_eq!(
+            service.get_transaction_unchecked(tx.id).unwrap().status,
+            TransactionStatus::PartiallyRefunded { refunded_amount: 4_000, refund_transaction_ids: vec![first.id] }
+        );
+
+        assert!(matches!(
+            service.refund_transaction(tx.id, 6_001, "Too much".to_string(), recipient()),
+            Err(ApiError::ValidationError { .. })
+        ));
+
+        let second = service.refund_transaction(tx.id, 6_000, "Cancelled order".to_string(), recipient()).unwrap();
+        assert_eq!(
+            service.get_transaction_unchecked(tx.id).unwrap().status,
+            TransactionStatus::Refunded {
+                refund_transaction_id: second.id,
+                refund_transaction_ids: vec![first.id, second.id],
+                refunded_at: now(),
+            }
+        );
+        assert_eq!(available(sender()), sender_before + 10_000);
+        assert_eq!(available(recipient()), 0);
+
+        assert!(matches!(
+            service.refund_transaction(tx.id, 1, "Again".to_string(), recipient()),
+            Err(ApiError::InvalidState { .. })
+        ));
+    }
+
+    #[test]
+    fn test_refund_count_is_capped() {
+        let mut service = funded_service();
+        let tx = completed_payment(&mut service);
+
+        for _ in 1..MAX_REFUNDS_PER_TRANSACTION {
+            service.refund_transaction(tx.id, 100, "Partial".to_string(), recipient()).unwrap();
+        }
+        assert!(matches!(
+            service.refund_transaction(tx.id, 100, "Partial".to_string(), recipient()),
+            Err(ApiError::ValidationError { .. })
+        ));
+
+        let last = service.refund_transaction(tx.id, tx.amount - 900, "Rest".to_string(), recipient()).unwrap();
+        match service.get_transaction_unchecked(tx.id).unwrap().status {
+            TransactionStatus::Refunded { refund_transaction_id, refund_transaction_ids, .. } => {
+                assert_eq!(refund_transaction_id, last.id);
+                assert_eq!(refund_transaction_ids.len(), MAX_REFUNDS_PER_TRANSACTION);
+                assert_eq!(refund_transaction_ids.last(), Some(&last.id));
+            }
+            other => panic!("not refunded: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_refund_records_history_for_both_parties() {
+        let mut service = funded_service();
+        let tx = completed_payment(&mut service);
+        let refund = service.refund_transaction(tx.id, 2_500, "Partial refund".to_string(), recipient()).unwrap();
+
+        for (principal, change) in [(recipient(), -2_500i64), (sender(), 2_500)] {
+            let history = BALANCE_SERVICE.with(|s| {
+                s.borrow().get_balance_history(principal, None, None, Some(u64::MAX), PaginationParams { offset: 0, limit: 100 })
+            }).unwrap();
+            let entry = history.iter().find(|e| e.transaction_id == refund.id).unwrap();
+            assert_eq!(entry.change, change);
+            assert_eq!(entry.transaction_type, TransactionType::Refund { original_transaction_id: tx.id });
+        }
+    }
+}
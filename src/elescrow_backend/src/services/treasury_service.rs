@@ -0,0 +1,283 @@
+use candid::Principal;
+
+use crate::types::{
+    errors::ApiError,
+    ledger::Account,
+    transaction::Currency,
+    treasury::*,
+};
+use crate::models::transaction::TransactionModel;
+use crate::storage::{
+    stable_storage::StableStorage,
+    memory::MemoryRegion,
+    config::{StableCounter, TREASURY_ENTRY_ID_COUNTER},
+};
+use crate::security::validation;
+use crate::utils::time::now;
+
+/// Platform fees collected from transactions. Fees leave the payer's locked
+/// balance when a transaction settles and are held here, per currency, until
+/// an admin withdraws them to a ledger account.
+pub struct TreasuryService {
+    balances: StableStorage<String, TreasuryBalance>,
+    entries: StableStorage<u64, TreasuryEntry>,
+    next_id: StableCounter,
+}
+
+impl TreasuryService {
+    pub fn new() -> Self {
+        Self {
+            balances: StableStorage::new(MemoryRegion::TreasuryBalances),
+            entries: StableStorage::new(MemoryRegion::TreasuryEntries),
+            next_id: StableCounter::new(TREASURY_ENTRY_ID_COUNTER),
+        }
+    }
+
+    /// Books the fee of `transaction`, which the caller has already taken out
+    /// of the sender's locked funds.
+    pub fn record_fee(&self, transaction: &TransactionModel, source: FeeSource) {
+        if transaction.fee == 0 {
+            return;
+        }
+
+        let mut balance = self.get_balance(&transaction.currency);
+        balance.available = balance.available.saturating_add(transaction.fee);
+        balance.total_collected = balance.total_collected.saturating_add(transaction.fee);
+        balance.updated_at = now();
+        self.balances.insert(transaction.currency.code(), balance);
+
+        self.add_entry(
+            TreasuryEntryKind::FeeCollected { transaction_id: transaction.id, source },
+            &transaction.currency,
+            transaction.fee,
+            transaction.from,
+        );
+    }
+
+    /// Pays `amount` of the fee booked for `transaction` out to `referrer`.
+    /// The caller credits the referrer; this only takes it off the treasury.
+    pub fn record_referral_reward(
+        &self,
+        transaction: &TransactionModel,
+        referrer: Principal,
+        amount: u64,
+    ) -> Result<(), ApiError> {
+        let mut balance = self.get_balance(&transaction.currency);
+        if balance.available < amount {
+            return Err(ApiError::InsufficientFunds {
+                available: balance.available,
+                required: amount,
+            });
+        }
+
+        balance.available -= amount;
+        balance.updated_at = now();
+        self.balances.insert(transaction.currency.code(), balance);
+
+        self.add_entry(
+            TreasuryEntryKind::ReferralReward { transaction_id: transaction.id },
+            &transaction.currency,
+            amount,
+            referrer,
+        );
+
+        Ok(())
+    }
+
+    /// Reserves `amount` for a withdrawal while the ledger transfer is in
+    /// flight, so it cannot be withdrawn twice.
+    pub fn begin_withdrawal(&self, currency: &Currency, amount: u64) -> Result<TreasuryBalance, ApiError> {
+        validation::validate_amount(amount, Some(1), None)?;
+        let mut balance = self.get_balance(currency);
+
+        if balance.available < amount {
+            return Err(ApiError::InsufficientFunds {
+                available: balance.available,
+                required: amount,
+            });
+        }
+
+        balance.available -= amount;
+        balance.pending_withdrawal = balance.pending_withdrawal.saturating_add(amount);
+        balance.updated_at = now();
+        self.balances.insert(currency.code(), balance.clone());
+
+        Ok(balance)
+    }
+
+    pub fn complete_withdrawal(
+        &self,
+        admin: Principal,
+        currency: &Currency,
+        amount: u64,
+        to: Account,
+        ledger_block: u64,
+    ) -> TreasuryBalance {
+        let mut balance = self.get_balance(currency);
+        balance.pending_withdrawal = balance.pending_withdrawal.saturating_sub(amount);
+        balance.total_withdrawn = balance.total_withdrawn.saturating_add(amount);
+        balance.updated_at = now();
+        self.balances.insert(currency.code(), balance.clone());
+
+        self.add_entry(TreasuryEntryKind::Withdrawal { to, ledger_block }, currency, amount, admin);
+
+        balance
+    }
+
+    /// Returns the reservation to the available balance after the ledger
+    /// rejected the transfer.
+    pub fn fail_withdrawal(&self, currency: &Currency, amount: u64) -> TreasuryBalance {
+        let mut balance = self.get_balance(currency);
+        let released = amount.min(balance.pending_withdrawal);
+        balance.pending_withdrawal -= released;
+        balance.available = balance.available.saturating_add(released);
+        balance.updated_at = now();
+        self.balances.insert(currency.code(), balance.clone());
+
+        balance
+    }
+
+    pub fn get_balance(&self, currency: &Currency) -> TreasuryBalance {
+        self.balances.get(&currency.code()).unwrap_or_else(|| TreasuryBalance {
+            currency: currency.clone(),
+            available: 0,
+            pending_withdrawal: 0,
+            total_collected: 0,
+            total_withdrawn: 0,
+            updated_at: now(),
+        })
+    }
+
+    pub fn get_balances(&self) -> Vec<TreasuryBalance> {
+        self.balances.values()
+    }
+
+    /// Fee and withdrawal totals per currency for entries recorded between
+    /// `start` and `end`, inclusive.
+    pub fn get_fee_report(&self, start: u64, end: u64, currency: Option<Currency>) -> Result<Vec<FeeReport>, ApiError> {
+        if start > end {
+            return Err(ApiError::ValidationError {
+                field: "start".to_string(),
+                message: "Report period must start before it ends".to_string(),
+            });
+        }
+
+        let mut reports: Vec<FeeReport> = vec![];
+        let entries = self.entries.filter(|_, entry| {
+            entry.timestamp >= start
+                && entry.timestamp <= end
+                && currency.as_ref().is_none_or(|c| &entry.currency == c)
+        });
+
+        for (_, entry) in entries {
+            let index = match reports.iter().position(|r| r.currency == entry.currency) {
+                Some(index) => index,
+                None => {
+                    reports.push(FeeReport {
+                        currency: entry.currency.clone(),
+                        period_start: start,
+                        period_end: end,
+                        fees_collected: 0,
+                        fee_count: 0,
+                        withdrawn: 0,
+                        withdrawal_count: 0,
+                        referral_rewards: 0,
+                    });
+                    reports.len() - 1
+                }
+            };
+            let report = &mut reports[index];
+            match entry.kind {
+                TreasuryEntryKind::FeeCollected { .. } => {
+                    report.fees_collected = report.fees_collected.saturating_add(entry.amount);
+                    report.fee_count += 1;
+                }
+                TreasuryEntryKind::Withdrawal { .. } => {
+                    report.withdrawn = report.withdrawn.saturating_add(entry.amount);
+                    report.withdrawal_count += 1;
+                }
+                TreasuryEntryKind::ReferralReward { .. } => {
+                    report.referral_rewards = report.referral_rewards.saturating_add(entry.amount);
+                }
+            }
+        }
+
+        Ok(reports)
+    }
+
+    /// Fees collected since the treasury started, per currency.
+    pub fn get_collected_fees(&self) -> Vec<(Currency, u64)> {
+        self.balances
+            .values()
+            .into_iter()
+            .map(|balance| (balance.currency, balance.total_collected))
+            .collect()
+    }
+
+    fn add_entry(&self, kind: TreasuryEntryKind, currency: &Currency, amount: u64, principal: Principal) {
+        let id = self.next_id.next();
+        self.entries.insert(id, TreasuryEntry {
+            id,
+            kind,
+            currency: currency.clone(),
+            amount,
+            principal,
+            timestamp: now(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::transaction::{Transaction, TransactionMetadata, TransactionStatus, TransactionType};
+    use crate::utils::{constants::NANOS_PER_DAY, time::advance_mock_time};
+
+    fn completed(id: u64, fee: u64, currency: Currency) -> TransactionModel {
+        Transaction {
+            id,
+            transaction_type: TransactionType::DirectPayment,
+            from: Principal::from_slice(&[1; 29]),
+            to: Principal::from_slice(&[2; 29]),
+            amount: fee * 100,
+            fee,
+            currency,
+            description: String::new(),
+            status: TransactionStatus::Completed,
+            escrow_agent: None,
+            created_at: 0,
+            updated_at: 0,
+            completed_at: Some(0),
+            deadline: None,
+            metadata: TransactionMetadata::default(),
+            agent_fee_bps: None,
+            agent_accepted_at: None,
+        }.into()
+    }
+
+    #[test]
+    fn test_fee_report_by_period_and_currency() {
+        let service = TreasuryService::new();
+        let day_one = now();
+        service.record_fee(&completed(1, 100, Currency::ICP), FeeSource::Completion);
+        service.record_fee(&completed(2, 0, Currency::ICP), FeeSource::Completion);
+        service.record_fee(&completed(3, 40, Currency::USDT), FeeSource::DisputeResolution);
+
+        advance_mock_time(NANOS_PER_DAY);
+        service.record_fee(&completed(4, 60, Currency::ICP), FeeSource::ScheduledPayment);
+        service.begin_withdrawal(&Currency::ICP, 120).unwrap();
+        service.complete_withdrawal(Principal::anonymous(), &Currency::ICP, 120, Account::new(Principal::anonymous()), 7);
+
+        let first_day = service.get_fee_report(day_one, day_one + NANOS_PER_DAY - 1, Some(Currency::ICP)).unwrap();
+        assert_eq!(first_day.len(), 1);
+        assert_eq!((first_day[0].fees_collected, first_day[0].fee_count), (100, 1));
+
+        let all = service.get_fee_report(0, u64::MAX, None).unwrap();
+        let icp = all.iter().find(|r| r.currency == Currency::ICP).unwrap();
+        assert_eq!((icp.fees_collected, icp.fee_count, icp.withdrawn, icp.withdrawal_count), (160, 2, 120, 1));
+        assert_eq!(all.iter().find(|r| r.currency == Currency::USDT).unwrap().fees_collected, 40);
+
+        assert_eq!(service.get_balance(&Currency::ICP).available, 40);
+        assert!(matches!(service.get_fee_report(2, 1, None), Err(ApiError::ValidationError { .. })));
+    }
+}
@@ -1,6 +1,5 @@
 use candid::Principal;
-use ic_cdk::api::time;
-use std::cell::RefCell;
+use crate::utils::time::now;
 
 use crate::types::{
     errors::ApiError,
@@ -12,28 +11,23 @@ use crate::storage::{
     stable_storage::StableStorage,
     memory::MemoryRegion,
 };
-use crate::security::{
-    validation,
-    audit::AuditLogger,
-};
+use crate::security::validation;
 
 use crate::types::common::AuditAction;
+use crate::AUDIT_LOGGER;
 
 pub struct UserService {
     users: StableStorage<Principal, UserModel>,
-    usernames: StableStorage<String, Principal>,
-    emails: StableStorage<String, Principal>,
-    
-    audit_logger: RefCell<AuditLogger>,
+    // Usernames and emails share one map; usernames cannot contain '@', so
+    // the two kinds of key never collide.
+    user_index: StableStorage<String, Principal>,
 }
 
 impl UserService {
     pub fn new() -> Self {
         Self {
             users: StableStorage::new(MemoryRegion::Users),
-            usernames: StableStorage::new(MemoryRegion::UserIndex),
-            emails: StableStorage::new(MemoryRegion::UserIndex),
-            audit_logger: RefCell::new(AuditLogger::with_defaults()),
+            user_index: StableStorage::new(MemoryRegion::UserIndex),
         }
     }
     
@@ -60,21 +54,26 @@ impl UserService {
             });
         }
         
-        if self.usernames.contains_key(&request.username) {
+        if self.user_index.contains_key(&request.username) {
             return Err(ApiError::AlreadyExists {
                 resource: "Username".to_string(),
             });
         }
         
         if let Some(email) = &request.email {
-            if self.emails.contains_key(email) {
+            if self.user_index.contains_key(email) {
                 return Err(ApiError::AlreadyExists {
                     resource: "Email".to_string(),
                 });
             }
         }
         
-        let now = time();
+        let referral_code = match &request.referral_code {
+            Some(code) => Some(crate::REFERRAL_SERVICE.with(|s| s.borrow().resolve_code(code, principal))?),
+            None => None,
+        };
+        
+        let now = now();
         let user_model = UserModel {
             principal,
             profile: UserProfile {
@@ -102,18 +101,22 @@ impl UserService {
         };
         
         self.users.insert(principal, user_model.clone());
-        self.usernames.insert(request.username.clone(), principal);
+        self.user_index.insert(request.username.clone(), principal);
         
         if let Some(email) = &request.email {
-            self.emails.insert(email.clone(), principal);
+            self.user_index.insert(email.clone(), principal);
         }
         
-        self.audit_logger.borrow().log(
+        if let Some(code) = referral_code {
+            crate::REFERRAL_SERVICE.with(|s| s.borrow().record_referral(principal, code));
+        }
+        
+        AUDIT_LOGGER.with(|l| l.borrow().log(
             principal,
             AuditAction::UserRegistered,
             &principal.to_text(),
             Some(format!("Username: {}", request.username)),
-        );
+        ));
         
         Ok(user_model.into())
     }
@@ -126,8 +129,18 @@ impl UserService {
         Ok(user_model.into())
     }
     
+    /// Read-only check, safe to call from `inspect_message`.
+    pub fn is_frozen(&self, principal: Principal) -> bool {
+        self.users.get(&principal).is_some_and(|user| user.account.is_frozen)
+    }
+    
+    /// Read-only lookup of the account flags, for authorization checks.
+    pub fn get_account(&self, principal: Principal) -> Option<UserAccount> {
+        self.users.get(&principal).map(|user| user.account)
+    }
+    
     pub fn get_user_by_username(&self, username: &str) -> Result<User, ApiError> {
-        let principal = self.usernames.get_or_error(&username.to_string(), "Username")?;
+        let principal = self.user_index.get_or_error(&username.to_string(), "Username")?;
         self.get_user(principal)
     }
     
@@ -162,9 +175,9 @@ impl UserService {
             validation::validate_email(&email)?;
             
             if let Some(old_email) = &user_model.profile.email {
-                self.emails.remove(old_email);
+                self.user_index.remove(old_email);
             }
-            self.emails.insert(email.clone(), principal);
+            self.user_index.insert(email.clone(), principal);
             user_model.profile.email = Some(email);
         }
         
@@ -173,16 +186,16 @@ impl UserService {
             user_model.profile.phone = Some(phone);
         }
         
-        user_model.profile.updated_at = time();
+        user_model.profile.updated_at = now();
         
         self.users.insert(principal, user_model.clone());
         
-        self.audit_logger.borrow().log(
+        AUDIT_LOGGER.with(|l| l.borrow().log(
             principal,
             AuditAction::UserUpdated,
             &principal.to_text(),
             Some("Profile updated".to_string()),
-        );
+        ));
         
         Ok(user_model.into())
     }
@@ -195,7 +208,7 @@ impl UserService {
         let mut user_model = self.users.get_or_error(&principal, "User")?;
         
         user_model.notification_preferences = preferences;
-        user_model.profile.updated_at = time();
+        user_model.profile.updated_at = now();
         
         self.users.insert(principal, user_model.clone());
         
@@ -210,16 +223,16 @@ impl UserService {
         let mut user_model = self.users.get_or_error(&principal, "User")?;
         
         user_model.security_settings = settings;
-        user_model.profile.updated_at = time();
+        user_model.profile.updated_at = now();
         
         self.users.insert(principal, user_model.clone());
         
-        self.audit_logger.borrow().log(
+        AUDIT_LOGGER.with(|l| l.borrow().log(
             principal,
             AuditAction::UserUpdated,
             &principal.to_text(),
             Some("Security settings updated".to_string()),
-        );
+        ));
         
         Ok(user_model.into())
     }
@@ -233,16 +246,16 @@ impl UserService {
         let mut user_model = self.users.get_or_error(&principal, "User")?;
         
         user_model.account.is_active = false;
-        user_model.profile.updated_at = time();
+        user_model.profile.updated_at = now();
         
         self.users.insert(principal, user_model);
         
-        self.audit_logger.borrow().log(
+        AUDIT_LOGGER.with(|l| l.borrow().log(
             principal,
             AuditAction::UserDeactivated,
             &principal.to_text(),
             None,
-        );
+        ));
         
         Ok(())
     }
@@ -257,16 +270,16 @@ impl UserService {
         }
         
         user_model.account.is_active = true;
-        user_model.profile.updated_at = time();
+        user_model.profile.updated_at = now();
         
         self.users.insert(principal, user_model);
         
-        self.audit_logger.borrow().log(
+        AUDIT_LOGGER.with(|l| l.borrow().log(
             principal,
             AuditAction::UserReactivated,
             &principal.to_text(),
             None,
-        );
+        ));
         
         Ok(())
     }
@@ -335,16 +348,16 @@ impl UserService {
         
         user_model.account.is_frozen = true;
         user_model.account.freeze_reason = Some(reason.clone());
-        user_model.profile.updated_at = time();
+        user_model.profile.updated_at = now();
         
         self.users.insert(principal, user_model);
         
-        self.audit_logger.borrow().log(
+        AUDIT_LOGGER.with(|l| l.borrow().log(
             frozen_by,
             AuditAction::AccountFrozen,
             &principal.to_text(),
             Some(format!("Reason: {}", reason)),
-        );
+        ));
         
         Ok(())
     }
@@ -358,16 +371,16 @@ impl UserService {
         
         user_model.account.is_frozen = false;
         user_model.account.freeze_reason = None;
-        user_model.profile.updated_at = time();
+        user_model.profile.updated_at = now();
         
         self.users.insert(principal, user_model);
         
-        self.audit_logger.borrow().log(
+        AUDIT_LOGGER.with(|l| l.borrow().log(
             unfrozen_by,
             AuditAction::AccountUnfrozen,
             &principal.to_text(),
             None,
-        );
+        ));
         
         Ok(())
     }
@@ -382,23 +395,57 @@ impl UserService {
 
         user.account.is_verified = true;
         user.account.verification_level = verification_level.clone();
-        user.profile.updated_at = time();
+        user.profile.updated_at = now();
 
         self.users.insert(user_principal, user.clone().into());
 
-        self.audit_logger.borrow().log(
+        AUDIT_LOGGER.with(|l| l.borrow().log(
             admin_principal,
             AuditAction::KycStatusUpdated,
             &user_principal.to_string(),
             Some(format!("User verified to level: {:?}", verification_level)),
-        );
+        ));
 
         Ok(user)
     }
 
+    /// Records a KYC transition on the account. `verification_level` is set
+    /// when the transition changes what the user is verified to.
+    pub fn update_kyc_status(
+        &self,
+        principal: Principal,
+        kyc_status: KycStatus,
+        verification_level: Option<VerificationLevel>,
+        changed_by: Principal,
+    ) -> Result<(), ApiError> {
+        let mut user_model = self.users.get_or_error(&principal, "User")?;
+
+        if let Some(level) = verification_level {
+            user_model.account.is_verified = level > VerificationLevel::Basic;
+            user_model.account.verification_level = level;
+        }
+        user_model.account.kyc_status = kyc_status.clone();
+        user_model.profile.updated_at = now();
+
+        let details = format!(
+            "KYC status: {:?}, verification level: {:?}",
+            kyc_status, user_model.account.verification_level,
+        );
+        self.users.insert(principal, user_model);
+
+        AUDIT_LOGGER.with(|l| l.borrow().log(
+            changed_by,
+            AuditAction::KycStatusUpdated,
+            &principal.to_text(),
+            Some(details),
+        ));
+
+        Ok(())
+    }
+
     pub fn get_user_statistics(&self) -> UserStatistics {
         let total_users = self.users.len();
-        let now = time();
+        let now = now();
         let day_ago = now - (24 * 60 * 60 * 1_000_000_000);
         
         let mut active_users = 0;
@@ -428,7 +475,7 @@ impl UserService {
     
     fn update_last_active(&self, principal: Principal) {
         if let Some(mut user) = self.users.get(&principal) {
-            user.profile.last_active = time();
+            user.profile.last_active = now();
             self.users.insert(principal, user);
         }
     }
@@ -0,0 +1,155 @@
+use std::cell::RefCell;
+use crate::storage::{memory::MemoryRegion, stable_storage::StableStorage};
+
+pub const TRANSACTION_ID_COUNTER: &str = "counter.transaction_id";
+pub const NOTIFICATION_ID_COUNTER: &str = "counter.notification_id";
+pub const AUDIT_LOG_ID_COUNTER: &str = "counter.audit_log_id";
+pub const MESSAGE_ID_COUNTER: &str = "counter.message_id";
+pub const THREAD_MESSAGE_ID_COUNTER: &str = "counter.thread_message_id";
+pub const EVIDENCE_ID_COUNTER: &str = "counter.evidence_id";
+pub const KYC_SUBMISSION_ID_COUNTER: &str = "counter.kyc_submission_id";
+pub const TREASURY_ENTRY_ID_COUNTER: &str = "counter.treasury_entry_id";
+pub const FEE_PROMOTION_ID_COUNTER: &str = "counter.fee_promotion_id";
+
+pub const FEE_PERCENTAGE_BPS: &str = "config.fee_percentage_bps";
+pub const MAX_AGENT_FEE_BPS: &str = "config.max_agent_fee_bps";
+pub const REFERRAL_FEE_SHARE_BPS: &str = "config.referral.fee_share_bps";
+pub const REFERRAL_REWARDED_TRANSACTIONS: &str = "config.referral.rewarded_transactions";
+
+// Next transaction ID to visit while indexing transactions stored by older
+// builds; `u64::MAX` once done.
+pub const ESCROW_DEADLINE_BACKFILL_CURSOR: &str = "backfill.escrow_deadlines";
+pub const PAYMENT_SCHEDULE_BACKFILL_CURSOR: &str = "backfill.payment_schedules";
+// Message IDs below the end are added to conversation lists by the backfill;
+// those below the cursor already have been.
+pub const CONVERSATION_BACKFILL_CURSOR: &str = "backfill.user_conversations";
+pub const CONVERSATION_BACKFILL_END: &str = "backfill.user_conversations.end";
+
+thread_local! {
+    // Single map per region: two StableBTreeMap handles over the same memory
+    // would each cache their own root and length and corrupt each other.
+    static CONFIG: RefCell<StableStorage<String, u64>> =
+        RefCell::new(StableStorage::new(MemoryRegion::Configuration));
+}
+
+pub fn get(key: &str) -> Option<u64> {
+    CONFIG.with(|c| c.borrow().get(&key.to_string()))
+}
+
+pub fn get_or(key: &str, default: u64) -> u64 {
+    get(key).unwrap_or(default)
+}
+
+pub fn set(key: &str, value: u64) {
+    CONFIG.with(|c| c.borrow().insert(key.to_string(), value));
+}
+
+pub fn remove(key: &str) {
+    CONFIG.with(|c| c.borrow().remove(&key.to_string()));
+}
+
+pub fn entries() -> Vec<(String, u64)> {
+    CONFIG.with(|c| c.borrow().entries())
+}
+
+/// Monotonic ID generator whose position lives in the `Configuration` region,
+/// so IDs keep increasing across canister upgrades.
+#[derive(Clone, Copy, Debug)]
+pub struct StableCounter {
+    key: &'static str,
+}
+
+impl StableCounter {
+    pub const fn new(key: &'static str) -> Self {
+        Self { key }
+    }
+
+    pub fn next(&self) -> u64 {
+        let current = self.peek();
+        set(self.key, current + 1);
+        current
+    }
+
+    pub fn peek(&self) -> u64 {
+        get_or(self.key, 1)
+    }
+
+    /// Moves the counter past `last_used` if it is not already. Used after an
+    /// upgrade to heal counters that were never persisted by older builds.
+    pub fn ensure_after(&self, last_used: Option<u64>) {
+        if let Some(last_used) = last_used {
+            if self.peek() <= last_used {
+                set(self.key, last_used + 1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn simulate_upgrade() {
+        // Heap state is dropped on upgrade; only the stable memory survives.
+        CONFIG.with(|c| *c.borrow_mut() = StableStorage::new(MemoryRegion::Configuration));
+    }
+
+    #[test]
+    fn test_counter_survives_upgrade() {
+        let counter = StableCounter::new(TRANSACTION_ID_COUNTER);
+        assert_eq!(counter.next(), 1);
+        assert_eq!(counter.next(), 2);
+
+        simulate_upgrade();
+
+        let counter = StableCounter::new(TRANSACTION_ID_COUNTER);
+        assert_eq!(counter.next(), 3);
+
+        simulate_upgrade();
+        simulate_upgrade();
+
+        assert_eq!(StableCounter::new(TRANSACTION_ID_COUNTER).peek(), 4);
+    }
+
+    #[test]
+    fn test_counters_are_independent() {
+        let transactions = StableCounter::new(TRANSACTION_ID_COUNTER);
+        let messages = StableCounter::new(MESSAGE_ID_COUNTER);
+
+        assert_eq!(transactions.next(), 1);
+        assert_eq!(transactions.next(), 2);
+        assert_eq!(messages.next(), 1);
+
+        simulate_upgrade();
+
+        assert_eq!(transactions.next(), 3);
+        assert_eq!(messages.next(), 2);
+        assert_eq!(StableCounter::new(NOTIFICATION_ID_COUNTER).next(), 1);
+        assert_eq!(StableCounter::new(AUDIT_LOG_ID_COUNTER).next(), 1);
+    }
+
+    #[test]
+    fn test_ensure_after_heals_legacy_counters() {
+        let counter = StableCounter::new(NOTIFICATION_ID_COUNTER);
+
+        counter.ensure_after(None);
+        assert_eq!(counter.peek(), 1);
+
+        counter.ensure_after(Some(41));
+        assert_eq!(counter.next(), 42);
+
+        counter.ensure_after(Some(10));
+        assert_eq!(counter.next(), 43);
+    }
+
+    #[test]
+    fn test_config_values_survive_upgrade() {
+        assert_eq!(get_or(FEE_PERCENTAGE_BPS, 100), 100);
+
+        set(FEE_PERCENTAGE_BPS, 250);
+        simulate_upgrade();
+
+        assert_eq!(get(FEE_PERCENTAGE_BPS), Some(250));
+        assert_eq!(entries(), vec![(FEE_PERCENTAGE_BPS.to_string(), 250)]);
+    }
+}
@@ -4,6 +4,8 @@ use std::cell::RefCell;
 
 pub type Memory = VirtualMemory<DefaultMemoryImpl>;
 
+pub const WASM_PAGE_SIZE: u64 = 65_536;
+
 #[repr(u8)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum MemoryRegion {
@@ -26,6 +28,72 @@ pub enum MemoryRegion {
     UserTransactionsData = 16,
     Messages = 17,
     ConversationIndex = 18,
+    CurrencyBalances = 19,
+    Ledgers = 20,
+    Roles = 21,
+    Evidence = 22,
+    EvidenceChunks = 23,
+    KycSubmissions = 24,
+    ReferralCodes = 25,
+    Referrals = 26,
+    TreasuryBalances = 27,
+    TreasuryEntries = 28,
+    FeeRules = 29,
+    FeePromotions = 30,
+    TransactionThreads = 31,
+    MessageRevisions = 32,
+    Contacts = 33,
+    UserConversations = 34,
+    OutgoingTransactions = 35,
+    WithdrawalIndex = 36,
+    EscrowDeadlines = 37,
+    PaymentSchedules = 38,
+    RecentConversations = 39,
+}
+
+impl MemoryRegion {
+    pub const ALL: [MemoryRegion; 40] = [
+        MemoryRegion::Users,
+        MemoryRegion::UserIndex,
+        MemoryRegion::Transactions,
+        MemoryRegion::TransactionIndex,
+        MemoryRegion::Balances,
+        MemoryRegion::BalanceHistory,
+        MemoryRegion::Notifications,
+        MemoryRegion::NotificationIndex,
+        MemoryRegion::RateLimits,
+        MemoryRegion::AuditLogs,
+        MemoryRegion::Sessions,
+        MemoryRegion::Configuration,
+        MemoryRegion::Statistics,
+        MemoryRegion::Reserved1,
+        MemoryRegion::Reserved2,
+        MemoryRegion::Reserved3,
+        MemoryRegion::UserTransactionsData,
+        MemoryRegion::Messages,
+        MemoryRegion::ConversationIndex,
+        MemoryRegion::CurrencyBalances,
+        MemoryRegion::Ledgers,
+        MemoryRegion::Roles,
+        MemoryRegion::Evidence,
+        MemoryRegion::EvidenceChunks,
+        MemoryRegion::KycSubmissions,
+        MemoryRegion::ReferralCodes,
+        MemoryRegion::Referrals,
+        MemoryRegion::TreasuryBalances,
+        MemoryRegion::TreasuryEntries,
+        MemoryRegion::FeeRules,
+        MemoryRegion::FeePromotions,
+        MemoryRegion::TransactionThreads,
+        MemoryRegion::MessageRevisions,
+        MemoryRegion::Contacts,
+        MemoryRegion::UserConversations,
+        MemoryRegion::OutgoingTransactions,
+        MemoryRegion::WithdrawalIndex,
+        MemoryRegion::EscrowDeadlines,
+        MemoryRegion::PaymentSchedules,
+        MemoryRegion::RecentConversations,
+    ];
 }
 
 impl From<MemoryRegion> for MemoryId {
@@ -75,29 +143,33 @@ pub mod utils {
         Critical,
     }
     
+    /// Bytes of stable memory the region has grown to.
+    pub fn region_size(region: MemoryRegion) -> u64 {
+        get_memory(region).size() * WASM_PAGE_SIZE
+    }
+
+    /// Heap and stable memory in bytes. Both are zero outside a canister.
+    pub fn memory_usage() -> crate::types::common::MemoryUsage {
+        #[cfg(target_arch = "wasm32")]
+        let (heap_size, stable_size) = (
+            core::arch::wasm32::memory_size(0) as u64 * WASM_PAGE_SIZE,
+            ic_cdk::stable::stable_size() * WASM_PAGE_SIZE,
+        );
+        #[cfg(not(target_arch = "wasm32"))]
+        let (heap_size, stable_size) = (0, 0);
+
+        crate::types::common::MemoryUsage {
+            heap_size,
+            stable_size,
+            total_size: heap_size + stable_size,
+        }
+    }
+
     pub fn validate_memory_regions() -> Result<(), String> {
         // Ensure no duplicate memory region IDs
         let mut used_ids = std::collections::HashSet::new();
         
-        let regions = [
-            MemoryRegion::Users,
-            MemoryRegion::UserIndex,
-            MemoryRegion::Transactions,
-            MemoryRegion::TransactionIndex,
-            MemoryRegion::Balances,
-            MemoryRegion::BalanceHistory,
-            MemoryRegion::Notifications,
-            MemoryRegion::NotificationIndex,
-            MemoryRegion::RateLimits,
-            MemoryRegion::AuditLogs,
-            MemoryRegion::Sessions,
-            MemoryRegion::Configuration,
-            MemoryRegion::Statistics,
-            MemoryRegion::Reserved1,
-            MemoryRegion::Reserved2,
-            MemoryRegion::Reserved3,
-            MemoryRegion::UserTransactionsData,
-        ];
+        let regions = MemoryRegion::ALL;
         
         for region in regions.iter() {
             let id = *region as u8;
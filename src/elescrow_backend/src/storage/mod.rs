@@ -1,2 +1,3 @@
 pub mod memory;
-pub mod stable_storage;
\ No newline at end of file
+pub mod stable_storage;
+pub mod config;
\ No newline at end of file
@@ -82,6 +82,10 @@ where
         }
     }
     
+    pub fn last_key(&self) -> Option<K> {
+        self.get_or_init_map().last_key_value().map(|(k, _)| k)
+    }
+    
     pub fn keys(&self) -> Vec<K> {
         self.get_or_init_map().iter().map(|(k, _)| k).collect()
     }
@@ -104,6 +108,40 @@ where
             .collect()
     }
     
+    pub fn range_while<F>(&self, start: K, predicate: F) -> Vec<(K, V)>
+    where
+        F: Fn(&K, &V) -> bool,
+    {
+        self.get_or_init_map()
+            .range(start..)
+            .take_while(|(k, v)| predicate(k, v))
+            .collect()
+    }
+    
+    /// Like `range_while`, but stops after `limit` entries.
+    pub fn range_while_take<F>(&self, start: K, limit: usize, predicate: F) -> Vec<(K, V)>
+    where
+        F: Fn(&K, &V) -> bool,
+    {
+        self.get_or_init_map()
+            .range(start..)
+            .take_while(|(k, v)| predicate(k, v))
+            .take(limit)
+            .collect()
+    }
+    
+    /// Like `range_while`, but walks down from `end`, inclusive.
+    pub fn range_rev_while<F>(&self, end: K, predicate: F) -> Vec<(K, V)>
+    where
+        F: Fn(&K, &V) -> bool,
+    {
+        self.get_or_init_map()
+            .range(..=end)
+            .rev()
+            .take_while(|(k, v)| predicate(k, v))
+            .collect()
+    }
+    
     pub fn update<F>(&self, key: &K, updater: F) -> Result<V, ApiError>
     where
         F: FnOnce(&mut V),
@@ -217,8 +255,24 @@ where
         }
     }
     
-    pub fn add(&self, timestamp: u64, value: V) {
-        self.storage.insert(timestamp, value);
+    /// Stores `value` at `timestamp`, or at the next free nanosecond when
+    /// several entries are recorded within the same message.
+    pub fn add(&self, timestamp: u64, value: V) -> u64 {
+        let mut key = timestamp;
+        while self.storage.contains_key(&key) {
+            key += 1;
+        }
+        self.storage.insert(key, value);
+        key
+    }
+    
+    pub fn get(&self, timestamp: u64) -> Option<V> {
+        self.storage.get(&timestamp)
+    }
+    
+    /// Entries from `start` onwards, without scanning older ones.
+    pub fn since(&self, start: u64) -> Vec<(u64, V)> {
+        self.storage.range_while(start, |_, _| true)
     }
     
     pub fn range(&self, start: u64, end: u64) -> Vec<(u64, V)> {
@@ -244,32 +298,37 @@ where
     }
 }
 
-use std::sync::Once;
-
 pub struct StorageManager {
     transaction_storage: StableStorage<u64, crate::models::transaction::TransactionModel>,
     user_transaction_index: IndexedStorage<u64, crate::models::transaction::TransactionModel, candid::Principal>,
+    // (sender, created_at, id) of every transaction, for per-user volume.
+    outgoing_index: StableStorage<(candid::Principal, u64, u64), ()>,
+    // (due_at, id) of escrows that release or expire on their own.
+    escrow_deadline_index: StableStorage<(u64, u64), ()>,
+    // (due_at, id) of pending scheduled payments, counting retries.
+    payment_schedule_index: StableStorage<(u64, u64), ()>,
     balance_storage: StableStorage<candid::Principal, crate::types::transaction::Balance>,
 }
 
-static INIT: Once = Once::new();
-static mut STORAGE_MANAGER: Option<StorageManager> = None;
+thread_local! {
+    // A canister runs on a single thread, so one instance per thread is one
+    // instance per canister; unit tests each get their own.
+    static STORAGE_MANAGER: &'static StorageManager = Box::leak(Box::new(StorageManager {
+        transaction_storage: StableStorage::new(MemoryRegion::Transactions),
+        user_transaction_index: IndexedStorage::new(
+            MemoryRegion::UserTransactionsData,
+            MemoryRegion::TransactionIndex,
+        ),
+        outgoing_index: StableStorage::new(MemoryRegion::OutgoingTransactions),
+        escrow_deadline_index: StableStorage::new(MemoryRegion::EscrowDeadlines),
+        payment_schedule_index: StableStorage::new(MemoryRegion::PaymentSchedules),
+        balance_storage: StableStorage::new(MemoryRegion::Balances),
+    }));
+}
 
 impl StorageManager {
     pub fn instance() -> &'static StorageManager {
-        unsafe {
-            INIT.call_once(|| {
-                STORAGE_MANAGER = Some(StorageManager {
-                    transaction_storage: StableStorage::new(MemoryRegion::Transactions),
-                    user_transaction_index: IndexedStorage::new(
-                        MemoryRegion::UserTransactionsData,
-                        MemoryRegion::TransactionIndex,
-                    ),
-                    balance_storage: StableStorage::new(MemoryRegion::Balances),
-                });
-            });
-            STORAGE_MANAGER.as_ref().unwrap()
-        }
+        STORAGE_MANAGER.with(|manager| *manager)
     }
     
     pub fn transactions(&self) -> &StableStorage<u64, crate::models::transaction::TransactionModel> {
@@ -280,6 +339,18 @@ impl StorageManager {
         &self.user_transaction_index
     }
     
+    pub fn outgoing_transactions(&self) -> &StableStorage<(candid::Principal, u64, u64), ()> {
+        &self.outgoing_index
+    }
+    
+    pub fn escrow_deadlines(&self) -> &StableStorage<(u64, u64), ()> {
+        &self.escrow_deadline_index
+    }
+    
+    pub fn payment_schedules(&self) -> &StableStorage<(u64, u64), ()> {
+        &self.payment_schedule_index
+    }
+    
     pub fn balances(&self) -> &StableStorage<candid::Principal, crate::types::transaction::Balance> {
         &self.balance_storage
     }
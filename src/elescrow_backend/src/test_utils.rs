@@ -0,0 +1,102 @@
+// Shared fixtures for unit tests: the usual cast of principals and a funded
+// escrow that can be walked through its states.
+
+use candid::Principal;
+
+use crate::services::transaction_service::TransactionService;
+use crate::types::transaction::{CreateTransactionRequest, Currency, Transaction, TransactionType};
+use crate::types::user::RegisterUserRequest;
+use crate::{BALANCE_SERVICE, TREASURY_SERVICE, USER_SERVICE};
+
+pub fn sender() -> Principal {
+    Principal::from_slice(&[1; 29])
+}
+
+pub fn recipient() -> Principal {
+    Principal::from_slice(&[2; 29])
+}
+
+pub fn agent() -> Principal {
+    Principal::from_slice(&[3; 29])
+}
+
+pub fn admin() -> Principal {
+    Principal::from_slice(&[8; 29])
+}
+
+pub fn register(principal: Principal) {
+    USER_SERVICE.with(|s| {
+        s.borrow().register(principal, RegisterUserRequest {
+            username: format!("user{}", principal.as_slice()[0]),
+            email: None,
+            display_name: None,
+            referral_code: None,
+        })
+    }).unwrap();
+}
+
+pub fn fund(principal: Principal, amount: u64) {
+    BALANCE_SERVICE.with(|s| {
+        s.borrow_mut().credit_funds(principal, amount, &Currency::ICP, 0, "Test funds")
+    }).unwrap();
+}
+
+pub fn available(principal: Principal) -> u64 {
+    BALANCE_SERVICE.with(|s| s.borrow_mut().get_balance(principal, &Currency::ICP)).unwrap().available
+}
+
+pub fn treasury_available() -> u64 {
+    TREASURY_SERVICE.with(|s| s.borrow().get_balance(&Currency::ICP)).available
+}
+
+/// A service whose sender holds 1,000,000 ICP units.
+pub fn funded_service() -> TransactionService {
+    fund(sender(), 1_000_000);
+    TransactionService::new()
+}
+
+/// A 10,000 unit escrow from the sender to the recipient.
+pub fn escrow_request(auto_release_after: Option<u64>, deadline: Option<u64>) -> CreateTransactionRequest {
+    CreateTransactionRequest {
+        transaction_type: TransactionType::Escrow {
+            release_conditions: vec![],
+            auto_release_after,
+            milestones: None,
+        },
+        to: recipient(),
+        amount: 10_000,
+        currency: Currency::ICP,
+        description: "Logo design".to_string(),
+        escrow_agent: None,
+        deadline,
+        category: None,
+        tags: vec![],
+        agent_fee_bps: None,
+    }
+}
+
+/// `escrow_request` with the agent on board for a 2% fee.
+pub fn agent_escrow_request() -> CreateTransactionRequest {
+    CreateTransactionRequest {
+        escrow_agent: Some(agent()),
+        agent_fee_bps: Some(200),
+        ..escrow_request(None, None)
+    }
+}
+
+/// Creates the escrow, has the recipient accept and deliver, and lets the
+/// sender dispute the work.
+pub fn disputed_escrow(service: &mut TransactionService, request: CreateTransactionRequest) -> Transaction {
+    let tx = service.create_transaction(sender(), request).unwrap();
+    service.accept_escrow_terms(tx.id, recipient()).unwrap();
+    service.submit_escrow_work(tx.id, recipient()).unwrap();
+    service.raise_dispute(tx.id, sender(), "Incomplete delivery".to_string()).unwrap()
+}
+
+/// An escrow the sender has paid out to the recipient.
+pub fn completed_payment(service: &mut TransactionService) -> Transaction {
+    let tx = service.create_transaction(sender(), escrow_request(None, None)).unwrap();
+    service.accept_escrow_terms(tx.id, recipient()).unwrap();
+    service.submit_escrow_work(tx.id, recipient()).unwrap();
+    service.complete_transaction(tx.id, sender()).unwrap()
+}
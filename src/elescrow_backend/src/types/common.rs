@@ -113,9 +113,12 @@ pub enum AuditAction {
     TransactionReleased,
     TransactionDeposited,
     TransactionWithdrawn,
+    EvidenceUploaded,
+    ReferralRewarded,
     
     Deposit,
     Withdrawal,
+    TreasuryWithdrawal,
     FundsLocked,
     FundsUnlocked,
     
@@ -126,6 +129,9 @@ pub enum AuditAction {
     SystemPaused,
     SystemResumed,
     KycStatusUpdated,
+    RoleGranted,
+    RoleRevoked,
+    MessageRemoved,
     
     RateLimitExceeded,
     SuspiciousActivity,
@@ -53,6 +53,11 @@ pub enum ApiError {
     RateLimited { 
         retry_after: u64 
     },
+    LimitExceeded {
+        limit: String,
+        requested: u64,
+        remaining: u64,
+    },
     
     AccountFrozen { 
         reason: String 
@@ -105,6 +110,9 @@ impl ApiError {
             ApiError::RateLimited { retry_after } => 
                 format!("Rate limited, retry after {} seconds", retry_after),
             
+            ApiError::LimitExceeded { limit, requested, remaining } => 
+                format!("{} exceeded: requested {}, remaining {}", limit, requested, remaining),
+            
             ApiError::AccountFrozen { reason } => 
                 format!("Account frozen: {}", reason),
             
@@ -131,6 +139,7 @@ impl ApiError {
             ApiError::InternalError { .. } => "INTERNAL_ERROR",
             ApiError::ServiceUnavailable { .. } => "SERVICE_UNAVAILABLE",
             ApiError::RateLimited { .. } => "RATE_LIMITED",
+            ApiError::LimitExceeded { .. } => "LIMIT_EXCEEDED",
             ApiError::AccountFrozen { .. } => "ACCOUNT_FROZEN",
             ApiError::AccountNotVerified => "ACCOUNT_NOT_VERIFIED",
             ApiError::SystemPaused { .. } => "SYSTEM_PAUSED",
@@ -0,0 +1,54 @@
+use candid::{CandidType, Principal};
+use ic_stable_structures::Storable;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize, PartialEq)]
+pub struct EvidenceFile {
+    pub id: u64,
+    pub transaction_id: u64,
+    // Set when the evidence concerns a single disputed milestone.
+    pub milestone_id: Option<u32>,
+    pub uploaded_by: Principal,
+    pub name: String,
+    pub mime_type: String,
+    pub size: u64,
+    pub received_bytes: u64,
+    pub chunk_count: u32,
+    // Hex-encoded SHA-256 of the content, computed once the upload finishes.
+    pub sha256: Option<String>,
+    pub status: EvidenceStatus,
+    pub created_at: u64,
+    pub completed_at: Option<u64>,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize, PartialEq)]
+pub enum EvidenceStatus {
+    Uploading,
+    Complete,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct BeginEvidenceUploadRequest {
+    pub transaction_id: u64,
+    pub milestone_id: Option<u32>,
+    pub name: String,
+    pub mime_type: String,
+    pub size: u64,
+}
+
+impl Storable for EvidenceFile {
+    const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
+        max_size: 1024,
+        is_fixed_size: false,
+    };
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let bytes = serde_cbor::to_vec(self).expect("Failed to serialize EvidenceFile");
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        serde_cbor::from_slice(&bytes).expect("Failed to deserialize EvidenceFile")
+    }
+}
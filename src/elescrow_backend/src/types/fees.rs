@@ -0,0 +1,129 @@
+use candid::{CandidType, Principal};
+use ic_stable_structures::Storable;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+use super::transaction::{Currency, TransactionType};
+
+/// The transaction types fee rules are set for. Everything that is not an
+/// escrow or a standing order is priced as a direct payment.
+#[derive(Clone, Copy, Debug, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub enum FeeTransactionType {
+    DirectPayment,
+    Escrow,
+    ScheduledPayment,
+}
+
+impl FeeTransactionType {
+    pub fn of(transaction_type: &TransactionType) -> Self {
+        match transaction_type {
+            TransactionType::Escrow { .. } => FeeTransactionType::Escrow,
+            TransactionType::ScheduledPayment { .. } => FeeTransactionType::ScheduledPayment,
+            _ => FeeTransactionType::DirectPayment,
+        }
+    }
+}
+
+/// Rate for amounts from `min_amount` up to the next tier's `min_amount`.
+/// The whole amount is charged at the rate of the tier it falls in.
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize, PartialEq)]
+pub struct FeeTier {
+    pub min_amount: u64,
+    pub fee_bps: u64,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize, PartialEq)]
+pub struct FeeRule {
+    pub transaction_type: FeeTransactionType,
+    // None applies to every currency without a rule of its own.
+    pub currency: Option<Currency>,
+    // Sorted by `min_amount`; the first tier starts at 0.
+    pub tiers: Vec<FeeTier>,
+    pub min_fee: Option<u64>,
+    pub max_fee: Option<u64>,
+}
+
+/// A window in which matching transactions are charged no fee. Empty
+/// `transaction_types` or `currencies` match all of them.
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize, PartialEq)]
+pub struct FeePromotion {
+    pub id: u64,
+    pub name: String,
+    pub starts_at: u64,
+    pub ends_at: u64,
+    pub transaction_types: Vec<FeeTransactionType>,
+    pub currencies: Vec<Currency>,
+    pub created_by: Principal,
+    pub created_at: u64,
+}
+
+impl FeePromotion {
+    pub fn applies(&self, transaction_type: FeeTransactionType, currency: &Currency, at: u64) -> bool {
+        at >= self.starts_at
+            && at < self.ends_at
+            && (self.transaction_types.is_empty() || self.transaction_types.contains(&transaction_type))
+            && (self.currencies.is_empty() || self.currencies.contains(currency))
+    }
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct CreateFeePromotionRequest {
+    pub name: String,
+    pub starts_at: u64,
+    pub ends_at: u64,
+    pub transaction_types: Vec<FeeTransactionType>,
+    pub currencies: Vec<Currency>,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct FeeQuoteRequest {
+    pub transaction_type: FeeTransactionType,
+    pub amount: u64,
+    pub currency: Option<Currency>,
+}
+
+/// What a transaction would cost if submitted now.
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize, PartialEq)]
+pub struct FeeQuote {
+    pub transaction_type: FeeTransactionType,
+    pub currency: Currency,
+    pub amount: u64,
+    pub fee: u64,
+    // Amount plus fee: what the sender needs available.
+    pub total: u64,
+    // Rate of the tier the amount fell in, before caps.
+    pub fee_bps: u64,
+    pub promotion: Option<FeePromotion>,
+}
+
+impl Storable for FeeRule {
+    const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
+        max_size: 1024,
+        is_fixed_size: false,
+    };
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let bytes = serde_cbor::to_vec(self).expect("Failed to serialize FeeRule");
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        serde_cbor::from_slice(&bytes).expect("Failed to deserialize FeeRule")
+    }
+}
+
+impl Storable for FeePromotion {
+    const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
+        max_size: 1024,
+        is_fixed_size: false,
+    };
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let bytes = serde_cbor::to_vec(self).expect("Failed to serialize FeePromotion");
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        serde_cbor::from_slice(&bytes).expect("Failed to deserialize FeePromotion")
+    }
+}
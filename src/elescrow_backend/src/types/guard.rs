@@ -0,0 +1,58 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+use super::{transaction::Currency, user::VerificationLevel};
+
+/// Groups of update endpoints that share an authorization policy.
+#[derive(Clone, Copy, Debug, CandidType, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Operation {
+    CreateTransaction,
+    EscrowAction,
+    Deposit,
+    Withdraw,
+    Refund,
+    Messaging,
+    Evidence,
+    Profile,
+}
+
+impl Operation {
+    pub const ALL: [Operation; 8] = [
+        Operation::CreateTransaction,
+        Operation::EscrowAction,
+        Operation::Deposit,
+        Operation::Withdraw,
+        Operation::Refund,
+        Operation::Messaging,
+        Operation::Evidence,
+        Operation::Profile,
+    ];
+
+    pub fn key(&self) -> &'static str {
+        match self {
+            Operation::CreateTransaction => "create_transaction",
+            Operation::EscrowAction => "escrow_action",
+            Operation::Deposit => "deposit",
+            Operation::Withdraw => "withdraw",
+            Operation::Refund => "refund",
+            Operation::Messaging => "messaging",
+            Operation::Evidence => "evidence",
+            Operation::Profile => "profile",
+        }
+    }
+}
+
+/// Amounts at or above `min_amount` (in base units of `currency`) require
+/// at least `level`, whatever the operation's own minimum is.
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize, PartialEq)]
+pub struct AmountThreshold {
+    pub currency: Currency,
+    pub level: VerificationLevel,
+    pub min_amount: u64,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct GuardPolicy {
+    pub operation_levels: Vec<(Operation, VerificationLevel)>,
+    pub amount_thresholds: Vec<AmountThreshold>,
+}
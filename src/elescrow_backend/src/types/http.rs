@@ -0,0 +1,31 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct HttpRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize)]
+pub struct HttpResponse {
+    pub status_code: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+/// What `/transactions/{id}` shows to anyone holding the ID. Parties,
+/// description and metadata are left out on purpose.
+#[derive(Clone, Debug, Serialize)]
+pub struct TransactionReceipt {
+    pub id: u64,
+    pub transaction_type: String,
+    pub status: String,
+    pub amount: u64,
+    pub fee: u64,
+    pub currency: String,
+    pub created_at: u64,
+    pub completed_at: Option<u64>,
+}
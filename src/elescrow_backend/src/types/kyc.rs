@@ -0,0 +1,68 @@
+use candid::{CandidType, Principal};
+use ic_stable_structures::Storable;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+use super::user::{KycStatus, VerificationLevel};
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize, PartialEq)]
+pub enum KycDocumentType {
+    Passport,
+    NationalId,
+    DriversLicense,
+    ProofOfAddress,
+    Selfie,
+    Other(String),
+}
+
+/// Metadata of a document held off-chain by the user or a KYC provider. Only
+/// its hash is kept here, so reviewers can check they were shown the same file.
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize, PartialEq)]
+pub struct KycDocument {
+    pub document_type: KycDocumentType,
+    pub file_name: String,
+    // Hex-encoded SHA-256 of the file.
+    pub sha256: String,
+    pub size: u64,
+    pub issuing_country: Option<String>,
+    pub expires_at: Option<u64>,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct SubmitKycRequest {
+    pub requested_level: VerificationLevel,
+    pub documents: Vec<KycDocument>,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize, PartialEq)]
+pub struct KycSubmission {
+    pub id: u64,
+    pub user: Principal,
+    pub requested_level: VerificationLevel,
+    pub documents: Vec<KycDocument>,
+    // Pending, UnderReview, Approved, Rejected or Expired.
+    pub status: KycStatus,
+    pub reviewer: Option<Principal>,
+    pub submitted_at: u64,
+    pub claimed_at: Option<u64>,
+    pub reviewed_at: Option<u64>,
+    // Set on approval; the scheduler expires the approval after this.
+    pub expires_at: Option<u64>,
+}
+
+impl Storable for KycSubmission {
+    // Fits MAX_KYC_DOCUMENTS documents with every text field at its limit.
+    const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
+        max_size: 8192,
+        is_fixed_size: false,
+    };
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let bytes = serde_cbor::to_vec(self).expect("Failed to serialize KycSubmission");
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        serde_cbor::from_slice(&bytes).expect("Failed to deserialize KycSubmission")
+    }
+}
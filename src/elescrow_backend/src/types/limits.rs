@@ -0,0 +1,28 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+use super::{transaction::Currency, user::VerificationLevel};
+
+/// Caps for one verification tier, in base units of whichever currency is
+/// moved. Volumes are rolling: daily covers the last 24 hours and monthly
+/// the last 30 days, counted per currency.
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize, PartialEq)]
+pub struct TierLimits {
+    pub max_per_transaction: u64,
+    pub daily_volume: u64,
+    pub monthly_volume: u64,
+    pub daily_withdrawal: u64,
+    pub monthly_withdrawal: u64,
+}
+
+/// A user's tier, its limits and how much of them is used in one currency.
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct LimitUsage {
+    pub tier: VerificationLevel,
+    pub currency: Currency,
+    pub limits: TierLimits,
+    pub daily_volume_used: u64,
+    pub monthly_volume_used: u64,
+    pub daily_withdrawn: u64,
+    pub monthly_withdrawn: u64,
+}
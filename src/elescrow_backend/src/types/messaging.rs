@@ -3,6 +3,9 @@ use serde::{Deserialize, Serialize};
 use ic_stable_structures::{storable::Bound, Storable};
 use std::borrow::Cow;
 
+use super::notification::Notification;
+use super::transaction::{Balance, Transaction};
+
 #[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
 pub struct CanisterInfo {
     pub name: String,
@@ -11,7 +14,7 @@ pub struct CanisterInfo {
     pub total_memory_usage: u64,
 }
 
-#[derive(CandidType, Deserialize)]
+#[derive(CandidType, Deserialize, Debug, PartialEq)]
 pub enum PostResult {
     Ok,
     Err(String),
@@ -106,11 +109,17 @@ pub struct Message {
     pub text: String,
     pub timestamp: u64,
     pub read: bool,
+    pub edited_at: Option<u64>,
+    // Soft-deleted by the author: the text is cleared, the message stays in
+    // the conversation as a placeholder.
+    pub deleted_at: Option<u64>,
 }
 
 impl Storable for Message {
+    // The two optional timestamps are appended after `read`, so records
+    // written before they existed still decode.
     const BOUND: Bound = Bound::Bounded {
-        max_size: 8 + 8 + (1 + MAX_PRINCIPAL_BYTES) * 2 + 4 + MAX_TEXT_BYTES + 1,
+        max_size: 8 + 8 + (1 + MAX_PRINCIPAL_BYTES) * 2 + 4 + MAX_TEXT_BYTES + 1 + (1 + 8) * 2,
         is_fixed_size: false,
     };
 
@@ -133,6 +142,9 @@ impl Storable for Message {
 
         buf.push(self.read as u8);
 
+        write_optional_u64(&mut buf, self.edited_at);
+        write_optional_u64(&mut buf, self.deleted_at);
+
         Cow::Owned(buf)
     }
 
@@ -161,6 +173,10 @@ impl Storable for Message {
         offset += text_len;
 
         let read = bytes[offset] != 0;
+        offset += 1;
+
+        let edited_at = read_optional_u64(&bytes, &mut offset);
+        let deleted_at = read_optional_u64(&bytes, &mut offset);
 
         Self {
             id,
@@ -169,14 +185,217 @@ impl Storable for Message {
             text,
             timestamp,
             read,
+            edited_at,
+            deleted_at,
+        }
+    }
+}
+
+fn write_optional_u64(buf: &mut Vec<u8>, value: Option<u64>) {
+    match value {
+        Some(value) => {
+            buf.push(1);
+            buf.extend(value.to_le_bytes());
+        }
+        None => buf.push(0),
+    }
+}
+
+/// Reads a field appended to the layout later; a record that ends before it
+/// decodes as `None`.
+fn read_optional_u64(bytes: &[u8], offset: &mut usize) -> Option<u64> {
+    if bytes.len() <= *offset || bytes[*offset] == 0 {
+        *offset += 1;
+        return None;
+    }
+
+    let value = u64::from_le_bytes(bytes[*offset + 1..*offset + 9].try_into().unwrap());
+    *offset += 9;
+    Some(value)
+}
+
+/// A previous text of an edited or deleted message.
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct MessageRevision {
+    pub message_id: u64,
+    pub revision: u32,
+    pub text: String,
+    pub replaced_at: u64,
+}
+
+impl Storable for MessageRevision {
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 8 + 4 + 4 + MAX_TEXT_BYTES + 8,
+        is_fixed_size: false,
+    };
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let mut buf = Vec::with_capacity(Self::BOUND.max_size() as usize);
+        buf.extend(self.message_id.to_le_bytes());
+        buf.extend(self.revision.to_le_bytes());
+
+        let text_bytes = self.text.as_bytes();
+        buf.extend((text_bytes.len() as u32).to_le_bytes());
+        buf.extend(text_bytes);
+
+        buf.extend(self.replaced_at.to_le_bytes());
+
+        Cow::Owned(buf)
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        let message_id = u64::from_le_bytes(bytes[0..8].try_into().unwrap());
+        let revision = u32::from_le_bytes(bytes[8..12].try_into().unwrap());
+
+        let text_len = u32::from_le_bytes(bytes[12..16].try_into().unwrap()) as usize;
+        let text = String::from_utf8_lossy(&bytes[16..16 + text_len]).into_owned();
+
+        let replaced_at = u64::from_le_bytes(bytes[16 + text_len..24 + text_len].try_into().unwrap());
+
+        Self {
+            message_id,
+            revision,
+            text,
+            replaced_at,
         }
     }
 }
 
+/// A message in the chat thread attached to a transaction.
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct ThreadMessage {
+    pub id: u64,
+    pub transaction_id: u64,
+    pub from: Principal,
+    pub text: String,
+    pub timestamp: u64,
+}
+
+impl Storable for ThreadMessage {
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 8 + 8 + 1 + MAX_PRINCIPAL_BYTES + 4 + MAX_TEXT_BYTES + 8,
+        is_fixed_size: false,
+    };
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let mut buf = Vec::with_capacity(Self::BOUND.max_size() as usize);
+        buf.extend(self.id.to_le_bytes());
+        buf.extend(self.transaction_id.to_le_bytes());
+
+        let from_slice = self.from.as_slice();
+        buf.push(from_slice.len() as u8);
+        buf.extend(from_slice);
+
+        let text_bytes = self.text.as_bytes();
+        buf.extend((text_bytes.len() as u32).to_le_bytes());
+        buf.extend(text_bytes);
+
+        buf.extend(self.timestamp.to_le_bytes());
+
+        Cow::Owned(buf)
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        let mut offset = 0;
+
+        let id = u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+        offset += 8;
+
+        let transaction_id = u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+        offset += 8;
+
+        let from_len = bytes[offset] as usize;
+        offset += 1;
+        let from = Principal::from_slice(&bytes[offset..offset + from_len]);
+        offset += from_len;
+
+        let text_len = u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()) as usize;
+        offset += 4;
+        let text = String::from_utf8_lossy(&bytes[offset..offset + text_len]).into_owned();
+        offset += text_len;
+
+        let timestamp = u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+
+        Self {
+            id,
+            transaction_id,
+            from,
+            text,
+            timestamp,
+        }
+    }
+}
+
+/// How a user treats another principal in direct messages.
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize, PartialEq)]
+pub enum ContactStatus {
+    Contact,
+    // Messages from a non-contact wait here until the recipient accepts.
+    Requested { first_message_at: u64, message_count: u32 },
+    Blocked { blocked_at: u64 },
+}
+
+impl Storable for ContactStatus {
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 64,
+        is_fixed_size: false,
+    };
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let bytes = serde_cbor::to_vec(self).expect("Failed to serialize ContactStatus");
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        serde_cbor::from_slice(&bytes).expect("Failed to deserialize ContactStatus")
+    }
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize, PartialEq)]
+pub struct MessageRequest {
+    pub from: Principal,
+    pub first_message_at: u64,
+    pub message_count: u32,
+}
+
+/// One entry of a user's conversation list.
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize, PartialEq)]
+pub struct ConversationSummary {
+    pub counterpart: Principal,
+    pub last_message_id: u64,
+    pub last_message_from: Principal,
+    pub last_message_preview: String,
+    pub last_message_at: u64,
+    pub unread_count: u64,
+}
+
+impl Storable for ConversationSummary {
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 1024,
+        is_fixed_size: false,
+    };
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let bytes = serde_cbor::to_vec(self).expect("Failed to serialize ConversationSummary");
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        serde_cbor::from_slice(&bytes).expect("Failed to deserialize ConversationSummary")
+    }
+}
+
 #[derive(CandidType, Clone, Debug)]
 pub enum WsEvent {
     NewMessage(Message),
     MessageRead { message_id: u64 },
+    TransactionUpdated(Transaction),
+    BalanceUpdated(Balance),
+    NewNotification(Notification),
+    NewThreadMessage(ThreadMessage),
+    MessageEdited(Message),
+    MessageDeleted { message_id: u64, permanent: bool },
+    NewMessageRequest(Message),
 }
 
 pub const TRANSACTIONS_MEMORY_ID: u8 = 1;
@@ -185,5 +404,38 @@ pub const BALANCES_MEMORY_ID: u8 = 3;
 pub const USERS_MEMORY_ID: u8 = 4;
 
 pub const MAX_TEXT_BYTES: u32 = 1000;
+pub const MESSAGE_PREVIEW_CHARS: usize = 100;
 pub const MAX_PRINCIPAL_BYTES: u32 = 29;
-pub const MAX_METADATA_BYTES: u32 = 2000;
\ No newline at end of file
+pub const MAX_METADATA_BYTES: u32 = 2000;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message() -> Message {
+        Message {
+            id: 7,
+            from: Principal::from_slice(&[1; 29]),
+            to: Principal::from_slice(&[2; 29]),
+            text: "hello".to_string(),
+            timestamp: 42,
+            read: true,
+            edited_at: None,
+            deleted_at: None,
+        }
+    }
+
+    #[test]
+    fn test_message_decodes_records_without_edit_fields() {
+        let mut legacy = message().to_bytes().into_owned();
+        legacy.truncate(legacy.len() - 2);
+
+        let decoded = Message::from_bytes(Cow::Owned(legacy));
+        assert_eq!((decoded.id, decoded.text.as_str(), decoded.read), (7, "hello", true));
+        assert_eq!((decoded.edited_at, decoded.deleted_at), (None, None));
+
+        let edited = Message { edited_at: Some(50), deleted_at: Some(60), ..message() };
+        let decoded = Message::from_bytes(edited.to_bytes());
+        assert_eq!((decoded.edited_at, decoded.deleted_at), (Some(50), Some(60)));
+    }
+}
@@ -3,4 +3,15 @@ pub mod errors;
 pub mod user;
 pub mod transaction;
 pub mod notification;
-pub mod messaging;
\ No newline at end of file
+pub mod messaging;
+pub mod ledger;
+pub mod role;
+pub mod evidence;
+pub mod http;
+pub mod rate_limit;
+pub mod guard;
+pub mod kyc;
+pub mod limits;
+pub mod referral;
+pub mod treasury;
+pub mod fees;
\ No newline at end of file
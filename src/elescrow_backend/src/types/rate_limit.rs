@@ -0,0 +1,108 @@
+use candid::{CandidType, Principal};
+use ic_stable_structures::Storable;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+#[derive(Clone, Copy, Debug, CandidType, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum EndpointClass {
+    Write,
+    Messaging,
+    Search,
+}
+
+impl EndpointClass {
+    pub const ALL: [EndpointClass; 3] = [EndpointClass::Write, EndpointClass::Messaging, EndpointClass::Search];
+
+    pub fn key(&self) -> &'static str {
+        match self {
+            EndpointClass::Write => "write",
+            EndpointClass::Messaging => "messaging",
+            EndpointClass::Search => "search",
+        }
+    }
+
+    fn to_byte(self) -> u8 {
+        match self {
+            EndpointClass::Write => 0,
+            EndpointClass::Messaging => 1,
+            EndpointClass::Search => 2,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Self {
+        match byte {
+            0 => EndpointClass::Write,
+            1 => EndpointClass::Messaging,
+            _ => EndpointClass::Search,
+        }
+    }
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize, PartialEq)]
+pub struct RateLimitConfig {
+    pub max_requests: u32,
+    // Window length and block duration are in nanoseconds.
+    pub window: u64,
+    pub block_duration: u64,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct RateLimitBlock {
+    pub principal: Principal,
+    pub class: EndpointClass,
+    pub blocked_until: u64,
+    pub violations: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RateLimitKey {
+    pub principal: Principal,
+    pub class: EndpointClass,
+}
+
+impl Storable for RateLimitKey {
+    const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
+        max_size: 30,
+        is_fixed_size: false,
+    };
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let mut bytes = vec![self.class.to_byte()];
+        bytes.extend_from_slice(self.principal.as_slice());
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        Self {
+            class: EndpointClass::from_byte(bytes[0]),
+            principal: Principal::from_slice(&bytes[1..]),
+        }
+    }
+}
+
+/// Request counts for the current and previous fixed window. The sliding
+/// count weighs the previous window by how much of it still overlaps.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RateLimitEntry {
+    pub window_start: u64,
+    pub current_count: u32,
+    pub previous_count: u32,
+    pub blocked_until: Option<u64>,
+    pub violations: u32,
+}
+
+impl Storable for RateLimitEntry {
+    const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
+        max_size: 128,
+        is_fixed_size: false,
+    };
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let bytes = serde_cbor::to_vec(self).expect("Failed to serialize RateLimitEntry");
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        serde_cbor::from_slice(&bytes).expect("Failed to deserialize RateLimitEntry")
+    }
+}
@@ -0,0 +1,76 @@
+use candid::{CandidType, Principal};
+use ic_stable_structures::Storable;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+use super::transaction::Currency;
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize, PartialEq)]
+pub struct ReferralCode {
+    pub code: String,
+    pub owner: Principal,
+    pub created_at: u64,
+    pub uses: u32,
+}
+
+/// Links a referred user to whoever referred them, and tracks the rewards
+/// their transactions have paid out so far.
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize, PartialEq)]
+pub struct Referral {
+    pub referee: Principal,
+    pub referrer: Principal,
+    pub code: String,
+    pub registered_at: u64,
+    pub rewarded_transactions: u32,
+    pub rewards: Vec<(Currency, u64)>,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct ReferralStats {
+    pub codes: Vec<ReferralCode>,
+    pub referred_users: u64,
+    pub rewarded_transactions: u64,
+    pub earnings: Vec<(Currency, u64)>,
+    // Set when the caller was referred themselves.
+    pub referred_by: Option<Referral>,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize, PartialEq)]
+pub struct ReferralConfig {
+    // Share of the platform fee paid to the referrer, in basis points.
+    pub fee_share_bps: u64,
+    // How many of the referee's completed transactions earn a reward.
+    pub rewarded_transactions: u32,
+}
+
+impl Storable for ReferralCode {
+    const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
+        max_size: 256,
+        is_fixed_size: false,
+    };
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let bytes = serde_cbor::to_vec(self).expect("Failed to serialize ReferralCode");
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        serde_cbor::from_slice(&bytes).expect("Failed to deserialize ReferralCode")
+    }
+}
+
+impl Storable for Referral {
+    const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
+        max_size: 1024,
+        is_fixed_size: false,
+    };
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let bytes = serde_cbor::to_vec(self).expect("Failed to serialize Referral");
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        serde_cbor::from_slice(&bytes).expect("Failed to deserialize Referral")
+    }
+}
@@ -0,0 +1,88 @@
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+use ic_stable_structures::Storable;
+use std::borrow::Cow;
+
+#[derive(Clone, Copy, Debug, CandidType, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum Role {
+    SuperAdmin,
+    Admin,
+    DisputeArbiter,
+    ComplianceOfficer,
+    Support,
+    Auditor,
+}
+
+#[derive(Clone, Copy, Debug, CandidType, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum Permission {
+    ManageRoles,
+    ManageSystem,
+    ManageFees,
+    ManageTreasury,
+    ViewTreasury,
+    ManageLedgers,
+    FreezeAccounts,
+    VerifyUsers,
+    ViewUsers,
+    ViewTransactions,
+    ResolveDisputes,
+    ReverseTransactions,
+    ViewAuditLogs,
+    ModerateMessages,
+}
+
+impl Role {
+    pub fn permissions(&self) -> &'static [Permission] {
+        use Permission::*;
+
+        match self {
+            Role::SuperAdmin => &[
+                ManageRoles, ManageSystem, ManageFees, ManageTreasury, ViewTreasury, ManageLedgers,
+                FreezeAccounts, VerifyUsers, ViewUsers, ViewTransactions, ResolveDisputes,
+                ReverseTransactions, ViewAuditLogs, ModerateMessages,
+            ],
+            Role::Admin => &[
+                ManageSystem, ManageFees, ManageTreasury, ViewTreasury, ManageLedgers, FreezeAccounts,
+                VerifyUsers, ViewUsers, ViewTransactions, ResolveDisputes, ReverseTransactions, ViewAuditLogs,
+                ModerateMessages,
+            ],
+            Role::DisputeArbiter => &[ViewTransactions, ResolveDisputes],
+            Role::ComplianceOfficer => &[FreezeAccounts, VerifyUsers, ViewUsers, ViewTransactions, ViewAuditLogs],
+            Role::Support => &[ViewUsers, ViewTransactions, ModerateMessages],
+            Role::Auditor => &[ViewUsers, ViewTransactions, ViewAuditLogs, ViewTreasury],
+        }
+    }
+
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        self.permissions().contains(&permission)
+    }
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct RoleAssignment {
+    pub principal: Principal,
+    pub roles: Vec<Role>,
+    pub updated_by: Principal,
+    pub updated_at: u64,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct InitArgs {
+    pub super_admin: Principal,
+}
+
+impl Storable for RoleAssignment {
+    const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
+        max_size: 256,
+        is_fixed_size: false,
+    };
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let bytes = serde_cbor::to_vec(self).expect("Failed to serialize RoleAssignment");
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        serde_cbor::from_slice(&bytes).expect("Failed to deserialize RoleAssignment")
+    }
+}
@@ -19,6 +19,8 @@ pub struct Transaction {
     pub completed_at: Option<u64>,
     pub deadline: Option<u64>,
     pub metadata: TransactionMetadata,
+    pub agent_fee_bps: Option<u64>,
+    pub agent_accepted_at: Option<u64>,
 }
 
 #[derive(Clone, Debug, CandidType, Serialize, Deserialize, PartialEq)]
@@ -26,7 +28,12 @@ pub enum TransactionType {
     DirectPayment,
     Escrow {
         release_conditions: Vec<String>,
+        // Nanoseconds after `SubmittedForReview` at which funds release
+        // without the sender's approval.
         auto_release_after: Option<u64>,
+        // When set, the escrowed amount is released milestone by milestone
+        // instead of all at once.
+        milestones: Option<Vec<Milestone>>,
     },
     ScheduledPayment {
         schedule: PaymentSchedule,
@@ -79,7 +86,10 @@ pub enum TransactionStatus {
     },
     
     Refunded {
+        // The refund that completed it; every refund is in the list.
         refund_transaction_id: u64,
+        #[serde(default)]
+        refund_transaction_ids: Vec<u64>,
         refunded_at: u64,
     },
     PartiallyRefunded {
@@ -93,6 +103,81 @@ pub enum TransactionStatus {
     },
 }
 
+impl TransactionType {
+    /// Variant name without its payload, for public and aggregate views.
+    pub fn label(&self) -> &'static str {
+        match self {
+            TransactionType::DirectPayment => "DirectPayment",
+            TransactionType::Escrow { .. } => "Escrow",
+            TransactionType::ScheduledPayment { .. } => "ScheduledPayment",
+            TransactionType::Refund { .. } => "Refund",
+            TransactionType::Dispute { .. } => "Dispute",
+            TransactionType::Release => "Release",
+            TransactionType::Withdrawal => "Withdrawal",
+            TransactionType::Deposit => "Deposit",
+            TransactionType::Reversal => "Reversal",
+        }
+    }
+}
+
+impl TransactionStatus {
+    /// Variant name without its payload, for public and aggregate views.
+    pub fn label(&self) -> &'static str {
+        match self {
+            TransactionStatus::Draft => "Draft",
+            TransactionStatus::Pending => "Pending",
+            TransactionStatus::Approved => "Approved",
+            TransactionStatus::Processing => "Processing",
+            TransactionStatus::InEscrow => "InEscrow",
+            TransactionStatus::SubmittedForReview { .. } => "SubmittedForReview",
+            TransactionStatus::Completed => "Completed",
+            TransactionStatus::Cancelled { .. } => "Cancelled",
+            TransactionStatus::Failed { .. } => "Failed",
+            TransactionStatus::Disputed { .. } => "Disputed",
+            TransactionStatus::UnderReview { .. } => "UnderReview",
+            TransactionStatus::Refunded { .. } => "Refunded",
+            TransactionStatus::PartiallyRefunded { .. } => "PartiallyRefunded",
+            TransactionStatus::Resolved { .. } => "Resolved",
+        }
+    }
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize, PartialEq)]
+pub struct Milestone {
+    pub id: u32,
+    pub description: String,
+    pub amount: u64,
+    pub due_date: Option<u64>,
+    pub status: MilestoneStatus,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize, PartialEq)]
+pub enum MilestoneStatus {
+    Pending,
+    Submitted {
+        submitted_at: u64,
+    },
+    Approved {
+        approved_at: u64,
+    },
+    Disputed {
+        reason: String,
+        disputed_by: Principal,
+        disputed_at: u64,
+    },
+    Resolved {
+        resolution: DisputeResolution,
+        resolved_by: Principal,
+        resolved_at: u64,
+    },
+}
+
+impl MilestoneStatus {
+    pub fn is_settled(&self) -> bool {
+        matches!(self, MilestoneStatus::Approved { .. } | MilestoneStatus::Resolved { .. })
+    }
+}
+
 #[derive(Clone, Debug, CandidType, Serialize, Deserialize, PartialEq)]
 pub enum Currency {
     ICP,
@@ -101,6 +186,43 @@ pub enum Currency {
     Custom { symbol: String, decimals: u8 },
 }
 
+impl Currency {
+    pub fn code(&self) -> String {
+        match self {
+            Currency::ICP => "ICP".to_string(),
+            Currency::Cycles => "CYCLES".to_string(),
+            Currency::USDT => "USDT".to_string(),
+            Currency::Custom { symbol, decimals } => format!("CUSTOM:{}:{}", symbol, decimals),
+        }
+    }
+
+    /// Number of decimal places of one whole token.
+    pub fn decimals(&self) -> u8 {
+        match self {
+            Currency::ICP => 8,
+            Currency::Cycles => 12,
+            Currency::USDT => 6,
+            Currency::Custom { decimals, .. } => *decimals,
+        }
+    }
+
+    /// Inverse of `code`.
+    pub fn from_code(code: &str) -> Option<Currency> {
+        match code {
+            "ICP" => Some(Currency::ICP),
+            "CYCLES" => Some(Currency::Cycles),
+            "USDT" => Some(Currency::USDT),
+            _ => {
+                let (symbol, decimals) = code.strip_prefix("CUSTOM:")?.rsplit_once(':')?;
+                Some(Currency::Custom {
+                    symbol: symbol.to_string(),
+                    decimals: decimals.parse().ok()?,
+                })
+            }
+        }
+    }
+}
+
 #[derive(Clone, Debug, CandidType, Serialize, Deserialize, PartialEq)]
 pub struct PaymentSchedule {
     pub frequency: PaymentFrequency,
@@ -110,6 +232,9 @@ pub struct PaymentSchedule {
     pub total_payments: Option<u32>,
     pub payments_completed: u32,
     pub next_payment_date: u64,
+    // Consecutive failed attempts at the current `next_payment_date`.
+    pub failed_attempts: Option<u32>,
+    pub retry_at: Option<u64>,
 }
 
 #[derive(Clone, Debug, CandidType, Serialize, Deserialize, PartialEq)]
@@ -124,6 +249,23 @@ pub enum PaymentFrequency {
     Custom { interval_days: u32 },
 }
 
+impl PaymentFrequency {
+    /// Time between payments in nanoseconds; `None` for a one-off payment.
+    pub fn interval(&self) -> Option<u64> {
+        let days = match self {
+            PaymentFrequency::OneTime => return None,
+            PaymentFrequency::Daily => 1,
+            PaymentFrequency::Weekly => 7,
+            PaymentFrequency::BiWeekly => 14,
+            PaymentFrequency::Monthly => 30,
+            PaymentFrequency::Quarterly => 91,
+            PaymentFrequency::Yearly => 365,
+            PaymentFrequency::Custom { interval_days } => *interval_days as u64,
+        };
+        Some(days * crate::utils::constants::NANOS_PER_DAY)
+    }
+}
+
 #[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
 pub struct TransactionMetadata {
     pub invoice_id: Option<String>,
@@ -176,6 +318,9 @@ pub struct CreateTransactionRequest {
     pub deadline: Option<u64>,
     pub category: Option<TransactionCategory>,
     pub tags: Vec<String>,
+    // Share of the escrowed amount, in basis points, paid to the escrow
+    // agent when they resolve a dispute.
+    pub agent_fee_bps: Option<u64>,
 }
 
 #[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
@@ -200,6 +345,21 @@ pub struct TransactionFilter {
     pub date_range: Option<crate::types::common::TimeFilter>,
 }
 
+#[derive(Clone, Debug, Default, CandidType, Serialize, Deserialize, PartialEq)]
+pub struct EscrowSweepReport {
+    pub released: u32,
+    pub expired: u32,
+    pub warned: u32,
+    pub failed: u32,
+}
+
+#[derive(Clone, Debug, Default, CandidType, Serialize, Deserialize, PartialEq)]
+pub struct ScheduledPaymentReport {
+    pub executed: u32,
+    pub failed: u32,
+    pub finished: u32,
+}
+
 #[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
 pub struct TransactionStatistics {
     pub total_transactions: u64,
@@ -244,6 +404,17 @@ pub struct BalanceHistoryEntry {
     pub transaction_id: u64,
     pub transaction_type: TransactionType,
     pub description: String,
+    // `None` for entries recorded before balances were kept per currency (ICP).
+    pub currency: Option<Currency>,
+    // `None` for entries recorded before ledger settlement was tracked (completed).
+    pub status: Option<BalanceEntryStatus>,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize, PartialEq)]
+pub enum BalanceEntryStatus {
+    Pending,
+    Completed { ledger_block: Option<u64> },
+    Failed { reason: String },
 }
 
 impl Storable for BalanceHistoryEntry {
@@ -0,0 +1,89 @@
+use candid::{CandidType, Principal};
+use ic_stable_structures::Storable;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+use super::ledger::Account;
+use super::transaction::Currency;
+
+/// Platform fees held by the canister in one currency.
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize, PartialEq)]
+pub struct TreasuryBalance {
+    pub currency: Currency,
+    pub available: u64,
+    // Reserved while a withdrawal is waiting on the ledger.
+    pub pending_withdrawal: u64,
+    pub total_collected: u64,
+    pub total_withdrawn: u64,
+    pub updated_at: u64,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize, PartialEq)]
+pub enum FeeSource {
+    Completion,
+    ScheduledPayment,
+    DisputeResolution,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize, PartialEq)]
+pub enum TreasuryEntryKind {
+    FeeCollected { transaction_id: u64, source: FeeSource },
+    Withdrawal { to: Account, ledger_block: u64 },
+    ReferralReward { transaction_id: u64 },
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize, PartialEq)]
+pub struct TreasuryEntry {
+    pub id: u64,
+    pub kind: TreasuryEntryKind,
+    pub currency: Currency,
+    pub amount: u64,
+    // Payer of a fee, the admin who withdrew, or the referrer rewarded.
+    pub principal: Principal,
+    pub timestamp: u64,
+}
+
+/// Fee totals for one currency over `[period_start, period_end]`.
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize, PartialEq)]
+pub struct FeeReport {
+    pub currency: Currency,
+    pub period_start: u64,
+    pub period_end: u64,
+    pub fees_collected: u64,
+    pub fee_count: u64,
+    pub withdrawn: u64,
+    pub withdrawal_count: u64,
+    pub referral_rewards: u64,
+}
+
+impl Storable for TreasuryBalance {
+    const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
+        max_size: 256,
+        is_fixed_size: false,
+    };
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let bytes = serde_cbor::to_vec(self).expect("Failed to serialize TreasuryBalance");
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        serde_cbor::from_slice(&bytes).expect("Failed to deserialize TreasuryBalance")
+    }
+}
+
+impl Storable for TreasuryEntry {
+    const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
+        max_size: 512,
+        is_fixed_size: false,
+    };
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let bytes = serde_cbor::to_vec(self).expect("Failed to serialize TreasuryEntry");
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        serde_cbor::from_slice(&bytes).expect("Failed to deserialize TreasuryEntry")
+    }
+}
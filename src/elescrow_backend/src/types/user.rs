@@ -25,7 +25,8 @@ pub struct UserAccount {
     pub kyc_status: KycStatus,
 }
 
-#[derive(Clone, Debug, CandidType, Serialize, Deserialize, PartialEq)]
+// Declared from least to most verified; the derived ordering relies on it.
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub enum VerificationLevel {
     Basic,
     Standard,
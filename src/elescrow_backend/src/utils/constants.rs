@@ -11,6 +11,66 @@ pub const NANOS_PER_WEEK: u64 = 7 * NANOS_PER_DAY;
 pub const MIN_TRANSACTION_AMOUNT: u64 = 1_000;
 pub const MAX_TRANSACTION_AMOUNT: u64 = 1_000_000_000_000;
 pub const DEFAULT_TRANSACTION_FEE_BPS: u64 = 100;
+pub const DEFAULT_MAX_AGENT_FEE_BPS: u64 = 500;
+pub const MAX_FEE_TIERS: usize = 16;
+pub const MAX_FEE_PROMOTION_CURRENCIES: usize = 8;
+pub const DEFAULT_REFERRAL_FEE_SHARE_BPS: u64 = 2_000;
+pub const DEFAULT_REFERRAL_REWARDED_TRANSACTIONS: u32 = 5;
+pub const MAX_REFERRAL_CODES_PER_USER: usize = 5;
+pub const REFERRAL_CODE_LENGTH: usize = 8;
+pub const MAX_MILESTONES: usize = 10;
+pub const MAX_MILESTONE_DESCRIPTION_LENGTH: usize = 100;
+pub const MAX_MILESTONE_DISPUTE_REASON_LENGTH: usize = 200;
+pub const MAX_REFUNDS_PER_TRANSACTION: usize = 10;
+
+pub const ESCROW_SWEEP_INTERVAL: u64 = 5 * NANOS_PER_MINUTE;
+// Escrows acted on per sweep; the rest wait for the next tick.
+pub const ESCROW_SWEEP_BATCH_SIZE: usize = 50;
+pub const ESCROW_EXPIRY_WARNING: u64 = NANOS_PER_DAY;
+pub const INDEX_BACKFILL_BATCH_SIZE: u64 = 500;
+
+pub const SCHEDULED_PAYMENT_SWEEP_INTERVAL: u64 = NANOS_PER_MINUTE;
+// Payments run per sweep; the rest wait for the next tick.
+pub const SCHEDULED_PAYMENT_BATCH_SIZE: usize = 50;
+pub const SCHEDULED_PAYMENT_RETRY_DELAY: u64 = 6 * NANOS_PER_HOUR;
+pub const MAX_SCHEDULED_PAYMENT_RETRIES: u32 = 3;
+
+pub const MAX_EVIDENCE_FILE_SIZE: u64 = 10 * 1024 * 1024;
+pub const MAX_EVIDENCE_CHUNK_SIZE: usize = 1024 * 1024;
+pub const MAX_EVIDENCE_FILES_PER_TRANSACTION: usize = 20;
+pub const MAX_EVIDENCE_UPLOADS_IN_PROGRESS: usize = 3;
+pub const EVIDENCE_UPLOAD_TIMEOUT: u64 = NANOS_PER_DAY;
+pub const EVIDENCE_CLEANUP_INTERVAL: u64 = NANOS_PER_HOUR;
+pub const ALLOWED_EVIDENCE_MIME_TYPES: &[&str] = &[
+    "image/png",
+    "image/jpeg",
+    "image/gif",
+    "image/webp",
+    "application/pdf",
+    "text/plain",
+];
+
+// Default caps for Basic accounts, in units of 10^-8 of a token (ICP e8s) and
+// rescaled to each currency's decimals. Each tier above gets ten times more.
+pub const DEFAULT_BASIC_MAX_PER_TRANSACTION: u64 = 1_000_000_000;
+pub const DEFAULT_BASIC_DAILY_VOLUME: u64 = 2_000_000_000;
+pub const DEFAULT_BASIC_MONTHLY_VOLUME: u64 = 10_000_000_000;
+pub const DEFAULT_BASIC_DAILY_WITHDRAWAL: u64 = 1_000_000_000;
+pub const DEFAULT_BASIC_MONTHLY_WITHDRAWAL: u64 = 5_000_000_000;
+pub const TIER_LIMIT_MULTIPLIER: u64 = 10;
+pub const TIER_LIMIT_DEFAULT_DECIMALS: u8 = 8;
+pub const LIMIT_DAILY_WINDOW: u64 = NANOS_PER_DAY;
+pub const LIMIT_MONTHLY_WINDOW: u64 = 30 * NANOS_PER_DAY;
+
+pub const MAX_KYC_DOCUMENTS: usize = 8;
+pub const MAX_KYC_FILE_NAME_LENGTH: usize = 255;
+pub const MAX_KYC_DOCUMENT_TYPE_LENGTH: usize = 50;
+pub const MAX_KYC_REJECTION_REASON_LENGTH: usize = 500;
+pub const KYC_APPROVAL_VALIDITY: u64 = 365 * NANOS_PER_DAY;
+pub const KYC_EXPIRY_SWEEP_INTERVAL: u64 = NANOS_PER_HOUR;
+
+pub const DEFAULT_MAX_INGRESS_ARG_BYTES: usize = 8 * 1024;
+pub const MAX_CREATE_TRANSACTION_ARG_BYTES: usize = 16 * 1024;
 
 pub const MAX_USERNAME_LENGTH: usize = 30;
 pub const MIN_USERNAME_LENGTH: usize = 3;
@@ -26,6 +86,11 @@ pub const PASSWORD_MAX_LENGTH: usize = 128;
 pub const DEFAULT_RATE_LIMIT_REQUESTS: u32 = 100;
 pub const DEFAULT_RATE_LIMIT_WINDOW: u64 = NANOS_PER_HOUR;
 pub const DEFAULT_RATE_LIMIT_BLOCK_DURATION: u64 = 5 * NANOS_PER_MINUTE;
+pub const DEFAULT_MESSAGING_RATE_LIMIT_REQUESTS: u32 = 30;
+pub const DEFAULT_MESSAGING_RATE_LIMIT_WINDOW: u64 = NANOS_PER_MINUTE;
+pub const DEFAULT_SEARCH_RATE_LIMIT_REQUESTS: u32 = 60;
+pub const DEFAULT_SEARCH_RATE_LIMIT_WINDOW: u64 = NANOS_PER_MINUTE;
+pub const RATE_LIMIT_CLEANUP_INTERVAL: u64 = NANOS_PER_HOUR;
 
 pub const MAX_NOTIFICATIONS_PER_USER: usize = 1000;
 pub const NOTIFICATION_RETENTION_DAYS: u32 = 90;
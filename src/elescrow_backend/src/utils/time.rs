@@ -1,8 +1,25 @@
-use ic_cdk::api::time;
 use crate::utils::constants::*;
 
+#[cfg(not(test))]
 pub fn now() -> u64 {
-    time()
+    ic_cdk::api::time()
+}
+
+// `ic_cdk::api::time` traps outside a canister, so unit tests run against a
+// per-thread clock they can move forward explicitly.
+#[cfg(test)]
+thread_local! {
+    static MOCK_TIME: std::cell::Cell<u64> = const { std::cell::Cell::new(1_700_000_000 * NANOS_PER_SECOND) };
+}
+
+#[cfg(test)]
+pub fn now() -> u64 {
+    MOCK_TIME.with(|t| t.get())
+}
+
+#[cfg(test)]
+pub fn advance_mock_time(duration: u64) {
+    MOCK_TIME.with(|t| t.set(t.get() + duration));
 }
 
 pub fn nanos_to_seconds(nanos: u64) -> u64 {